@@ -0,0 +1,169 @@
+use linkerd_identity::Name;
+use std::{convert::Infallible, fmt, str::FromStr};
+
+/// Matches identities against an authorization policy.
+///
+/// Patterns are normalized like DNS names: they are compared
+/// case-insensitively, and a trailing dot is ignored.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum IdentityPattern {
+    /// Matches any identity (`*`).
+    Any,
+
+    /// Matches exactly one identity.
+    Exact(String),
+
+    /// Matches identities that are strictly within the given domain
+    /// (`.ns1.serviceaccount.identity.linkerd.cluster.local`), at any depth.
+    Suffix(String),
+
+    /// Matches identities that add exactly one label to the given domain
+    /// (`*.ns1.serviceaccount.identity.linkerd.cluster.local`).
+    Wildcard(String),
+}
+
+/// Returns true if `name` matches any of the `allowed` patterns.
+pub fn identity_authorized(name: &Name, allowed: &[IdentityPattern]) -> bool {
+    let name = normalize(name.as_str());
+    allowed.iter().any(|p| p.matches_normalized(&name))
+}
+
+fn normalize(name: &str) -> String {
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
+
+// === impl IdentityPattern ===
+
+impl IdentityPattern {
+    pub fn matches(&self, name: &Name) -> bool {
+        self.matches_normalized(&normalize(name.as_str()))
+    }
+
+    /// Like `matches`, for DNS names that may not be valid identities (e.g.
+    /// wildcard SANs).
+    pub(crate) fn matches_dns_name(&self, name: &str) -> bool {
+        self.matches_normalized(&normalize(name))
+    }
+
+    fn matches_normalized(&self, name: &str) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Exact(exact) => name == normalize(exact),
+            Self::Suffix(suffix) => match name.strip_suffix(&*normalize(suffix)) {
+                Some(prefix) => prefix.len() > 1 && prefix.ends_with('.'),
+                None => false,
+            },
+            Self::Wildcard(suffix) => match name.strip_suffix(&*normalize(suffix)) {
+                Some(prefix) => match prefix.strip_suffix('.') {
+                    Some(label) => !label.is_empty() && !label.contains('.'),
+                    None => false,
+                },
+                None => false,
+            },
+        }
+    }
+}
+
+/// Parses `*` as [`IdentityPattern::Any`], `*.<domain>` as a wildcard,
+/// `.<domain>` as a suffix, and anything else as an exact identity.
+impl FromStr for IdentityPattern {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim_end_matches('.').to_ascii_lowercase();
+        if s == "*" {
+            return Ok(Self::Any);
+        }
+        if let Some(suffix) = s.strip_prefix("*.") {
+            return Ok(Self::Wildcard(suffix.to_string()));
+        }
+        if let Some(suffix) = s.strip_prefix('.') {
+            return Ok(Self::Suffix(suffix.to_string()));
+        }
+        Ok(Self::Exact(s))
+    }
+}
+
+impl fmt::Display for IdentityPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Any => f.write_str("*"),
+            Self::Exact(name) => f.write_str(name),
+            Self::Suffix(suffix) => write!(f, ".{}", suffix),
+            Self::Wildcard(suffix) => write!(f, "*.{}", suffix),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(s: &str) -> Name {
+        s.parse().expect("name must be valid")
+    }
+
+    fn patterns(ps: &[&str]) -> Vec<IdentityPattern> {
+        ps.iter().map(|p| p.parse().unwrap()).collect()
+    }
+
+    const FOO_NS1: &str = "foo.ns1.serviceaccount.identity.linkerd.cluster.local";
+
+    #[test]
+    fn exact() {
+        let allowed = patterns(&["FOO.ns1.serviceaccount.identity.linkerd.cluster.local."]);
+        assert!(identity_authorized(&name(FOO_NS1), &allowed));
+        assert!(!identity_authorized(
+            &name("bar.ns1.serviceaccount.identity.linkerd.cluster.local"),
+            &allowed
+        ));
+        assert!(!identity_authorized(
+            &name("x.foo.ns1.serviceaccount.identity.linkerd.cluster.local"),
+            &allowed
+        ));
+    }
+
+    #[test]
+    fn suffix() {
+        let allowed = patterns(&[".ns1.serviceaccount.identity.linkerd.cluster.local"]);
+        assert!(identity_authorized(&name(FOO_NS1), &allowed));
+        assert!(identity_authorized(
+            &name("a.b.ns1.serviceaccount.identity.linkerd.cluster.local"),
+            &allowed
+        ));
+        // The domain itself and names that merely end with the same characters
+        // are not within it.
+        assert!(!identity_authorized(
+            &name("ns1.serviceaccount.identity.linkerd.cluster.local"),
+            &allowed
+        ));
+        assert!(!identity_authorized(
+            &name("foo.xns1.serviceaccount.identity.linkerd.cluster.local"),
+            &allowed
+        ));
+    }
+
+    #[test]
+    fn wildcard() {
+        let allowed = patterns(&["*.NS1.serviceaccount.identity.linkerd.cluster.local"]);
+        assert!(identity_authorized(&name(FOO_NS1), &allowed));
+        assert!(!identity_authorized(
+            &name("a.b.ns1.serviceaccount.identity.linkerd.cluster.local"),
+            &allowed
+        ));
+        assert!(!identity_authorized(
+            &name("ns1.serviceaccount.identity.linkerd.cluster.local"),
+            &allowed
+        ));
+        assert!(!identity_authorized(
+            &name("foo.ns2.serviceaccount.identity.linkerd.cluster.local"),
+            &allowed
+        ));
+    }
+
+    #[test]
+    fn any() {
+        assert!(identity_authorized(&name(FOO_NS1), &patterns(&["*"])));
+        assert!(!identity_authorized(&name(FOO_NS1), &[]));
+    }
+}
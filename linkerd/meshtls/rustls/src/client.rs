@@ -1,3 +1,4 @@
+use crate::{ResolvedIdentity, TlsSummary};
 use futures::prelude::*;
 use linkerd_io as io;
 use linkerd_stack::{NewService, Service};
@@ -25,7 +26,7 @@ pub type ConnectFuture<I> = futures::future::MapOk<
 >;
 
 #[derive(Debug)]
-pub struct ClientIo<I>(tokio_rustls::client::TlsStream<I>);
+pub struct ClientIo<I>(tokio_rustls::client::TlsStream<I>, Option<ResolvedIdentity>);
 
 // === impl NewClient ===
 
@@ -33,6 +34,11 @@ impl NewClient {
     pub(crate) fn new(config: watch::Receiver<Arc<ClientConfig>>) -> Self {
         Self { config }
     }
+
+    #[cfg(any(test, feature = "test-util"))]
+    pub(crate) fn config(&self) -> Arc<ClientConfig> {
+        (*self.config.borrow()).clone()
+    }
 }
 
 impl NewService<ClientTls> for NewClient {
@@ -91,7 +97,7 @@ where
         tokio_rustls::TlsConnector::from(self.config.clone())
             // XXX(eliza): it's a bummer that the server name has to be cloned here...
             .connect(self.server_id.clone(), io)
-            .map_ok(ClientIo)
+            .map_ok(ClientIo::new)
     }
 }
 
@@ -140,6 +146,17 @@ impl<I: io::AsyncRead + io::AsyncWrite + Unpin> io::AsyncWrite for ClientIo<I> {
 }
 
 impl<I> ClientIo<I> {
+    fn new(io: tokio_rustls::client::TlsStream<I>) -> Self {
+        let server_id = ResolvedIdentity::from_peer_certs(io.get_ref().1.peer_certificates());
+        Self(io, server_id)
+    }
+
+    /// Returns the server's identity, as verified during the handshake.
+    #[inline]
+    pub fn server_identity(&self) -> Option<&ResolvedIdentity> {
+        self.1.as_ref()
+    }
+
     #[inline]
     pub fn negotiated_protocol(&self) -> Option<NegotiatedProtocolRef<'_>> {
         self.0
@@ -148,6 +165,11 @@ impl<I> ClientIo<I> {
             .alpn_protocol()
             .map(NegotiatedProtocolRef)
     }
+
+    /// Summarizes what the handshake negotiated.
+    pub fn tls_summary(&self) -> TlsSummary {
+        TlsSummary::new(self.0.get_ref().1)
+    }
 }
 
 impl<I: io::PeerAddr> io::PeerAddr for ClientIo<I> {
@@ -1,106 +1,1497 @@
+mod bootstrap;
+mod builder;
+mod csr;
+mod metrics;
+mod ocsp;
 mod receiver;
 mod store;
+mod verify;
+mod x509;
 
-pub use self::{receiver::Receiver, store::Store};
+pub(crate) use self::receiver::ServerState;
+pub use self::{
+    builder::CredentialsBuilder,
+    metrics::{Metrics, ResolutionFailures},
+    ocsp::{OcspFetcher, OcspResponse},
+    receiver::{Leaf, Readiness, Receiver},
+    store::{
+        ClientHelloRecord, Install, InstallRecord, RotationInfo, Store, TrustRootsInfo, TrustedRoot,
+    },
+};
 use linkerd_error::Result;
 use linkerd_identity as id;
-use ring::{error::KeyRejected, signature::EcdsaKeyPair};
+use ring::{
+    error::KeyRejected,
+    signature::{EcdsaKeyPair, Ed25519KeyPair, RsaKeyPair},
+};
 use std::sync::Arc;
 use thiserror::Error;
 use tokio::sync::watch;
 use tokio_rustls::rustls;
 use tracing::warn;
 
+/// Describes why trust roots, a private key, or a CSR could not be loaded,
+/// e.g. by `watch`.
+///
+/// Errors are returned boxed, so callers may inspect them with
+/// `Error::downcast_ref` (e.g. to decide whether loading may be retried once
+/// the inputs are updated).
+#[derive(Debug, Error)]
+pub enum WatchError {
+    /// The trust anchors could not be parsed as PEM.
+    #[error("invalid trust anchors PEM: {0}")]
+    PemParse(#[source] std::io::Error),
+
+    /// The PEM bundle contains no certificates.
+    #[error("no trust roots in PEM file")]
+    NoTrustRoots,
+
+    /// None of the certificates in the PEM bundle are valid trust anchors.
+    #[error("all {skipped} trust anchors in PEM file are invalid")]
+    AllAnchorsSkipped { skipped: usize },
+
+    /// The private key PEM could not be parsed.
+    #[error("invalid private key PEM: {0}")]
+    KeyPemParse(#[source] std::io::Error),
+
+    /// The private key PEM contains no `PRIVATE KEY`, `EC PRIVATE KEY`, or
+    /// `RSA PRIVATE KEY` section.
+    #[error("no private key in PEM file")]
+    NoPrivateKey,
+
+    /// The private key PEM contains more than one key.
+    #[error("{count} private keys in PEM file, but only one may be used")]
+    MultiplePrivateKeys { count: usize },
+
+    /// The private key is encrypted, but no password is configured.
+    #[error("private key is encrypted, but no key password is configured")]
+    KeyPasswordRequired,
+
+    /// A password is configured, but the private key is not encrypted.
+    #[error("a key password is configured, but the private key is not encrypted")]
+    KeyNotEncrypted,
+
+    #[error(transparent)]
+    KeyDecryption(#[from] KeyDecryption),
+
+    #[error(transparent)]
+    InvalidKey(#[from] InvalidKey),
+
+    #[error(transparent)]
+    UnsupportedKeyAlgorithm(#[from] UnsupportedKeyAlgorithm),
+
+    /// An external key's SubjectPublicKeyInfo could not be parsed.
+    #[error("invalid SubjectPublicKeyInfo for external key")]
+    InvalidPublicKey,
+
+    /// The certificate signing request PEM could not be parsed.
+    #[error("invalid certificate signing request PEM: {0}")]
+    CsrPemParse(#[source] std::io::Error),
+
+    /// The certificate signing request PEM contains no `CERTIFICATE REQUEST`
+    /// section.
+    #[error("no certificate signing request in PEM file")]
+    NoCsr,
+
+    /// The certificate signing request PEM contains more than one request.
+    #[error("{count} certificate signing requests in PEM file, but only one may be used")]
+    MultipleCsrs { count: usize },
+
+    /// The certificate signing request is neither PEM nor a DER-encoded
+    /// PKCS #10 request, so the identity service couldn't sign it.
+    #[error("certificate signing request is not a PKCS #10 request")]
+    InvalidCsr,
+}
+
 #[derive(Debug, Error)]
 #[error(transparent)]
 pub struct InvalidKey(KeyRejected);
 
+#[derive(Debug, Error)]
+#[error("private key could not be decrypted (is the password correct?): {0}")]
+pub struct KeyDecryption(pkcs8::Error);
+
+#[derive(Debug, Error)]
+#[error("unsupported private key algorithm: {0}")]
+pub struct UnsupportedKeyAlgorithm(&'static str);
+
 #[derive(Debug, Error)]
 #[error("invalid trust roots")]
 pub struct InvalidTrustRoots(());
 
+#[derive(Debug, Error)]
+#[error("certificate {index} in the chain lacks the {extension} extension")]
+pub struct MissingKeyIdentifier {
+    index: usize,
+    extension: &'static str,
+}
+
+#[derive(Debug, Error)]
+#[error("issued certificate is not valid for our identity {identity}; it names {sans:?}")]
+pub struct IdentityMismatch {
+    identity: id::Name,
+    sans: Vec<String>,
+}
+
+#[derive(Debug, Error)]
+#[error("certificate {index} in the chain permits {path_len} intermediate CAs beneath it, but {depth} are present")]
+pub struct PathLenExceeded {
+    index: usize,
+    path_len: u64,
+    depth: usize,
+}
+
+#[derive(Debug, Error)]
+#[error("certificate {index} in the chain is not issued by the certificate after it; its issuer is {issuer:?}")]
+pub struct MisorderedChain {
+    index: usize,
+    issuer: String,
+}
+
+#[derive(Debug, Error)]
+#[error("certificate {index} in the chain is not issued by a trust root; its issuer is {issuer:?}")]
+pub struct UntrustedChain {
+    index: usize,
+    issuer: String,
+}
+
+#[derive(Debug, Error)]
+#[error("issued certificate names {issued:?}, but {requested:?} were requested")]
+pub struct CsrSanMismatch {
+    requested: Vec<String>,
+    issued: Vec<String>,
+}
+
+#[derive(Debug, Error)]
+#[error(
+    "issued certificate is not valid until {}; it is now {}",
+    x509::format_time(.not_before),
+    x509::format_time(.now)
+)]
+pub struct NotYetValid {
+    not_before: std::time::SystemTime,
+    now: std::time::SystemTime,
+}
+
+#[derive(Debug, Error)]
+#[error(
+    "issued certificate expired at {}; it is now {}",
+    x509::format_time(.not_after),
+    x509::format_time(.now)
+)]
+pub struct Expired {
+    not_after: std::time::SystemTime,
+    now: std::time::SystemTime,
+}
+
+#[derive(Debug, Error)]
+#[error("peer certificate serial number {0} is not allowed")]
+pub struct SerialNotAllowed(String);
+
+#[derive(Debug, Error)]
+#[error("peer certificate names {0:?}, which is not in a permitted domain")]
+pub struct DnsNameNotPermitted(String);
+
+#[derive(Debug, Error)]
+#[error("peer certificate {index} in the chain is signed with {algorithm}, which is not allowed")]
+pub struct SignatureAlgorithmNotAllowed {
+    index: usize,
+    algorithm: String,
+}
+
+#[derive(Debug, Error)]
+#[error("peer certificate is valid for {lifetime:?}, longer than the allowed {max:?}")]
+pub struct PeerLifetimeExceeded {
+    lifetime: std::time::Duration,
+    max: std::time::Duration,
+}
+
+#[derive(Debug, Error)]
+#[error("peer presented {count} intermediate certificates, but at most {max} are allowed")]
+pub struct TooManyIntermediates {
+    count: usize,
+    max: usize,
+}
+
+#[derive(Debug, Error)]
+#[error("peer certificate is not issued through a pinned intermediate")]
+pub struct IntermediateNotPinned(());
+
+#[derive(Debug, Error)]
+#[error("issued certificate is not for our private key")]
+pub struct KeyMismatch(());
+
+#[derive(Debug, Error)]
+#[error("issued certificate's public key is {certificate}, but our private key is {key}")]
+pub struct CertKeyAlgorithmMismatch {
+    certificate: &'static str,
+    key: &'static str,
+}
+
+#[derive(Debug, Error)]
+#[error("certificate signing request is empty")]
+pub struct EmptyCsr(());
+
+#[derive(Debug, Error)]
+#[error("certificate signing request is not for our private key")]
+pub struct CsrKeyMismatch(());
+
+#[derive(Debug, Error)]
+#[error("certificate chain is empty")]
+pub struct EmptyChain(());
+
+#[derive(Debug, Error)]
+#[error("certificate {0} in the chain is empty")]
+pub struct EmptyCertificate(usize);
+
+#[derive(Debug, Error)]
+#[error("no certificate has been installed")]
+pub struct NoCertificate(());
+
+#[derive(Debug, Error)]
+#[error("the server resolver does not select the installed certificate for {0}")]
+pub struct UnresolvableIdentity(id::Name);
+
+#[derive(Debug, Error)]
+#[error("{0} is the store's own identity, whose certificate is set by `set_certificate`")]
+pub struct OwnIdentity(id::Name);
+
+#[derive(Debug, Error)]
+#[error("certificates for {max} other identities are installed, which is the most permitted")]
+pub struct TooManyIdentities {
+    max: usize,
+}
+
+#[derive(Debug, Error)]
+#[error("{0} is not approved for use in FIPS mode")]
+pub struct NotFipsApproved(CipherSuite);
+
+#[derive(Debug, Error)]
+#[error("{0} requires TLS 1.2 to be enabled")]
+pub struct Tls12NotEnabled(CipherSuite);
+
+#[derive(Debug, Error)]
+#[error("no cipher suites are enabled")]
+pub struct NoCipherSuites(());
+
+#[derive(Debug, Error)]
+#[error("unknown cipher suite {0:?}")]
+pub struct UnknownCipherSuite(String);
+
+#[derive(Debug, Error)]
+#[error("no signature algorithms are allowed")]
+pub struct NoSignatureAlgorithms(());
+
+#[derive(Debug, Error)]
+#[error("no DNS domains are permitted")]
+pub struct NoPermittedDnsDomains(());
+
+#[derive(Debug, Error)]
+#[error("client CA hint {0} is not a valid certificate")]
+pub struct InvalidClientCaHint(usize);
+
+#[derive(Debug, Error)]
+#[error(
+    "protocol versions {0:?} are not supported; TLS 1.3 must be enabled, optionally with TLS 1.2"
+)]
+pub struct UnsupportedProtocolVersions(Vec<rustls::ProtocolVersion>);
+
+#[derive(Debug, Error)]
+#[error("credentials require the {0} to be set")]
+pub struct MissingField(&'static str);
+
+#[derive(Debug, Error)]
+#[error("invalid {field}: {source}")]
+pub struct InvalidField {
+    field: &'static str,
+    #[source]
+    source: linkerd_error::Error,
+}
+
+/// Optional settings that control how credentials are validated and used.
+#[derive(Clone, Default)]
+pub struct Options {
+    /// Determines how certificates that lack the Subject and Authority Key
+    /// Identifier extensions are handled when they are installed.
+    pub key_identifiers: KeyIdentifierPolicy,
+
+    /// Determines how certificates whose DNS SANs differ from those requested
+    /// by the CSR are handled when they are installed.
+    pub csr_sans: CsrSanPolicy,
+
+    /// Records metrics about the credential store.
+    pub metrics: Metrics,
+
+    /// Stores client sessions for resumption.
+    ///
+    /// When set, client connections may resume sessions stored here (for
+    /// instance, in a cache shared by several connection pools). Otherwise,
+    /// client session resumption is disabled unless `session_resumption` is
+    /// enabled.
+    ///
+    /// Note that rustls does not expose an encoding for the session values it
+    /// hands to the store, so sessions cannot be exported to bytes and restored
+    /// by a successor process; a store may only share sessions within a
+    /// process.
+    pub client_sessions: Option<Arc<dyn rustls::client::ClientSessionStore>>,
+
+    /// Enables TLS session resumption, which is disabled by default.
+    ///
+    /// When enabled, clients cache sessions in memory (unless
+    /// `client_sessions` is set) and servers issue stateless session tickets.
+    /// The client cache holds sessions for up to 256 servers, each with up to
+    /// 8 TLS 1.3 tickets. Each ticket retains the server's certificate chain,
+    /// so with typical mesh certificates (1-2 KiB) the cache may grow to about
+    /// 4 MiB. Tickets are encrypted with keys that rotate every 6 hours, so
+    /// servers only retain two keys rather than any per-session state.
+    pub session_resumption: bool,
+
+    /// The ALPN protocols offered by client configurations and accepted by
+    /// server configurations, in order of preference (e.g. `h2`, then
+    /// `http/1.1`).
+    ///
+    /// These apply to every configuration the store publishes, including
+    /// those published before a certificate is installed, until they are
+    /// replaced with `Store::set_alpn`. When empty (the default), ALPN is not
+    /// negotiated.
+    pub alpn_protocols: Vec<Vec<u8>>,
+
+    /// When set, every client and server certificate resolution decision is
+    /// logged at the TRACE level, including the SNI, offered signature schemes,
+    /// and acceptable issuers.
+    ///
+    /// This is intended for debugging certificate selection and is noisy.
+    pub trace_resolver: bool,
+
+    /// When set, servers present their certificate to clients that send no
+    /// SNI, rather than failing the handshake (e.g. for legacy clients that
+    /// connect by address). Clients that send SNI must still request our
+    /// identity.
+    ///
+    /// This should only be set when a listener serves a single identity:
+    /// clients without SNI are served our own certificate even when others
+    /// are installed with `Store::set_certificate_for`.
+    pub serve_without_sni: bool,
+
+    /// When set, limits the number of other identities whose certificates
+    /// may be installed with `Store::set_certificate_for`. Replacing an
+    /// installed identity's certificate doesn't count against the limit.
+    ///
+    /// When unset, any number of identities may be installed.
+    pub max_identities: Option<usize>,
+
+    /// Determines how a certificate for a new identity is handled once
+    /// `max_identities` are installed.
+    pub identity_limit: IdentityLimit,
+
+    /// The cipher suites to enable, in order of preference.
+    ///
+    /// When unset, ChaCha20-Poly1305 is used (or AES-GCM in FIPS mode).
+    /// Credentials fail to load if the list is empty. Suites may be parsed
+    /// from their IANA names (e.g. `TLS13_AES_128_GCM_SHA256`).
+    pub cipher_suites: Option<Vec<CipherSuite>>,
+
+    /// Restricts TLS configurations to FIPS-approved algorithms.
+    ///
+    /// In FIPS mode, only the AES-GCM cipher suites and the P-256 and P-384
+    /// key exchange groups are enabled, and credentials fail to load if any
+    /// other cipher suite is configured.
+    pub fips: bool,
+
+    /// Enables TLS 1.2 alongside TLS 1.3, for peers that don't support TLS 1.3.
+    ///
+    /// When enabled, the ECDHE ChaCha20-Poly1305 TLS 1.2 suites are also
+    /// enabled by default (or the ECDHE AES-GCM suites in FIPS mode).
+    ///
+    /// When disabled (the default), credentials fail to load if a TLS 1.2
+    /// suite is configured, and handshakes with peers that only support TLS
+    /// 1.2 fail: a server rejects their ClientHellos with a `protocol_version`
+    /// alert, and a client fails when such a server rejects its ClientHello.
+    /// The connection's I/O error wraps the `rustls::Error` (e.g.
+    /// `PeerIncompatible` on the server, `AlertReceived(ProtocolVersion)` on
+    /// the client).
+    pub tls12: bool,
+
+    /// Called with a record of each certificate that is installed, e.g. for
+    /// audit logging.
+    pub on_install: Option<Arc<dyn Fn(&InstallRecord) + Send + Sync>>,
+
+    /// Called with the new leaf's expiry each time a certificate is rotated
+    /// in, e.g. to export "seconds until expiry" and rotation counts.
+    ///
+    /// Unlike `on_install`, this is only called once both the client and
+    /// server configurations presenting the certificate have been published
+    /// to receivers; it is never called for certificates that fail
+    /// validation.
+    pub on_rotation: Option<Arc<dyn Fn(std::time::SystemTime) + Send + Sync>>,
+
+    /// When set, peers are only accepted if, in addition to being valid, their
+    /// leaf certificates have one of these (big-endian) serial numbers.
+    pub allowed_serials: Option<Vec<Vec<u8>>>,
+
+    /// When set, OCSP responses are fetched from the responder named by each
+    /// installed certificate and stapled to it when it is served.
+    ///
+    /// Responses are fetched in the background once a certificate is installed
+    /// and refreshed before they become stale. Certificates are served without
+    /// a staple until a response is available or if fetching fails.
+    pub ocsp_fetcher: Option<Arc<dyn OcspFetcher>>,
+
+    /// Returns the current time when validating certificates as they are
+    /// installed (or verified with `Store::verify_peer` and
+    /// `Store::verify_with_pool`), when determining whether the installed
+    /// certificate has expired (including whether clients may still present
+    /// it), and when determining whether a fetched OCSP response is fresh
+    /// enough to staple, e.g. so that tests may simulate any point in time.
+    ///
+    /// Peers' certificates are verified during handshakes against the system
+    /// clock, which rustls reads itself. When unset, the system clock is used
+    /// throughout.
+    pub clock: Option<Arc<dyn Fn() -> std::time::SystemTime + Send + Sync>>,
+
+    /// The trust domain that installed certificates are expected to belong
+    /// to, e.g. `cluster.local`.
+    ///
+    /// When set, a warning is logged if an installed certificate names neither
+    /// a DNS name nor a SPIFFE ID in this domain. This often indicates that
+    /// the trust roots were loaded for another trust domain, even when the
+    /// chain validates (e.g. because the roots are federated).
+    pub trust_domain: Option<String>,
+
+    /// Called with a summary of each ClientHello received by servers, e.g.
+    /// to capture exactly what peers offered when debugging interoperability.
+    ///
+    /// This is disabled by default. Rustls does not expose the raw bytes of
+    /// handshake messages to resolvers or verifiers (nor the ServerHello to
+    /// clients), so only the fields that it parses are recorded.
+    pub on_client_hello: Option<Arc<dyn Fn(&ClientHelloRecord) + Send + Sync>>,
+
+    /// When set, servers are accepted with identities in deprecated trust
+    /// domains while a trust domain is renamed.
+    pub trust_domain_migration: Option<TrustDomainMigration>,
+
+    /// When set, until a certificate is installed, servers present an
+    /// ephemeral self-signed certificate to clients that request this name via
+    /// SNI (e.g. for bootstrap endpoints that need TLS before the identity is
+    /// provisioned). Handshakes for other names fail until then.
+    ///
+    /// The certificate and its key are generated when credentials are
+    /// created and are never used for the mesh identity.
+    pub bootstrap_server_name: Option<String>,
+
+    /// When set, peers are only accepted if, in addition to being valid, their
+    /// leaf certificates' validity periods (from notBefore to notAfter) are no
+    /// longer than this, e.g. to enforce the use of short-lived certificates.
+    pub max_peer_lifetime: Option<std::time::Duration>,
+
+    /// When set, peers are only accepted if they present no more than this
+    /// many intermediate certificates alongside their leaves. Chains are
+    /// counted as presented, before they are validated.
+    ///
+    /// When unset, any number of intermediates is accepted.
+    pub max_intermediates: Option<usize>,
+
+    /// The SHA-256 digests of the SubjectPublicKeyInfos of intermediates
+    /// that peers' chains must pass through (see `spki_sha256`).
+    ///
+    /// When set, peers are only accepted if, in addition to being valid, their
+    /// leaf certificates are issued (directly or through other intermediates)
+    /// by one of the intermediates they present whose key is pinned. When
+    /// empty, no intermediates are pinned.
+    pub pinned_intermediates: Vec<[u8; 32]>,
+
+    /// When set, peers are only accepted if, in addition to being valid, their
+    /// leaf certificates and the intermediates they present are signed with
+    /// one of these algorithms (e.g. to reject SHA-1 and PKCS #1 signatures).
+    ///
+    /// This constrains the signatures on certificates, not those made during
+    /// handshakes. Credentials fail to load if the list is empty.
+    pub signature_algorithms: Option<Vec<SignatureAlgorithm>>,
+
+    /// When set, a CSR is generated for the private key from this template,
+    /// and the CSR passed to `watch_with_options` is ignored (so it may be
+    /// empty).
+    pub generate_csr: Option<CsrTemplate>,
+
+    /// The password that the private key is encrypted with, if it is a PKCS #8
+    /// `EncryptedPrivateKeyInfo` (e.g. as written by `openssl pkcs8 -topk8`).
+    ///
+    /// PBES2 encryption with PBKDF2 or scrypt and AES-CBC is supported.
+    /// Credentials fail to load if the key is encrypted and no password is
+    /// set, or if a password is set for a key that isn't encrypted. The
+    /// password is only used to decrypt the key, and is not retained.
+    pub key_password: Option<Vec<u8>>,
+
+    /// How long before the installed certificate expires to warn that it is
+    /// about to lapse (e.g. because the identity controller has stalled).
+    ///
+    /// The expiry is checked as each certificate is installed and whenever
+    /// `Store::check_expiry` is called, against the configured `clock`. When
+    /// unset, the threshold is 24 hours; a zero threshold only warns once the
+    /// certificate has expired.
+    pub expiry_warning: Option<std::time::Duration>,
+
+    /// Called with the installed certificate's expiry whenever an expiry
+    /// warning is logged, e.g. to alert on impending expiry.
+    pub on_expiry_warning: Option<Arc<dyn Fn(std::time::SystemTime) + Send + Sync>>,
+
+    /// Determines whether servers require clients to present certificates.
+    pub client_auth: ClientAuth,
+
+    /// When set, servers are only accepted if, in addition to being valid,
+    /// every DNS SAN in their leaf certificates is within one of these
+    /// domains (e.g. to enforce a root's name constraints explicitly).
+    ///
+    /// A domain such as `svc.cluster.local` permits itself and any name
+    /// within it; `*.svc.cluster.local` only permits names within it, at any
+    /// depth. Names are compared case-insensitively, at label boundaries, so
+    /// `svc.cluster.local` doesn't permit `xsvc.cluster.local`. Credentials
+    /// fail to load if the list is empty.
+    pub permitted_dns_domains: Option<Vec<String>>,
+
+    /// The DER-encoded certificates of the CAs that servers advertise to
+    /// clients when requesting their certificates, if curated.
+    ///
+    /// By default, servers advertise the subject of every trust root, which
+    /// makes the CertificateRequest grow with the root store. These hints
+    /// only help clients to choose a certificate: clients are still verified
+    /// against all of the trust roots, whether or not their issuers are
+    /// advertised. An empty list advertises no CAs.
+    pub client_ca_hints: Option<Vec<Vec<u8>>>,
+
+    /// When set, handshake signatures made with RSA keys, which are far more
+    /// expensive than ECDSA or Ed25519 signatures, are computed with
+    /// `tokio::task::block_in_place` so that they don't stall other tasks on
+    /// the runtime's worker thread.
+    ///
+    /// This only applies on multi-threaded runtimes; elsewhere, and for other
+    /// keys, signatures are computed inline. Either way, each signature is
+    /// returned to rustls (or fails) before the handshake proceeds.
+    pub offload_rsa_signatures: bool,
+}
+
+/// Describes a certificate signing request that is generated for the private
+/// key (see `Options::generate_csr`).
+///
+/// The request is signed with the algorithm that corresponds to the key's
+/// type (e.g. ecdsa-with-SHA256 for P-256 keys).
+#[derive(Clone, Debug, Default)]
+pub struct CsrTemplate {
+    /// The subject's common name.
+    ///
+    /// When unset, the identity name is used. When empty, the subject is
+    /// empty.
+    pub common_name: Option<String>,
+
+    /// The DNS names requested in the Subject Alternative Name extension.
+    ///
+    /// When empty, only the identity name is requested.
+    pub dns_names: Vec<String>,
+}
+
+/// Describes the rename of a trust domain.
+///
+/// While a trust domain is renamed, peers may present identities from either
+/// domain. Until the migration ends, a server that is expected to have an
+/// identity in `trust_domain` is also accepted if it presents the same
+/// identity in one of the `deprecated` domains, and a warning is logged.
+/// Afterwards, only `trust_domain` is accepted.
+///
+/// Client identities are not constrained by name, so clients with identities
+/// in any trust domain are accepted as long as they chain to the trust roots.
+#[derive(Clone, Debug)]
+pub struct TrustDomainMigration {
+    /// The trust domain being migrated to, e.g. `cluster.local`.
+    pub trust_domain: String,
+
+    /// The trust domains being migrated from.
+    pub deprecated: Vec<String>,
+
+    /// The time when deprecated trust domains are no longer accepted.
+    pub until: std::time::SystemTime,
+}
+
+/// A cipher suite that may be enabled on client and server configurations.
+///
+/// Every TLS 1.3 suite is forward secret, since key exchange is negotiated
+/// separately (over the configured ECDHE groups). Only ECDHE suites are
+/// offered for TLS 1.2, and rustls implements no static-RSA key exchange in
+/// any case, so there are no non-PFS suites that could be configured or would
+/// need to be filtered out.
+///
+/// The TLS 1.2 suites may only be enabled along with TLS 1.2 (see
+/// `Options::tls12`). Their ECDSA and RSA variants are only negotiated with
+/// keys of the corresponding type (ECDSA suites also serve Ed25519 keys).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CipherSuite {
+    Tls13Aes128GcmSha256,
+    Tls13Aes256GcmSha384,
+    Tls13Chacha20Poly1305Sha256,
+    Tls12EcdheEcdsaWithAes128GcmSha256,
+    Tls12EcdheEcdsaWithAes256GcmSha384,
+    Tls12EcdheEcdsaWithChacha20Poly1305Sha256,
+    Tls12EcdheRsaWithAes128GcmSha256,
+    Tls12EcdheRsaWithAes256GcmSha384,
+    Tls12EcdheRsaWithChacha20Poly1305Sha256,
+}
+
+/// An algorithm that certificates may be signed with.
+///
+/// Webpki never accepts the SHA-1 algorithms; they are named so that peers
+/// presenting them are rejected with a clear error when signature algorithms
+/// are restricted (see `Options::signature_algorithms`).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SignatureAlgorithm {
+    EcdsaSha1,
+    EcdsaSha256,
+    EcdsaSha384,
+    EcdsaSha512,
+    Ed25519,
+    RsaPkcs1Sha1,
+    RsaPkcs1Sha256,
+    RsaPkcs1Sha384,
+    RsaPkcs1Sha512,
+    RsaPssSha256,
+    RsaPssSha384,
+    RsaPssSha512,
+}
+
+/// Determines whether servers require clients to authenticate.
+///
+/// Either way, clients that present certificates must present valid ones.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ClientAuth {
+    /// Clients may connect anonymously, e.g. so that unmeshed clients can
+    /// reach the proxy.
+    #[default]
+    Optional,
+
+    /// Handshakes fail if the client doesn't present a certificate.
+    Required,
+}
+
+/// Determines how installed certificates that lack the Subject Key Identifier
+/// or Authority Key Identifier extensions are handled.
+///
+/// Some strict verifiers rely on these extensions to build chains, so
+/// certificates that omit them may be accepted by this proxy but rejected by
+/// its peers.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum KeyIdentifierPolicy {
+    /// Key identifiers are not checked.
+    #[default]
+    Ignore,
+
+    /// A warning is logged for each certificate that lacks key identifiers.
+    Warn,
+
+    /// Certificates that lack key identifiers are rejected.
+    Require,
+}
+
+/// Determines how installed certificates whose DNS SANs differ from those
+/// requested by the CSR are handled.
+///
+/// A divergence indicates that the CA overrode the request (e.g. by policy) or
+/// misbehaved.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum CsrSanPolicy {
+    /// SANs are not compared.
+    #[default]
+    Ignore,
+
+    /// A warning is logged when the SANs differ.
+    Warn,
+
+    /// Certificates whose SANs differ (or whose CSR cannot be read) are
+    /// rejected.
+    Require,
+}
+
+/// Determines how `Store::set_certificate_for` handles a certificate for a new
+/// identity once `Options::max_identities` are installed.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum IdentityLimit {
+    /// The certificate is rejected.
+    #[default]
+    Reject,
+
+    /// The identity whose certificate a server resolved least recently is
+    /// evicted to make room, unless it is being presented by a handshake. If
+    /// every installed identity's certificate is being presented, the new
+    /// certificate is rejected.
+    EvictLeastRecentlyUsed,
+}
+
+/// A private key that is held outside of the process, e.g. by an HSM or a
+/// PKCS #11 token, so that the store never holds key material (see
+/// `watch_with_external_key`).
+pub trait ExternalKey: Send + Sync + 'static {
+    /// Returns the key's DER-encoded SubjectPublicKeyInfo.
+    ///
+    /// Its algorithm determines the schemes the key signs with: ECDSA (P-256
+    /// and P-384), Ed25519, and RSA keys are supported, as for keys loaded by
+    /// `watch`.
+    fn spki(&self) -> &[u8];
+
+    /// Signs `message` with `scheme`, one of the key's schemes, returning the
+    /// signature as it is encoded in TLS (e.g. as a DER-encoded `Ecdsa-Sig-Value`).
+    ///
+    /// Rustls signs synchronously, from within the handshake, so this blocks
+    /// the connection's task until it returns.
+    fn sign(
+        &self,
+        scheme: rustls::SignatureScheme,
+        message: &[u8],
+    ) -> Result<Vec<u8>, rustls::Error>;
+}
+
+/// Returns a store and receiver for `identity`'s credentials.
+///
+/// The private key must be PKCS #8-encoded, as DER or PEM (SEC 1 and PKCS #1
+/// PEM keys are also accepted). ECDSA (P-256 and P-384), Ed25519, and RSA keys
+/// are supported. The CSR may be DER or PEM; it is sent to the identity
+/// service as DER.
+///
+/// `CredentialsBuilder` assembles the same credentials from named settings.
 pub fn watch(
     identity: id::Name,
     roots_pem: &str,
     key_pkcs8: &[u8],
     csr: &[u8],
 ) -> Result<(Store, Receiver)> {
-    let mut roots = rustls::RootCertStore::empty();
-    let certs = match rustls_pemfile::certs(&mut std::io::Cursor::new(roots_pem)) {
-        Err(error) => {
-            warn!(%error, "invalid trust anchors file");
-            return Err(error.into());
-        }
-        Ok(certs) if certs.is_empty() => {
-            warn!("no valid certs in trust anchors file");
-            return Err("no trust roots in PEM file".into());
+    watch_with_options(identity, roots_pem, key_pkcs8, csr, Options::default())
+}
+
+pub fn watch_with_options(
+    identity: id::Name,
+    roots_pem: &str,
+    key_pkcs8: &[u8],
+    csr: &[u8],
+    options: Options,
+) -> Result<(Store, Receiver)> {
+    watch_with_bundles(identity, &[roots_pem], key_pkcs8, csr, options)
+}
+
+/// Returns a store and receiver for `identity`'s credentials that trust the
+/// roots in all of the given PEM bundles, e.g. those of several clusters.
+///
+/// A bundle that contains no certificates is skipped with a warning, but at
+/// least one bundle must contain a valid trust anchor.
+pub fn watch_with_bundles(
+    identity: id::Name,
+    roots_pems: &[&str],
+    key_pkcs8: &[u8],
+    csr: &[u8],
+    options: Options,
+) -> Result<(Store, Receiver)> {
+    watch_key(
+        identity,
+        roots_pems,
+        KeySource::Pkcs8(key_pkcs8),
+        csr,
+        None,
+        options,
+    )
+}
+
+/// Returns a store and receiver for `identity`'s credentials, which sign with
+/// a key held outside of the process.
+///
+/// Generated CSRs (see `Options::generate_csr`) are signed by the key, too.
+/// `Options::key_password` doesn't apply to external keys and is ignored.
+pub fn watch_with_external_key(
+    identity: id::Name,
+    roots_pems: &[&str],
+    key: Arc<dyn ExternalKey>,
+    csr: &[u8],
+    options: Options,
+) -> Result<(Store, Receiver)> {
+    watch_key(
+        identity,
+        roots_pems,
+        KeySource::External(key),
+        csr,
+        None,
+        options,
+    )
+}
+
+/// Returns a store and receiver for `identity`'s credentials, which verify
+/// servers with `verifier` rather than against the trust roots.
+///
+/// The verifier also validates the store's own certificates as they are
+/// installed, and it is kept when the trust roots are replaced. The roots
+/// still verify clients, and the constraints that `Options` places on peers
+/// (e.g. `Options::allowed_serials`) still apply to servers once the verifier
+/// accepts them.
+pub fn watch_with_verifier(
+    identity: id::Name,
+    roots_pems: &[&str],
+    key_pkcs8: &[u8],
+    csr: &[u8],
+    verifier: Arc<dyn rustls::client::ServerCertVerifier>,
+    options: Options,
+) -> Result<(Store, Receiver)> {
+    watch_key(
+        identity,
+        roots_pems,
+        KeySource::Pkcs8(key_pkcs8),
+        csr,
+        Some(verifier),
+        options,
+    )
+}
+
+/// The private key that a store signs with.
+enum KeySource<'k> {
+    /// A PKCS #8-encoded key, as DER or PEM.
+    Pkcs8(&'k [u8]),
+
+    /// A key held outside of the process.
+    External(Arc<dyn ExternalKey>),
+}
+
+fn watch_key(
+    identity: id::Name,
+    roots_pems: &[&str],
+    key: KeySource<'_>,
+    csr: &[u8],
+    verifier: Option<Arc<dyn rustls::client::ServerCertVerifier>>,
+    mut options: Options,
+) -> Result<(Store, Receiver)> {
+    // The CSR is sent to the identity service as-is, which would reject an
+    // empty request far less clearly.
+    if csr.is_empty() && options.generate_csr.is_none() {
+        return Err(EmptyCsr(()).into());
+    }
+
+    let now = match options.clock {
+        Some(ref clock) => clock(),
+        None => std::time::SystemTime::now(),
+    };
+    let (roots, roots_expiry, roots_sha256, trusted_roots, roots_info) =
+        load_roots(roots_pems, now)?;
+    options.metrics.roots_loaded(roots_expiry);
+
+    // The password is only needed to decrypt the key, so the store doesn't
+    // retain it.
+    let password = options.key_password.take();
+    let key = match key {
+        KeySource::Pkcs8(key_pkcs8) => read_key_with_password(&key_der(key_pkcs8)?, password)?,
+        KeySource::External(key) => read_external_key(key)?,
+    };
+    let csr = match options.generate_csr {
+        Some(ref template) => csr::generate(template, &identity, &key)?,
+        None => {
+            let csr = csr_der(csr)?;
+            check_csr_key(&csr, &key)?;
+            csr.into_owned()
         }
-        Ok(certs) => certs,
     };
 
-    let (added, skipped) = roots.add_parsable_certificates(&certs[..]);
-    if skipped != 0 {
-        warn!("Skipped {} invalid trust anchors", skipped);
-    }
-    if added == 0 {
-        return Err("no trust roots loaded".into());
+    let params = params::TlsParams::new(&options)?;
+    if options.session_resumption && options.client_sessions.is_none() {
+        // The cache is shared by every client configuration the store
+        // publishes, so sessions survive certificate rotations.
+        options.client_sessions = Some(Arc::new(rustls::client::ClientSessionMemoryCache::new(
+            params::CLIENT_SESSION_CACHE_SIZE,
+        )));
     }
 
-    let key = EcdsaKeyPair::from_pkcs8(params::SIGNATURE_ALG_RING_SIGNING, key_pkcs8)
-        .map_err(InvalidKey)?;
-
-    // XXX: Rustls's built-in verifiers don't let us tweak things as fully as we'd like (e.g.
-    // controlling the set of trusted signature algorithms), but they provide good enough
-    // defaults for now.
-    // TODO: lock down the verification further.
-    let server_cert_verifier = Arc::new(rustls::client::WebPkiVerifier::new(
-        roots.clone(),
-        None, // no certificate transparency policy
-    ));
+    // Client configurations wrap this verifier to apply any additional
+    // constraints on peers (e.g. on the algorithms that their certificates are
+    // signed with), but the store validates its own certificates without them.
+    let custom_verifier = verifier.is_some();
+    let server_cert_verifier = verifier.unwrap_or_else(|| store::root_verifier(&roots));
 
     let (client_tx, client_rx) = {
         // Since we don't have a certificate yet, build a client configuration
         // that doesn't attempt client authentication. Once we get a
         // certificate, the `Store` will publish a new configuration with a
         // client certificate resolver.
-        let mut c =
-            store::client_config_builder(server_cert_verifier.clone()).with_no_client_auth();
-
-        // Disable session resumption for the time-being until resumption is
-        // more tested.
-        c.resumption = rustls::client::Resumption::disabled();
+        let mut c = store::client_config_builder(&params, server_cert_verifier.clone())
+            .with_no_client_auth();
+        c.resumption = store::resumption(&options);
+        c.alpn_protocols = options.alpn_protocols.clone();
 
         watch::channel(Arc::new(c))
     };
     let (server_tx, server_rx) = {
         // Since we don't have a certificate yet, use an empty cert resolver so
-        // that handshaking always fails. Once we get a certificate, the `Store`
-        // will publish a new configuration with a server certificate resolver.
-        let empty_resolver = Arc::new(rustls::server::ResolvesServerCertUsingSni::new());
-        watch::channel(store::server_config(roots.clone(), empty_resolver))
+        // that handshaking always fails (except for a bootstrap name, if one is
+        // configured). Once we get a certificate, the `Store` will publish a
+        // new configuration with a server certificate resolver.
+        let bootstrap = match options.bootstrap_server_name {
+            Some(ref name) => Some(bootstrap::resolver(name)?),
+            None => None,
+        };
+        let empty_resolver = Arc::new(store::AwaitingCert::new(bootstrap, options.metrics.clone()));
+        let mut c = store::server_config(&params, roots.clone(), empty_resolver);
+        // The configuration was just built, so it isn't shared (or copied).
+        Arc::make_mut(&mut c).alpn_protocols = options.alpn_protocols.clone();
+        watch::channel(ServerState {
+            config: c,
+            leaf: None,
+        })
     };
 
-    let rx = Receiver::new(identity.clone(), client_rx, server_rx);
+    // Describes the installed leaf, which is unset until a certificate is
+    // installed.
+    let (leaf_tx, leaf_rx) = watch::channel(None);
+    let (rotation_tx, rotation_rx) = watch::channel(None);
+
+    let rx = Receiver::new(
+        identity.clone(),
+        params.clone(),
+        options.clock.clone(),
+        leaf_rx,
+        rotation_rx,
+        client_rx,
+        server_rx,
+    );
     let store = Store::new(
+        params,
         roots,
+        roots_expiry,
+        roots_sha256,
+        trusted_roots,
+        roots_info,
         server_cert_verifier,
+        custom_verifier,
         key,
-        csr,
+        &csr,
         identity,
+        options,
         client_tx,
         server_tx,
+        leaf_tx,
+        rotation_tx,
     );
 
     Ok((store, rx))
 }
 
+/// Fails if a CSR requests a certificate for a key other than ours, which the
+/// identity service would otherwise issue a certificate that can't be used
+/// for.
+///
+/// Requests whose public key can't be read aren't checked: the identity
+/// service rejects them.
+fn check_csr_key(csr: &[u8], key: &AnyKey) -> Result<(), CsrKeyMismatch> {
+    let public_key = match x509::csr_parts(csr).and_then(|(_, spki, _, _)| x509::spki_parts(spki)) {
+        Ok((_, _, public_key)) => public_key,
+        Err(_) => return Ok(()),
+    };
+    if public_key != key.public_key() {
+        return Err(CsrKeyMismatch(()));
+    }
+    Ok(())
+}
+
+/// Reads PEM bundles of trust roots, returning their union along with the
+/// earliest root expiry, the SHA-256 digest of their DER encodings, a
+/// description of each root that was added, and counts of the roots that were
+/// added and skipped.
+///
+/// Roots that were already read (from any bundle) are skipped as duplicates.
+/// Roots that have expired by `now` are still added, since webpki doesn't
+/// check trust anchors' validity, but they are counted and logged: operators
+/// are otherwise surprised that an expired root is loaded but doesn't work.
+fn load_roots(
+    roots_pems: &[&str],
+    now: std::time::SystemTime,
+) -> Result<
+    (
+        rustls::RootCertStore,
+        Option<std::time::SystemTime>,
+        ring::digest::Digest,
+        Vec<TrustedRoot>,
+        TrustRootsInfo,
+    ),
+    WatchError,
+> {
+    let mut bundles = Vec::with_capacity(roots_pems.len());
+    for (bundle, roots_pem) in roots_pems.iter().enumerate() {
+        match rustls_pemfile::certs(&mut std::io::Cursor::new(roots_pem)) {
+            Err(error) => {
+                warn!(%error, bundle, "invalid trust anchors file");
+                return Err(WatchError::PemParse(error));
+            }
+            Ok(certs) if certs.is_empty() => {
+                warn!(bundle, "no valid certs in trust anchors file");
+            }
+            Ok(certs) => bundles.push((bundle, certs)),
+        }
+    }
+    if bundles.is_empty() {
+        return Err(WatchError::NoTrustRoots);
+    }
+    let certs = bundles
+        .iter()
+        .flat_map(|(_, certs)| certs)
+        .collect::<Vec<_>>();
+
+    let roots_expiry = certs
+        .iter()
+        .filter_map(|c| x509::Cert::parse(c).and_then(|c| c.not_after()).ok())
+        .min();
+    let roots_sha256 = {
+        let mut ctx = ring::digest::Context::new(&ring::digest::SHA256);
+        for c in &certs {
+            ctx.update(c);
+        }
+        ctx.finish()
+    };
+
+    let mut roots = rustls::RootCertStore::empty();
+    let mut trusted = Vec::<TrustedRoot>::new();
+    let mut info = TrustRootsInfo::default();
+    for (bundle, certs) in &bundles {
+        let mut s = 0;
+        for c in certs {
+            let root = TrustedRoot::from_der(c);
+            if trusted.iter().any(|t| t.sha256 == root.sha256) {
+                info.duplicates += 1;
+                continue;
+            }
+            if roots.add(&rustls::Certificate(c.clone())).is_err() {
+                s += 1;
+                continue;
+            }
+            let not_after = x509::Cert::parse(c).and_then(|c| c.not_after());
+            if matches!(not_after, Ok(t) if t < now) {
+                warn!(bundle, root = %root.subject, "Trust anchor has expired");
+                info.expired += 1;
+            }
+            trusted.push(root);
+        }
+        if s != 0 {
+            warn!(bundle, "Skipped {} invalid trust anchors", s);
+        }
+        info.skipped += s;
+    }
+    if trusted.is_empty() {
+        return Err(WatchError::AllAnchorsSkipped {
+            skipped: info.skipped,
+        });
+    }
+    if info.duplicates != 0 {
+        warn!("Skipped {} duplicate trust anchors", info.duplicates);
+    }
+    info.added = trusted.len();
+
+    Ok((roots, roots_expiry, roots_sha256, trusted, info))
+}
+
+/// A private key of any supported algorithm.
+#[derive(Clone)]
+pub(crate) enum AnyKey {
+    EcdsaP256(Arc<EcdsaKeyPair>),
+    EcdsaP384(Arc<EcdsaKeyPair>),
+    Ed25519(Arc<Ed25519KeyPair>),
+    Rsa(Arc<RsaKeyPair>),
+
+    /// A key held outside of the process, whose type and public key are read
+    /// from its SubjectPublicKeyInfo when it is loaded.
+    External {
+        key: Arc<dyn ExternalKey>,
+        key_type: params::KeyType,
+        public_key: Arc<[u8]>,
+    },
+}
+
+/// Returns the PKCS #8 DER encoding of a private key, which may be PEM-encoded.
+///
+/// PEM keys may be PKCS #8 (`PRIVATE KEY`), SEC 1 (`EC PRIVATE KEY`), or
+/// PKCS #1 (`RSA PRIVATE KEY`) keys; the latter are converted to PKCS #8.
+fn key_der(key: &[u8]) -> Result<std::borrow::Cow<'_, [u8]>, WatchError> {
+    let start = key.iter().position(|b| !b.is_ascii_whitespace());
+    if !start.map_or(false, |i| key[i..].starts_with(b"-----BEGIN ")) {
+        return Ok(key.into());
+    }
+
+    let items = rustls_pemfile::read_all(&mut std::io::Cursor::new(key)).map_err(|error| {
+        warn!(%error, "invalid private key file");
+        WatchError::KeyPemParse(error)
+    })?;
+    let mut keys = items
+        .into_iter()
+        .filter_map(|item| match item {
+            rustls_pemfile::Item::PKCS8Key(der) => Some(Ok(der)),
+            rustls_pemfile::Item::ECKey(der) => Some(x509::sec1_to_pkcs8(&der)),
+            rustls_pemfile::Item::RSAKey(der) => Some(Ok(x509::pkcs1_to_pkcs8(&der))),
+            _ => None,
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|error| {
+            WatchError::KeyPemParse(std::io::Error::new(std::io::ErrorKind::InvalidData, error))
+        })?;
+    match keys.len() {
+        0 => Err(WatchError::NoPrivateKey),
+        1 => Ok(keys.remove(0).into()),
+        count => Err(WatchError::MultiplePrivateKeys { count }),
+    }
+}
+
+/// Returns the DER encoding of a CSR, which may be PEM-encoded, failing if it
+/// isn't a PKCS #10 request.
+///
+/// Its structure is checked but not its signature, which the identity service
+/// verifies.
+fn csr_der(csr: &[u8]) -> Result<std::borrow::Cow<'_, [u8]>, WatchError> {
+    let start = csr.iter().position(|b| !b.is_ascii_whitespace());
+    let der = if start.map_or(false, |i| csr[i..].starts_with(b"-----BEGIN ")) {
+        csr_pem_der(csr)?.into()
+    } else {
+        csr.into()
+    };
+    if x509::csr_parts(&der).is_err() {
+        warn!("invalid certificate signing request");
+        return Err(WatchError::InvalidCsr);
+    }
+    Ok(der)
+}
+
+/// Returns the DER encoding of the one CSR in a PEM file.
+///
+/// rustls-pemfile (as used by `key_der`) skips sections with labels it doesn't
+/// know, which include `CERTIFICATE REQUEST`, so the envelope is read here.
+fn csr_pem_der(csr: &[u8]) -> Result<Vec<u8>, WatchError> {
+    let invalid = |error: String| {
+        warn!(%error, "invalid certificate signing request file");
+        WatchError::CsrPemParse(std::io::Error::new(std::io::ErrorKind::InvalidData, error))
+    };
+    let pem = std::str::from_utf8(csr).map_err(|e| invalid(e.to_string()))?;
+    let mut csrs = Vec::new();
+    let mut lines = pem.lines().map(str::trim);
+    while let Some(line) = lines.next() {
+        let label = match line
+            .strip_prefix("-----BEGIN ")
+            .and_then(|l| l.strip_suffix("-----"))
+        {
+            Some(label) => label,
+            None => continue,
+        };
+        let end = format!("-----END {}-----", label);
+        let mut body = String::new();
+        loop {
+            match lines.next() {
+                Some(line) if line == end => break,
+                Some(line) => body.push_str(line),
+                None => return Err(invalid(format!("{} section is not terminated", label))),
+            }
+        }
+        // OpenSSL labels requests as `NEW CERTIFICATE REQUEST` when asked to.
+        if label == "CERTIFICATE REQUEST" || label == "NEW CERTIFICATE REQUEST" {
+            csrs.push(base64::decode(&body).map_err(|e| invalid(e.to_string()))?);
+        }
+    }
+    match csrs.len() {
+        0 => Err(WatchError::NoCsr),
+        1 => Ok(csrs.remove(0)),
+        count => Err(WatchError::MultipleCsrs { count }),
+    }
+}
+
+/// Reads a PKCS #8-encoded private key, decrypting it with `password` if it is
+/// encrypted.
+fn read_key_with_password(pkcs8: &[u8], password: Option<Vec<u8>>) -> Result<AnyKey, WatchError> {
+    match (x509::pkcs8_is_encrypted(pkcs8), password) {
+        (false, None) => read_key(pkcs8),
+        (true, Some(password)) => {
+            use std::convert::TryFrom;
+            let decrypted = pkcs8::EncryptedPrivateKeyInfo::try_from(pkcs8)
+                .and_then(|info| info.decrypt(&password))
+                .map_err(KeyDecryption)?;
+            read_key(decrypted.as_bytes())
+        }
+        (true, None) => Err(WatchError::KeyPasswordRequired),
+        (false, Some(_)) => Err(WatchError::KeyNotEncrypted),
+    }
+}
+
+/// Reads a PKCS #8-encoded private key, using the algorithm named by its
+/// `AlgorithmIdentifier`.
+fn read_key(pkcs8: &[u8]) -> Result<AnyKey, WatchError> {
+    use ring::signature::{ECDSA_P256_SHA256_ASN1_SIGNING, ECDSA_P384_SHA384_ASN1_SIGNING};
+    use x509::oid;
+
+    let (algorithm, parameters) = match x509::pkcs8_algorithm(pkcs8) {
+        Ok(algorithm) => algorithm,
+        // Read malformed keys as P-256 keys so that ring describes the error.
+        Err(_) => (oid::EC_PUBLIC_KEY, Some(oid::PRIME256V1)),
+    };
+    let key = match (algorithm, parameters) {
+        (oid::EC_PUBLIC_KEY, Some(oid::PRIME256V1)) => AnyKey::EcdsaP256(Arc::new(
+            EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8).map_err(InvalidKey)?,
+        )),
+        (oid::EC_PUBLIC_KEY, Some(oid::SECP384R1)) => AnyKey::EcdsaP384(Arc::new(
+            EcdsaKeyPair::from_pkcs8(&ECDSA_P384_SHA384_ASN1_SIGNING, pkcs8).map_err(InvalidKey)?,
+        )),
+        // OpenSSL writes version 1 keys, which omit the public key, so ring
+        // must derive it rather than check it.
+        (oid::ED25519, None) => AnyKey::Ed25519(Arc::new(
+            Ed25519KeyPair::from_pkcs8_maybe_unchecked(pkcs8).map_err(InvalidKey)?,
+        )),
+        (oid::RSA_ENCRYPTION, None) => {
+            AnyKey::Rsa(Arc::new(RsaKeyPair::from_pkcs8(pkcs8).map_err(InvalidKey)?))
+        }
+        (oid::EC_PUBLIC_KEY, Some(oid::SECP521R1)) => {
+            return Err(UnsupportedKeyAlgorithm("ECDSA P-521").into())
+        }
+        (oid::EC_PUBLIC_KEY, _) => {
+            return Err(UnsupportedKeyAlgorithm("ECDSA with an unknown curve").into())
+        }
+        (oid::RSASSA_PSS, _) => return Err(UnsupportedKeyAlgorithm("RSASSA-PSS").into()),
+        (oid::ED448, _) => return Err(UnsupportedKeyAlgorithm("Ed448").into()),
+        (oid::X25519, _) => return Err(UnsupportedKeyAlgorithm("X25519").into()),
+        (oid::X448, _) => return Err(UnsupportedKeyAlgorithm("X448").into()),
+        (oid::DSA, _) => return Err(UnsupportedKeyAlgorithm("DSA").into()),
+        // Let ring reject keys of unknown algorithms, as it always has.
+        _ => AnyKey::EcdsaP256(Arc::new(
+            EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8).map_err(InvalidKey)?,
+        )),
+    };
+    Ok(key)
+}
+
+/// Returns the SHA-256 digest of a DER-encoded certificate's
+/// SubjectPublicKeyInfo, as pinned by `Options::pinned_intermediates`.
+pub fn spki_sha256(cert_der: &[u8]) -> Result<[u8; 32]> {
+    let cert = x509::Cert::parse(cert_der)?;
+    Ok(store::sha256(cert.spki()))
+}
+
+/// Reads an external key's type and public key from its SubjectPublicKeyInfo.
+fn read_external_key(key: Arc<dyn ExternalKey>) -> Result<AnyKey, WatchError> {
+    let (key_type, public_key) = {
+        let (algorithm, parameters, public_key) =
+            x509::spki_parts(key.spki()).map_err(|_| WatchError::InvalidPublicKey)?;
+        let key_type = spki_key_type(algorithm, parameters)?;
+        (key_type, Arc::from(public_key))
+    };
+    Ok(AnyKey::External {
+        key,
+        key_type,
+        public_key,
+    })
+}
+
+/// Returns the type of a key from its SubjectPublicKeyInfo's algorithm OID and
+/// parameters.
+fn spki_key_type(
+    algorithm: &[u8],
+    parameters: Option<&[u8]>,
+) -> Result<params::KeyType, UnsupportedKeyAlgorithm> {
+    use params::KeyType;
+    use x509::oid;
+
+    match (algorithm, parameters) {
+        (oid::EC_PUBLIC_KEY, Some(oid::PRIME256V1)) => Ok(KeyType::EcdsaP256),
+        (oid::EC_PUBLIC_KEY, Some(oid::SECP384R1)) => Ok(KeyType::EcdsaP384),
+        (oid::ED25519, None) => Ok(KeyType::Ed25519),
+        (oid::RSA_ENCRYPTION, None) => Ok(KeyType::Rsa),
+        (oid::EC_PUBLIC_KEY, Some(oid::SECP521R1)) => Err(UnsupportedKeyAlgorithm("ECDSA P-521")),
+        (oid::EC_PUBLIC_KEY, _) => Err(UnsupportedKeyAlgorithm("ECDSA with an unknown curve")),
+        (oid::RSASSA_PSS, _) => Err(UnsupportedKeyAlgorithm("RSASSA-PSS")),
+        (oid::ED448, _) => Err(UnsupportedKeyAlgorithm("Ed448")),
+        _ => Err(UnsupportedKeyAlgorithm("an unknown algorithm")),
+    }
+}
+
+// === impl Options ===
+
+impl std::fmt::Debug for Options {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Options")
+            .field("key_identifiers", &self.key_identifiers)
+            .field("csr_sans", &self.csr_sans)
+            .field("metrics", &self.metrics)
+            .field("client_sessions", &self.client_sessions.is_some())
+            .field("session_resumption", &self.session_resumption)
+            .field("alpn_protocols", &self.alpn_protocols)
+            .field("trace_resolver", &self.trace_resolver)
+            .field("serve_without_sni", &self.serve_without_sni)
+            .field("max_identities", &self.max_identities)
+            .field("identity_limit", &self.identity_limit)
+            .field("cipher_suites", &self.cipher_suites)
+            .field("fips", &self.fips)
+            .field("tls12", &self.tls12)
+            .field("on_install", &self.on_install.is_some())
+            .field("on_rotation", &self.on_rotation.is_some())
+            .field("allowed_serials", &self.allowed_serials)
+            .field("ocsp_fetcher", &self.ocsp_fetcher.is_some())
+            .field("clock", &self.clock.is_some())
+            .field("trust_domain", &self.trust_domain)
+            .field("on_client_hello", &self.on_client_hello.is_some())
+            .field("trust_domain_migration", &self.trust_domain_migration)
+            .field("bootstrap_server_name", &self.bootstrap_server_name)
+            .field("max_peer_lifetime", &self.max_peer_lifetime)
+            .field("max_intermediates", &self.max_intermediates)
+            .field(
+                "pinned_intermediates",
+                &self
+                    .pinned_intermediates
+                    .iter()
+                    .map(hex::encode)
+                    .collect::<Vec<_>>(),
+            )
+            .field("signature_algorithms", &self.signature_algorithms)
+            .field("generate_csr", &self.generate_csr)
+            .field("key_password", &self.key_password.is_some())
+            .field("expiry_warning", &self.expiry_warning)
+            .field("on_expiry_warning", &self.on_expiry_warning.is_some())
+            .field("client_auth", &self.client_auth)
+            .field("permitted_dns_domains", &self.permitted_dns_domains)
+            .field(
+                "client_ca_hints",
+                &self.client_ca_hints.as_ref().map(Vec::len),
+            )
+            .field("offload_rsa_signatures", &self.offload_rsa_signatures)
+            .finish()
+    }
+}
+
+// === impl CipherSuite ===
+
+impl CipherSuite {
+    /// Every supported cipher suite.
+    pub const ALL: &'static [Self] = &[
+        Self::Tls13Aes128GcmSha256,
+        Self::Tls13Aes256GcmSha384,
+        Self::Tls13Chacha20Poly1305Sha256,
+        Self::Tls12EcdheEcdsaWithAes128GcmSha256,
+        Self::Tls12EcdheEcdsaWithAes256GcmSha384,
+        Self::Tls12EcdheEcdsaWithChacha20Poly1305Sha256,
+        Self::Tls12EcdheRsaWithAes128GcmSha256,
+        Self::Tls12EcdheRsaWithAes256GcmSha384,
+        Self::Tls12EcdheRsaWithChacha20Poly1305Sha256,
+    ];
+
+    /// Returns the suite's IANA name.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Tls13Aes128GcmSha256 => "TLS13_AES_128_GCM_SHA256",
+            Self::Tls13Aes256GcmSha384 => "TLS13_AES_256_GCM_SHA384",
+            Self::Tls13Chacha20Poly1305Sha256 => "TLS13_CHACHA20_POLY1305_SHA256",
+            Self::Tls12EcdheEcdsaWithAes128GcmSha256 => "TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256",
+            Self::Tls12EcdheEcdsaWithAes256GcmSha384 => "TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384",
+            Self::Tls12EcdheEcdsaWithChacha20Poly1305Sha256 => {
+                "TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256"
+            }
+            Self::Tls12EcdheRsaWithAes128GcmSha256 => "TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256",
+            Self::Tls12EcdheRsaWithAes256GcmSha384 => "TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384",
+            Self::Tls12EcdheRsaWithChacha20Poly1305Sha256 => {
+                "TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256"
+            }
+        }
+    }
+
+    fn rustls(self) -> rustls::SupportedCipherSuite {
+        match self {
+            Self::Tls13Aes128GcmSha256 => rustls::cipher_suite::TLS13_AES_128_GCM_SHA256,
+            Self::Tls13Aes256GcmSha384 => rustls::cipher_suite::TLS13_AES_256_GCM_SHA384,
+            Self::Tls13Chacha20Poly1305Sha256 => {
+                rustls::cipher_suite::TLS13_CHACHA20_POLY1305_SHA256
+            }
+            Self::Tls12EcdheEcdsaWithAes128GcmSha256 => {
+                rustls::cipher_suite::TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256
+            }
+            Self::Tls12EcdheEcdsaWithAes256GcmSha384 => {
+                rustls::cipher_suite::TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384
+            }
+            Self::Tls12EcdheEcdsaWithChacha20Poly1305Sha256 => {
+                rustls::cipher_suite::TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256
+            }
+            Self::Tls12EcdheRsaWithAes128GcmSha256 => {
+                rustls::cipher_suite::TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256
+            }
+            Self::Tls12EcdheRsaWithAes256GcmSha384 => {
+                rustls::cipher_suite::TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384
+            }
+            Self::Tls12EcdheRsaWithChacha20Poly1305Sha256 => {
+                rustls::cipher_suite::TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256
+            }
+        }
+    }
+
+    fn is_fips_approved(self) -> bool {
+        matches!(
+            self,
+            Self::Tls13Aes128GcmSha256
+                | Self::Tls13Aes256GcmSha384
+                | Self::Tls12EcdheEcdsaWithAes128GcmSha256
+                | Self::Tls12EcdheEcdsaWithAes256GcmSha384
+                | Self::Tls12EcdheRsaWithAes128GcmSha256
+                | Self::Tls12EcdheRsaWithAes256GcmSha384
+        )
+    }
+
+    fn is_tls12(self) -> bool {
+        !matches!(
+            self,
+            Self::Tls13Aes128GcmSha256
+                | Self::Tls13Aes256GcmSha384
+                | Self::Tls13Chacha20Poly1305Sha256
+        )
+    }
+}
+
+impl std::fmt::Display for CipherSuite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for CipherSuite {
+    type Err = UnknownCipherSuite;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|suite| suite.name() == s)
+            .ok_or_else(|| UnknownCipherSuite(s.to_string()))
+    }
+}
+
+// === impl SignatureAlgorithm ===
+
+impl SignatureAlgorithm {
+    /// Returns the algorithm's name, as used in RFC 3279, RFC 4055, RFC 5758,
+    /// and RFC 8410.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::EcdsaSha1 => "ecdsa-with-SHA1",
+            Self::EcdsaSha256 => "ecdsa-with-SHA256",
+            Self::EcdsaSha384 => "ecdsa-with-SHA384",
+            Self::EcdsaSha512 => "ecdsa-with-SHA512",
+            Self::Ed25519 => "Ed25519",
+            Self::RsaPkcs1Sha1 => "sha1WithRSAEncryption",
+            Self::RsaPkcs1Sha256 => "sha256WithRSAEncryption",
+            Self::RsaPkcs1Sha384 => "sha384WithRSAEncryption",
+            Self::RsaPkcs1Sha512 => "sha512WithRSAEncryption",
+            Self::RsaPssSha256 => "RSASSA-PSS with SHA-256",
+            Self::RsaPssSha384 => "RSASSA-PSS with SHA-384",
+            Self::RsaPssSha512 => "RSASSA-PSS with SHA-512",
+        }
+    }
+}
+
+impl std::fmt::Display for SignatureAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
 #[cfg(feature = "test-util")]
 pub fn for_test(ent: &linkerd_tls_test_util::Entity) -> (Store, Receiver) {
     watch(
         ent.name.parse().expect("name must be valid"),
         std::str::from_utf8(ent.trust_anchors).expect("roots must be PEM"),
         ent.key,
-        b"fake CSR",
+        ent.csr,
     )
     .expect("credentials must be valid")
 }
@@ -110,17 +1501,383 @@ pub fn default_for_test() -> (Store, Receiver) {
     for_test(&linkerd_tls_test_util::FOO_NS1)
 }
 
+/// Returns credentials for `FOO_NS1_EXPIRED`, whose expired certificate is
+/// installed without validation (which would reject it), e.g. to exercise
+/// expiry handling.
+#[cfg(feature = "test-util")]
+pub fn for_test_expired() -> (Store, Receiver) {
+    let ent = &linkerd_tls_test_util::FOO_NS1_EXPIRED;
+    let (mut store, rx) = for_test(ent);
+    store.install_unverified_for_test(id::DerX509(ent.crt.to_vec()));
+    (store, rx)
+}
+
+/// Returns a source of randomness that only ever produces `byte`, so that
+/// signatures made with it (see `Store::set_rng_for_test`) are reproducible.
+///
+/// This must never be used outside of tests.
+#[cfg(feature = "test-util")]
+pub fn fixed_rng_for_test(byte: u8) -> Arc<dyn ring::rand::SecureRandom + Send + Sync> {
+    Arc::new(ring::test::rand::FixedByteRandom { byte })
+}
+
+/// Returns receivers for two identities, each provisioned with its
+/// certificate, that can complete mutually-authenticated handshakes with each
+/// other (e.g. with `handshake_for_test`).
+#[cfg(feature = "test-util")]
+pub fn mutual_for_test(
+    a: &linkerd_tls_test_util::Entity,
+    b: &linkerd_tls_test_util::Entity,
+) -> (Receiver, Receiver) {
+    use id::Credentials;
+
+    let provision = |ent: &linkerd_tls_test_util::Entity| {
+        let (mut store, rx) = for_test(ent);
+        store
+            .set_certificate(
+                id::DerX509(ent.crt.to_vec()),
+                vec![],
+                std::time::SystemTime::now() + std::time::Duration::from_secs(600),
+            )
+            .expect("certificate must be valid");
+        // Receivers observe the last published configurations once the
+        // store is dropped.
+        rx
+    };
+    (provision(a), provision(b))
+}
+
+/// Runs an in-memory handshake from `client` to `server`, returning the
+/// server's identity as resolved by the client and the client's identity as
+/// resolved by the server.
+#[cfg(feature = "test-util")]
+pub fn handshake_for_test(
+    client: &Receiver,
+    server: &Receiver,
+) -> Result<
+    (
+        Option<crate::ResolvedIdentity>,
+        Option<crate::ResolvedIdentity>,
+    ),
+    rustls::Error,
+> {
+    use std::convert::TryFrom;
+
+    fn transfer(
+        from: &mut rustls::Connection,
+        to: &mut rustls::Connection,
+    ) -> Result<(), rustls::Error> {
+        let mut buf = Vec::new();
+        while from.wants_write() {
+            from.write_tls(&mut buf)
+                .expect("writes to a Vec must succeed");
+        }
+        let mut rd = &buf[..];
+        while !rd.is_empty() {
+            to.read_tls(&mut rd)
+                .expect("reads from a slice must succeed");
+            to.process_new_packets()?;
+        }
+        Ok(())
+    }
+
+    let server_name =
+        rustls::ServerName::try_from(server.name().as_str()).expect("name must be valid");
+    let mut c = rustls::Connection::from(rustls::ClientConnection::new(
+        client.new_client().config(),
+        server_name,
+    )?);
+    let mut s = rustls::Connection::from(rustls::ServerConnection::new(server.server().config())?);
+    for _ in 0..10 {
+        if !c.is_handshaking() && !s.is_handshaking() {
+            break;
+        }
+        transfer(&mut c, &mut s)?;
+        transfer(&mut s, &mut c)?;
+    }
+    assert!(
+        !c.is_handshaking() && !s.is_handshaking(),
+        "handshake did not complete"
+    );
+
+    Ok((
+        crate::ResolvedIdentity::from_peer_certs(c.peer_certificates()),
+        crate::ResolvedIdentity::from_peer_certs(s.peer_certificates()),
+    ))
+}
+
 mod params {
+    use super::{
+        x509, CipherSuite, ClientAuth, InvalidClientCaHint, NoCipherSuites, NoPermittedDnsDomains,
+        NoSignatureAlgorithms, NotFipsApproved, Options, SignatureAlgorithm, Tls12NotEnabled,
+        TrustDomainMigration,
+    };
+    use crate::IdentityPattern;
+    use linkerd_error::Result;
+    use std::sync::Arc;
     use tokio_rustls::rustls;
 
-    // These must be kept in sync:
-    pub static SIGNATURE_ALG_RING_SIGNING: &ring::signature::EcdsaSigningAlgorithm =
+    /// The kinds of private keys that may sign handshakes.
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub enum KeyType {
+        EcdsaP256,
+        EcdsaP384,
+        Ed25519,
+        Rsa,
+    }
+
+    impl KeyType {
+        /// Describes the key type, e.g. in errors.
+        pub fn name(self) -> &'static str {
+            match self {
+                Self::EcdsaP256 => "ECDSA P-256",
+                Self::EcdsaP384 => "ECDSA P-384",
+                Self::Ed25519 => "Ed25519",
+                Self::Rsa => "RSA",
+            }
+        }
+    }
+
+    /// The algorithm of keys that are generated rather than configured (e.g.
+    /// for bootstrap certificates).
+    pub static EPHEMERAL_KEY_ALG: &ring::signature::EcdsaSigningAlgorithm =
         &ring::signature::ECDSA_P256_SHA256_ASN1_SIGNING;
-    pub const SIGNATURE_ALG_RUSTLS_SCHEME: rustls::SignatureScheme =
-        rustls::SignatureScheme::ECDSA_NISTP256_SHA256;
-    pub const SIGNATURE_ALG_RUSTLS_ALGORITHM: rustls::SignatureAlgorithm =
-        rustls::SignatureAlgorithm::ECDSA;
+
+    // The signature schemes of each key type. These don't constrain the
+    // protocol versions or cipher suites that are negotiated.
+    static ECDSA_P256_SCHEMES: &[rustls::SignatureScheme] =
+        &[rustls::SignatureScheme::ECDSA_NISTP256_SHA256];
+    static ECDSA_P384_SCHEMES: &[rustls::SignatureScheme] =
+        &[rustls::SignatureScheme::ECDSA_NISTP384_SHA384];
+    static ED25519_SCHEMES: &[rustls::SignatureScheme] = &[rustls::SignatureScheme::ED25519];
+
+    /// RSA schemes, in order of preference. TLS 1.3 only permits the PSS
+    /// schemes in handshake signatures, so PKCS #1 is only chosen for TLS 1.2
+    /// peers that don't offer PSS.
+    static RSA_SCHEMES: &[rustls::SignatureScheme] = &[
+        rustls::SignatureScheme::RSA_PSS_SHA512,
+        rustls::SignatureScheme::RSA_PSS_SHA384,
+        rustls::SignatureScheme::RSA_PSS_SHA256,
+        rustls::SignatureScheme::RSA_PKCS1_SHA512,
+        rustls::SignatureScheme::RSA_PKCS1_SHA384,
+        rustls::SignatureScheme::RSA_PKCS1_SHA256,
+    ];
+
+    /// Returns the signature schemes that a key of the given type may use, in
+    /// order of preference.
+    pub fn signature_schemes(key: KeyType) -> &'static [rustls::SignatureScheme] {
+        match key {
+            KeyType::EcdsaP256 => ECDSA_P256_SCHEMES,
+            KeyType::EcdsaP384 => ECDSA_P384_SCHEMES,
+            KeyType::Ed25519 => ED25519_SCHEMES,
+            KeyType::Rsa => RSA_SCHEMES,
+        }
+    }
+
+    pub fn signature_algorithm(key: KeyType) -> rustls::SignatureAlgorithm {
+        match key {
+            KeyType::EcdsaP256 | KeyType::EcdsaP384 => rustls::SignatureAlgorithm::ECDSA,
+            KeyType::Ed25519 => rustls::SignatureAlgorithm::ED25519,
+            KeyType::Rsa => rustls::SignatureAlgorithm::RSA,
+        }
+    }
+
     pub static TLS_VERSIONS: &[&rustls::SupportedProtocolVersion] = &[&rustls::version::TLS13];
-    pub static TLS_SUPPORTED_CIPHERSUITES: &[rustls::SupportedCipherSuite] =
-        &[rustls::cipher_suite::TLS13_CHACHA20_POLY1305_SHA256];
+    pub static TLS_DEFAULT_CIPHERSUITES: &[CipherSuite] =
+        &[CipherSuite::Tls13Chacha20Poly1305Sha256];
+
+    // TLS 1.2, when enabled.
+    pub static TLS12_VERSIONS: &[&rustls::SupportedProtocolVersion] =
+        &[&rustls::version::TLS13, &rustls::version::TLS12];
+    pub static TLS12_DEFAULT_CIPHERSUITES: &[CipherSuite] = &[
+        CipherSuite::Tls12EcdheEcdsaWithChacha20Poly1305Sha256,
+        CipherSuite::Tls12EcdheRsaWithChacha20Poly1305Sha256,
+    ];
+
+    pub static TLS_KX_GROUPS: &[&rustls::SupportedKxGroup] = &[
+        &rustls::kx_group::X25519,
+        &rustls::kx_group::SECP256R1,
+        &rustls::kx_group::SECP384R1,
+    ];
+
+    // FIPS-approved algorithms.
+    pub static FIPS_DEFAULT_CIPHERSUITES: &[CipherSuite] = &[
+        CipherSuite::Tls13Aes128GcmSha256,
+        CipherSuite::Tls13Aes256GcmSha384,
+    ];
+    pub static FIPS_TLS12_DEFAULT_CIPHERSUITES: &[CipherSuite] = &[
+        CipherSuite::Tls12EcdheEcdsaWithAes128GcmSha256,
+        CipherSuite::Tls12EcdheEcdsaWithAes256GcmSha384,
+        CipherSuite::Tls12EcdheRsaWithAes128GcmSha256,
+        CipherSuite::Tls12EcdheRsaWithAes256GcmSha384,
+    ];
+    pub static FIPS_KX_GROUPS: &[&rustls::SupportedKxGroup] =
+        &[&rustls::kx_group::SECP256R1, &rustls::kx_group::SECP384R1];
+
+    /// The number of servers whose sessions clients cache, when resumption is
+    /// enabled.
+    pub const CLIENT_SESSION_CACHE_SIZE: usize = 256;
+
+    /// How long before the installed certificate expires a warning is logged,
+    /// unless `Options::expiry_warning` is set.
+    pub const EXPIRY_WARNING: std::time::Duration = std::time::Duration::from_secs(24 * 60 * 60);
+
+    /// The settings applied to client and server configurations.
+    #[derive(Clone, Debug)]
+    pub struct TlsParams {
+        pub versions: &'static [&'static rustls::SupportedProtocolVersion],
+        pub cipher_suites: Arc<[rustls::SupportedCipherSuite]>,
+        pub kx_groups: Arc<[&'static rustls::SupportedKxGroup]>,
+
+        /// The serial numbers of peer certificates to accept, if restricted.
+        pub allowed_serials: Option<Arc<[Vec<u8>]>>,
+
+        /// The trust domains to accept server identities in, if migrating.
+        pub trust_domain_migration: Option<Arc<TrustDomainMigration>>,
+
+        /// The longest validity period of peer certificates to accept, if
+        /// restricted.
+        pub max_peer_lifetime: Option<std::time::Duration>,
+
+        /// The most intermediates that peers may present, if restricted.
+        pub max_intermediates: Option<usize>,
+
+        /// The SPKI digests of intermediates that peers' chains must pass
+        /// through, if pinned.
+        pub pinned_intermediates: Option<Arc<[[u8; 32]]>>,
+
+        /// The algorithms that peer certificates may be signed with, if
+        /// restricted.
+        pub signature_algorithms: Option<Arc<[SignatureAlgorithm]>>,
+
+        /// The domains that server certificates' DNS SANs must be within, if
+        /// restricted.
+        pub permitted_dns_domains: Option<Arc<[IdentityPattern]>>,
+
+        /// The subjects that servers advertise to clients when requesting
+        /// their certificates, if curated.
+        pub client_ca_hints: Option<Arc<[rustls::DistinguishedName]>>,
+
+        /// Whether servers require clients to present certificates.
+        pub client_auth: ClientAuth,
+
+        /// Encrypts the session tickets that servers issue, if resumption is
+        /// enabled. It is shared by every server configuration, so tickets
+        /// remain valid across certificate rotations.
+        pub ticketer: Option<Ticketer>,
+    }
+
+    #[derive(Clone)]
+    pub struct Ticketer(pub Arc<dyn rustls::server::ProducesTickets>);
+
+    impl TlsParams {
+        pub fn new(options: &Options) -> Result<Self> {
+            let (default_suites, tls12_default_suites, kx_groups) = if options.fips {
+                (
+                    FIPS_DEFAULT_CIPHERSUITES,
+                    FIPS_TLS12_DEFAULT_CIPHERSUITES,
+                    FIPS_KX_GROUPS,
+                )
+            } else {
+                (
+                    TLS_DEFAULT_CIPHERSUITES,
+                    TLS12_DEFAULT_CIPHERSUITES,
+                    TLS_KX_GROUPS,
+                )
+            };
+            let suites = match options.cipher_suites {
+                Some(ref suites) => suites.clone(),
+                None if options.tls12 => [default_suites, tls12_default_suites].concat(),
+                None => default_suites.to_vec(),
+            };
+            // Rustls panics when building configurations without any suites.
+            if suites.is_empty() {
+                return Err(NoCipherSuites(()).into());
+            }
+            if options.fips {
+                if let Some(&suite) = suites.iter().find(|s| !s.is_fips_approved()) {
+                    return Err(NotFipsApproved(suite).into());
+                }
+            }
+            if !options.tls12 {
+                if let Some(&suite) = suites.iter().find(|s| s.is_tls12()) {
+                    return Err(Tls12NotEnabled(suite).into());
+                }
+            }
+            // An empty allowlist would reject every peer.
+            if matches!(options.signature_algorithms, Some(ref algs) if algs.is_empty()) {
+                return Err(NoSignatureAlgorithms(()).into());
+            }
+            if matches!(options.permitted_dns_domains, Some(ref ds) if ds.is_empty()) {
+                return Err(NoPermittedDnsDomains(()).into());
+            }
+            let client_ca_hints = match options.client_ca_hints {
+                Some(ref certs) => Some(
+                    certs
+                        .iter()
+                        .enumerate()
+                        .map(|(i, der)| {
+                            let cert =
+                                x509::Cert::parse(der).map_err(|_| InvalidClientCaHint(i))?;
+                            Ok(rustls::DistinguishedName::from(cert.subject().to_vec()))
+                        })
+                        .collect::<Result<Arc<[_]>, InvalidClientCaHint>>()?,
+                ),
+                None => None,
+            };
+
+            Ok(Self {
+                versions: if options.tls12 {
+                    TLS12_VERSIONS
+                } else {
+                    TLS_VERSIONS
+                },
+                cipher_suites: suites.iter().map(|s| s.rustls()).collect(),
+                kx_groups: kx_groups.into(),
+                allowed_serials: options.allowed_serials.as_deref().map(Into::into),
+                trust_domain_migration: options.trust_domain_migration.clone().map(Arc::new),
+                max_peer_lifetime: options.max_peer_lifetime,
+                max_intermediates: options.max_intermediates,
+                pinned_intermediates: if options.pinned_intermediates.is_empty() {
+                    None
+                } else {
+                    Some(options.pinned_intermediates.as_slice().into())
+                },
+                signature_algorithms: options.signature_algorithms.as_deref().map(Into::into),
+                client_auth: options.client_auth,
+                permitted_dns_domains: options
+                    .permitted_dns_domains
+                    .as_ref()
+                    .map(|ds| ds.iter().flat_map(|d| domain_patterns(d)).collect()),
+                client_ca_hints,
+                ticketer: if options.session_resumption {
+                    Some(Ticketer(rustls::Ticketer::new()?))
+                } else {
+                    None
+                },
+            })
+        }
+    }
+
+    /// Returns the patterns that match the names a permitted domain allows.
+    fn domain_patterns(domain: &str) -> Vec<IdentityPattern> {
+        match domain
+            .strip_prefix("*.")
+            .or_else(|| domain.strip_prefix('.'))
+        {
+            Some(domain) => vec![IdentityPattern::Suffix(domain.to_string())],
+            None => vec![
+                IdentityPattern::Exact(domain.to_string()),
+                IdentityPattern::Suffix(domain.to_string()),
+            ],
+        }
+    }
+
+    impl std::fmt::Debug for Ticketer {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("Ticketer")
+                .field("lifetime", &self.0.lifetime())
+                .finish()
+        }
+    }
 }
@@ -0,0 +1,115 @@
+//! An ephemeral, self-signed certificate that servers may present to bootstrap
+//! clients before the mesh identity is provisioned.
+
+use super::{
+    params::EPHEMERAL_KEY_ALG,
+    x509::{
+        self, tlv, BIT_STRING, DNS_NAME, EXPLICIT_0, EXPLICIT_3, GENERALIZED_TIME, INTEGER,
+        OCTET_STRING, OID, SEQUENCE, SET, UTC_TIME, UTF8_STRING,
+    },
+    AnyKey,
+};
+use linkerd_error::Result;
+use ring::{
+    rand::{self, SecureRandom},
+    signature::{EcdsaKeyPair, KeyPair},
+};
+use std::sync::Arc;
+use tokio_rustls::rustls;
+use tracing::info;
+
+/// The certificate is valid from the UNIX epoch and has no well-defined
+/// expiration (RFC 5280, section 4.1.2.5).
+const NOT_BEFORE: &[u8] = b"700101000000Z";
+const NOT_AFTER: &[u8] = b"99991231235959Z";
+
+/// Returns a server certificate resolver that presents a newly generated,
+/// self-signed certificate to clients that request `name` via SNI, and no
+/// certificate to any other clients.
+///
+/// The certificate's key is generated here, so it is never shared with the
+/// mesh identity.
+pub(super) fn resolver(name: &str) -> Result<Arc<rustls::server::ResolvesServerCertUsingSni>> {
+    let rng = rand::SystemRandom::new();
+    let pkcs8 = EcdsaKeyPair::generate_pkcs8(EPHEMERAL_KEY_ALG, &rng)?;
+    let key = EcdsaKeyPair::from_pkcs8(EPHEMERAL_KEY_ALG, pkcs8.as_ref())?;
+    let cert = self_signed(name, &key, &rng)?;
+
+    let mut resolver = rustls::server::ResolvesServerCertUsingSni::new();
+    resolver.add(
+        name,
+        rustls::sign::CertifiedKey::new(
+            vec![rustls::Certificate(cert)],
+            Arc::new(AnyKey::EcdsaP256(Arc::new(key))),
+        ),
+    )?;
+    info!(
+        server.name = %name,
+        "Presenting an ephemeral self-signed certificate until the identity is provisioned"
+    );
+    Ok(Arc::new(resolver))
+}
+
+/// Returns a DER-encoded, self-signed certificate for `name`.
+fn self_signed(
+    name: &str,
+    key: &EcdsaKeyPair,
+    rng: &dyn SecureRandom,
+) -> Result<Vec<u8>, ring::error::Unspecified> {
+    // A positive, 127-bit serial number.
+    let mut serial = [0; 16];
+    rng.fill(&mut serial)?;
+    serial[0] = (serial[0] & 0x7f) | 0x40;
+
+    let algorithm = tlv(SEQUENCE, &tlv(OID, x509::oid::ECDSA_WITH_SHA256));
+    let dn = tlv(
+        SEQUENCE,
+        &tlv(
+            SET,
+            &tlv(
+                SEQUENCE,
+                &[
+                    tlv(OID, x509::oid::COMMON_NAME),
+                    tlv(UTF8_STRING, name.as_bytes()),
+                ]
+                .concat(),
+            ),
+        ),
+    );
+    let validity = tlv(
+        SEQUENCE,
+        &[tlv(UTC_TIME, NOT_BEFORE), tlv(GENERALIZED_TIME, NOT_AFTER)].concat(),
+    );
+    let san = tlv(
+        SEQUENCE,
+        &[
+            tlv(OID, x509::oid::SUBJECT_ALT_NAME),
+            tlv(
+                OCTET_STRING,
+                &tlv(SEQUENCE, &tlv(DNS_NAME, name.as_bytes())),
+            ),
+        ]
+        .concat(),
+    );
+    let tbs = tlv(
+        SEQUENCE,
+        &[
+            tlv(EXPLICIT_0, &tlv(INTEGER, &[2])), // v3
+            tlv(INTEGER, &serial),
+            algorithm.clone(),
+            dn.clone(),
+            validity,
+            dn,
+            x509::p256_spki(key.public_key().as_ref()),
+            tlv(EXPLICIT_3, &tlv(SEQUENCE, &san)),
+        ]
+        .concat(),
+    );
+
+    let signature = key.sign(rng, &tbs)?;
+    let signature = [&[0][..], signature.as_ref()].concat(); // no unused bits
+    Ok(tlv(
+        SEQUENCE,
+        &[tbs, algorithm, tlv(BIT_STRING, &signature)].concat(),
+    ))
+}
@@ -0,0 +1,293 @@
+//! Assembles credentials from their parts, as an alternative to the `watch`
+//! functions' positional arguments.
+
+use super::{
+    watch_key, CipherSuite, CsrKeyMismatch, ExternalKey, InvalidClientCaHint, InvalidField,
+    KeySource, MissingField, NoCipherSuites, NotFipsApproved, Options, Receiver, Store,
+    Tls12NotEnabled, UnknownCipherSuite, UnsupportedProtocolVersions, WatchError,
+};
+use linkerd_error::{Error, Result};
+use linkerd_identity as id;
+use std::sync::Arc;
+use tokio_rustls::rustls;
+
+/// Builds a store and receiver for an identity's credentials.
+///
+/// The identity, trust roots, and private key must be set, as must a CSR
+/// unless one is generated (see `Options::generate_csr`). Settings that
+/// don't have a method here may be set with `options`; the other methods
+/// override the corresponding options, regardless of the order in which
+/// they're called.
+///
+/// Errors name the setting that is missing or invalid. Invalid settings are
+/// reported as an `InvalidField`, whose source is the error that `watch`
+/// would return.
+#[derive(Clone, Default)]
+pub struct CredentialsBuilder {
+    identity: Option<id::Name>,
+    roots_pems: Vec<String>,
+    key: Option<BuilderKey>,
+    csr: Option<Vec<u8>>,
+    cipher_suites: Option<Vec<CipherSuite>>,
+    protocol_versions: Option<Vec<rustls::ProtocolVersion>>,
+    resumption: Option<bool>,
+    max_intermediates: Option<usize>,
+    pinned_intermediates: Vec<[u8; 32]>,
+    client_ca_hints: Option<Vec<Vec<u8>>>,
+    server_cert_verifier: Option<Arc<dyn rustls::client::ServerCertVerifier>>,
+    options: Options,
+}
+
+#[derive(Clone)]
+enum BuilderKey {
+    Pkcs8(Vec<u8>),
+    External(Arc<dyn ExternalKey>),
+}
+
+// === impl CredentialsBuilder ===
+
+impl CredentialsBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the local identity.
+    pub fn identity(mut self, identity: id::Name) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Adds a PEM bundle of trust roots.
+    ///
+    /// This may be called more than once to trust the roots in several
+    /// bundles, as with `watch_with_bundles`.
+    pub fn trust_roots_pem(mut self, roots_pem: impl Into<String>) -> Self {
+        self.roots_pems.push(roots_pem.into());
+        self
+    }
+
+    /// Sets the private key, which may be PKCS #8 DER or PEM, as for `watch`.
+    ///
+    /// This replaces any external key.
+    pub fn key_pkcs8(mut self, key_pkcs8: impl Into<Vec<u8>>) -> Self {
+        self.key = Some(BuilderKey::Pkcs8(key_pkcs8.into()));
+        self
+    }
+
+    /// Sets a private key that is held outside of the process, as for
+    /// `watch_with_external_key`.
+    ///
+    /// This replaces any PKCS #8 key.
+    pub fn external_key(mut self, key: Arc<dyn ExternalKey>) -> Self {
+        self.key = Some(BuilderKey::External(key));
+        self
+    }
+
+    /// Sets the CSR that is sent to the identity service, as DER or PEM.
+    pub fn csr(mut self, csr: impl Into<Vec<u8>>) -> Self {
+        self.csr = Some(csr.into());
+        self
+    }
+
+    /// Sets the cipher suites to enable, in order of preference (see
+    /// `Options::cipher_suites`).
+    pub fn cipher_suites(mut self, suites: impl IntoIterator<Item = CipherSuite>) -> Self {
+        self.cipher_suites = Some(suites.into_iter().collect());
+        self
+    }
+
+    /// Sets the protocol versions to enable.
+    ///
+    /// TLS 1.3 must be enabled, optionally with TLS 1.2 (see `Options::tls12`);
+    /// no other versions are supported.
+    pub fn protocol_versions(
+        mut self,
+        versions: impl IntoIterator<Item = rustls::ProtocolVersion>,
+    ) -> Self {
+        self.protocol_versions = Some(versions.into_iter().collect());
+        self
+    }
+
+    /// Enables or disables session resumption (see
+    /// `Options::session_resumption`).
+    pub fn resumption(mut self, enabled: bool) -> Self {
+        self.resumption = Some(enabled);
+        self
+    }
+
+    /// Limits the number of intermediates that peers may present (see
+    /// `Options::max_intermediates`).
+    pub fn max_intermediates(mut self, max: usize) -> Self {
+        self.max_intermediates = Some(max);
+        self
+    }
+
+    /// Pins intermediates, by the SHA-256 digests of their
+    /// SubjectPublicKeyInfos, that peers' chains must pass through (see
+    /// `Options::pinned_intermediates`).
+    ///
+    /// This may be called more than once to pin more intermediates.
+    pub fn pinned_intermediates(mut self, pins: impl IntoIterator<Item = [u8; 32]>) -> Self {
+        self.pinned_intermediates.extend(pins);
+        self
+    }
+
+    /// Sets the DER-encoded certificates of the CAs that servers advertise to
+    /// clients when requesting their certificates (see
+    /// `Options::client_ca_hints`).
+    pub fn client_ca_hints(mut self, certs: impl IntoIterator<Item = Vec<u8>>) -> Self {
+        self.client_ca_hints = Some(certs.into_iter().collect());
+        self
+    }
+
+    /// Verifies servers with `verifier` rather than against the trust roots
+    /// (see `watch_with_verifier`).
+    pub fn server_cert_verifier(
+        mut self,
+        verifier: Arc<dyn rustls::client::ServerCertVerifier>,
+    ) -> Self {
+        self.server_cert_verifier = Some(verifier);
+        self
+    }
+
+    /// Sets options that don't have a method of their own.
+    pub fn options(mut self, options: Options) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Builds a store and receiver for the identity's credentials.
+    pub fn build(self) -> Result<(Store, Receiver)> {
+        let identity = self.identity.ok_or(MissingField("identity"))?;
+        if self.roots_pems.is_empty() {
+            return Err(MissingField("trust roots").into());
+        }
+        let key = self.key.ok_or(MissingField("private key"))?;
+        let mut options = self.options;
+        let csr = match self.csr {
+            Some(csr) => csr,
+            None if options.generate_csr.is_some() => Vec::new(),
+            None => return Err(MissingField("CSR").into()),
+        };
+
+        if let Some(suites) = self.cipher_suites {
+            options.cipher_suites = Some(suites);
+        }
+        if let Some(versions) = self.protocol_versions {
+            options.tls12 = tls12_enabled(versions).map_err(|e| InvalidField {
+                field: "protocol versions",
+                source: e.into(),
+            })?;
+        }
+        if let Some(enabled) = self.resumption {
+            options.session_resumption = enabled;
+        }
+        if let Some(max) = self.max_intermediates {
+            options.max_intermediates = Some(max);
+        }
+        if !self.pinned_intermediates.is_empty() {
+            options.pinned_intermediates = self.pinned_intermediates;
+        }
+        if let Some(hints) = self.client_ca_hints {
+            options.client_ca_hints = Some(hints);
+        }
+
+        let roots_pems = self
+            .roots_pems
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        let key = match key {
+            BuilderKey::Pkcs8(ref key) => KeySource::Pkcs8(key),
+            BuilderKey::External(key) => KeySource::External(key),
+        };
+        let verifier = self.server_cert_verifier;
+        watch_key(identity, &roots_pems, key, &csr, verifier, options).map_err(|source| {
+            match invalid_field(&source) {
+                Some(field) => InvalidField { field, source }.into(),
+                None => source,
+            }
+        })
+    }
+}
+
+impl std::fmt::Debug for CredentialsBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Keys are never formatted.
+        f.debug_struct("CredentialsBuilder")
+            .field("identity", &self.identity)
+            .field("roots_pems", &self.roots_pems.len())
+            .field("key", &self.key.is_some())
+            .field("csr", &self.csr.as_ref().map(Vec::len))
+            .field("cipher_suites", &self.cipher_suites)
+            .field("protocol_versions", &self.protocol_versions)
+            .field("resumption", &self.resumption)
+            .field("max_intermediates", &self.max_intermediates)
+            .field(
+                "pinned_intermediates",
+                &self
+                    .pinned_intermediates
+                    .iter()
+                    .map(hex::encode)
+                    .collect::<Vec<_>>(),
+            )
+            .field(
+                "client_ca_hints",
+                &self.client_ca_hints.as_ref().map(Vec::len),
+            )
+            .field("server_cert_verifier", &self.server_cert_verifier.is_some())
+            .field("options", &self.options)
+            .finish()
+    }
+}
+
+/// Returns whether `versions` enable TLS 1.2 alongside TLS 1.3.
+fn tls12_enabled(
+    versions: Vec<rustls::ProtocolVersion>,
+) -> Result<bool, UnsupportedProtocolVersions> {
+    use rustls::ProtocolVersion::{TLSv1_2, TLSv1_3};
+
+    let supported = versions.iter().all(|v| matches!(v, TLSv1_2 | TLSv1_3));
+    if !supported || !versions.contains(&TLSv1_3) {
+        return Err(UnsupportedProtocolVersions(versions));
+    }
+    Ok(versions.contains(&TLSv1_2))
+}
+
+/// Returns the setting that `watch_key` rejected, if it can be determined.
+fn invalid_field(error: &Error) -> Option<&'static str> {
+    if let Some(error) = error.downcast_ref::<WatchError>() {
+        return Some(match error {
+            WatchError::PemParse(_)
+            | WatchError::NoTrustRoots
+            | WatchError::AllAnchorsSkipped { .. } => "trust roots",
+            WatchError::CsrPemParse(_)
+            | WatchError::NoCsr
+            | WatchError::MultipleCsrs { .. }
+            | WatchError::InvalidCsr => "CSR",
+            WatchError::KeyPemParse(_)
+            | WatchError::NoPrivateKey
+            | WatchError::MultiplePrivateKeys { .. }
+            | WatchError::KeyPasswordRequired
+            | WatchError::KeyNotEncrypted
+            | WatchError::KeyDecryption(_)
+            | WatchError::InvalidKey(_)
+            | WatchError::UnsupportedKeyAlgorithm(_)
+            | WatchError::InvalidPublicKey => "private key",
+        });
+    }
+    if error.is::<CsrKeyMismatch>() {
+        return Some("CSR");
+    }
+    if error.is::<InvalidClientCaHint>() {
+        return Some("client CA hints");
+    }
+    if error.is::<NoCipherSuites>()
+        || error.is::<UnknownCipherSuite>()
+        || error.is::<NotFipsApproved>()
+        || error.is::<Tls12NotEnabled>()
+    {
+        return Some("cipher suites");
+    }
+    None
+}
@@ -0,0 +1,207 @@
+//! Generates PKCS #10 certificate signing requests for the private key, so that
+//! a CSR needn't be generated by an external tool before the proxy starts.
+
+use super::{
+    params::KeyType,
+    x509::{
+        oid, tlv, BIT_STRING, CSR_ATTRIBUTES, DNS_NAME, INTEGER, NULL, OCTET_STRING, OID, SEQUENCE,
+        SET, UTF8_STRING,
+    },
+    AnyKey, CsrTemplate,
+};
+use linkerd_error::Result;
+use linkerd_identity as id;
+use tokio_rustls::rustls::{self, sign::SigningKey};
+
+/// Returns a DER-encoded CSR for `name`, signed by `key`.
+///
+/// The request is signed with the algorithm that corresponds to the key's type:
+/// ECDSA keys sign with SHA-256 (P-256) or SHA-384 (P-384), Ed25519 keys sign
+/// with Ed25519, and RSA keys sign with PKCS #1 v1.5 and SHA-256, which CAs
+/// accept far more widely than PSS.
+pub(super) fn generate(template: &CsrTemplate, name: &id::Name, key: &AnyKey) -> Result<Vec<u8>> {
+    let info = request_info(template, name, key);
+
+    let (scheme, algorithm) = match key.key_type() {
+        KeyType::EcdsaP256 => (
+            rustls::SignatureScheme::ECDSA_NISTP256_SHA256,
+            tlv(SEQUENCE, &tlv(OID, oid::ECDSA_WITH_SHA256)),
+        ),
+        KeyType::EcdsaP384 => (
+            rustls::SignatureScheme::ECDSA_NISTP384_SHA384,
+            tlv(SEQUENCE, &tlv(OID, oid::ECDSA_WITH_SHA384)),
+        ),
+        KeyType::Ed25519 => (
+            rustls::SignatureScheme::ED25519,
+            tlv(SEQUENCE, &tlv(OID, oid::ED25519)),
+        ),
+        KeyType::Rsa => (
+            rustls::SignatureScheme::RSA_PKCS1_SHA256,
+            tlv(
+                SEQUENCE,
+                &[tlv(OID, oid::SHA256_WITH_RSA_ENCRYPTION), tlv(NULL, &[])].concat(),
+            ),
+        ),
+    };
+    let signature = key
+        .choose_scheme(&[scheme])
+        .expect("keys must support their CSR signature scheme")
+        .sign(&info)?;
+    let signature = [&[0][..], &signature[..]].concat(); // no unused bits
+
+    Ok(tlv(
+        SEQUENCE,
+        &[info, algorithm, tlv(BIT_STRING, &signature)].concat(),
+    ))
+}
+
+/// Returns the DER-encoded `CertificationRequestInfo` that is signed.
+fn request_info(template: &CsrTemplate, name: &id::Name, key: &AnyKey) -> Vec<u8> {
+    let common_name = template.common_name.as_deref().unwrap_or(name.as_str());
+    let subject = if common_name.is_empty() {
+        tlv(SEQUENCE, &[])
+    } else {
+        tlv(
+            SEQUENCE,
+            &tlv(
+                SET,
+                &tlv(
+                    SEQUENCE,
+                    &[
+                        tlv(OID, oid::COMMON_NAME),
+                        tlv(UTF8_STRING, common_name.as_bytes()),
+                    ]
+                    .concat(),
+                ),
+            ),
+        )
+    };
+
+    let dns_names = if template.dns_names.is_empty() {
+        tlv(DNS_NAME, name.as_str().as_bytes())
+    } else {
+        template
+            .dns_names
+            .iter()
+            .flat_map(|n| tlv(DNS_NAME, n.as_bytes()))
+            .collect()
+    };
+    let san = tlv(
+        SEQUENCE,
+        &[
+            tlv(OID, oid::SUBJECT_ALT_NAME),
+            tlv(OCTET_STRING, &tlv(SEQUENCE, &dns_names)),
+        ]
+        .concat(),
+    );
+    let extension_request = tlv(
+        SEQUENCE,
+        &[
+            tlv(OID, oid::EXTENSION_REQUEST),
+            tlv(SET, &tlv(SEQUENCE, &san)),
+        ]
+        .concat(),
+    );
+
+    tlv(
+        SEQUENCE,
+        &[
+            tlv(INTEGER, &[0]), // v1
+            subject,
+            spki(key),
+            tlv(CSR_ATTRIBUTES, &extension_request),
+        ]
+        .concat(),
+    )
+}
+
+/// Returns the DER-encoded SubjectPublicKeyInfo for the key.
+fn spki(key: &AnyKey) -> Vec<u8> {
+    let algorithm = match key.key_type() {
+        KeyType::EcdsaP256 => [tlv(OID, oid::EC_PUBLIC_KEY), tlv(OID, oid::PRIME256V1)].concat(),
+        KeyType::EcdsaP384 => [tlv(OID, oid::EC_PUBLIC_KEY), tlv(OID, oid::SECP384R1)].concat(),
+        KeyType::Ed25519 => tlv(OID, oid::ED25519),
+        KeyType::Rsa => [tlv(OID, oid::RSA_ENCRYPTION), tlv(NULL, &[])].concat(),
+    };
+    let public_key = [&[0][..], key.public_key()].concat(); // no unused bits
+    tlv(
+        SEQUENCE,
+        &[tlv(SEQUENCE, &algorithm), tlv(BIT_STRING, &public_key)].concat(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::creds::{read_key, x509};
+    use linkerd_tls_test_util::{FOO_NS1, FOO_NS1_ED25519};
+    use ring::signature;
+
+    fn check(key: &[u8], crt: &[u8], alg: &'static dyn signature::VerificationAlgorithm) {
+        let name = FOO_NS1.name.parse::<id::Name>().unwrap();
+        let key = read_key(key).unwrap();
+        let der = generate(&CsrTemplate::default(), &name, &key).unwrap();
+
+        let csr = x509::Csr::parse(&der).expect("CSR must parse");
+        assert_eq!(csr.dns_names().unwrap(), vec![FOO_NS1.name]);
+
+        let cert = x509::Cert::parse(crt).unwrap();
+        let (info, spki, _, sig) = x509::csr_parts(&der).unwrap();
+        assert_eq!(spki, cert.spki(), "CSR must name the certified key");
+        signature::UnparsedPublicKey::new(alg, cert.public_key().unwrap())
+            .verify(info, sig)
+            .expect("CSR must be signed by the key");
+    }
+
+    #[test]
+    fn requests_are_signed_by_each_key_type() {
+        check(FOO_NS1.key, FOO_NS1.crt, &signature::ECDSA_P256_SHA256_ASN1);
+        check(
+            include_bytes!("../testdata/foo-ns1-p384-key.p8"),
+            include_bytes!("../testdata/foo-ns1-p384.der"),
+            &signature::ECDSA_P384_SHA384_ASN1,
+        );
+        check(
+            FOO_NS1_ED25519.key,
+            FOO_NS1_ED25519.crt,
+            &signature::ED25519,
+        );
+        check(
+            include_bytes!("../testdata/foo-ns1-rsa-key.p8"),
+            include_bytes!("../testdata/foo-ns1-rsa.der"),
+            &signature::RSA_PKCS1_2048_8192_SHA256,
+        );
+    }
+
+    #[test]
+    fn requests_use_the_template() {
+        let name = FOO_NS1.name.parse::<id::Name>().unwrap();
+        let key = read_key(FOO_NS1.key).unwrap();
+        let template = CsrTemplate {
+            common_name: Some("foo".to_string()),
+            dns_names: vec!["foo.example.com".to_string(), "bar.example.com".to_string()],
+        };
+        let der = generate(&template, &name, &key).unwrap();
+
+        let csr = x509::Csr::parse(&der).unwrap();
+        assert_eq!(
+            csr.dns_names().unwrap(),
+            vec!["foo.example.com", "bar.example.com"]
+        );
+        let (info, _, _, _) = x509::csr_parts(&der).unwrap();
+        let cn = tlv(UTF8_STRING, b"foo");
+        assert!(info.windows(cn.len()).any(|w| w == cn));
+        let identity = name.as_str().as_bytes();
+        assert!(!info.windows(identity.len()).any(|w| w == identity));
+
+        // An empty common name leaves the subject empty.
+        let template = CsrTemplate {
+            common_name: Some(String::new()),
+            ..Default::default()
+        };
+        let der = generate(&template, &name, &key).unwrap();
+        let (info, _, _, _) = x509::csr_parts(&der).unwrap();
+        let empty_subject = [0x02, 0x01, 0x00, 0x30, 0x00]; // version, then subject
+        assert!(info.windows(5).any(|w| w == empty_subject));
+    }
+}
@@ -0,0 +1,265 @@
+use linkerd_metrics::{latency, metrics, Counter, FmtLabels, FmtMetrics, Gauge, Histogram};
+use parking_lot::Mutex;
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+metrics! {
+    identity_cert_installs_total: Counter {
+        "The total number of identity certificates that have been installed."
+    },
+
+    identity_cert_expiration_timestamp_seconds: Gauge {
+        "Time when the installed identity certificate will expire (in seconds since the UNIX epoch)."
+    },
+
+    identity_cert_resolver_max_bytes: Gauge {
+        "The largest total size of the certificate chains held by the resolvers (in bytes of DER)."
+    },
+
+    identity_cert_install_duration_us: Histogram<latency::Us> {
+        "The time taken to install a new identity certificate, by phase (in microseconds)."
+    },
+
+    identity_roots_expiration_timestamp_seconds: Gauge {
+        "Time when the earliest-expiring trust root will expire (in seconds since the UNIX epoch)."
+    },
+
+    identity_sni_mismatches_total: Counter {
+        "The total number of ClientHellos that requested an SNI name the identity certificate is not valid for."
+    },
+
+    identity_cert_resolution_failures_total: Counter {
+        "The total number of ClientHellos for which no identity certificate was resolved, by reason."
+    }
+}
+
+/// Metrics describing the credential store.
+///
+/// This is the single handle that owns all of the store's series. It is
+/// passed to the store via `Options` and reported once (e.g. by adding it to
+/// the proxy's `FmtMetrics` report); clones share the same series, so
+/// registering it does not duplicate them. Metrics are exported with
+/// `linkerd-metrics` rather than a `prometheus::Registry`, like the rest of
+/// the proxy's metrics.
+#[derive(Clone, Debug, Default)]
+pub struct Metrics {
+    installs: Arc<Counter>,
+    cert_expiry: Arc<Mutex<Option<SystemTime>>>,
+    resolver_max_bytes: Arc<AtomicU64>,
+    install: Arc<Install>,
+    roots_expiry: Arc<Mutex<Option<SystemTime>>>,
+    unresolved: Arc<UnresolvedCounts>,
+}
+
+/// The number of ClientHellos for which servers resolved no certificate, by
+/// reason (e.g. as returned by `Store::resolution_failures`).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResolutionFailures {
+    /// The client didn't request a name via SNI.
+    pub no_sni: u64,
+
+    /// The client requested a name that isn't a valid DNS name.
+    pub invalid_sni: u64,
+
+    /// The client requested a name that the certificate isn't valid for.
+    pub sni_mismatch: u64,
+
+    /// The client offered no signature schemes that the key supports.
+    pub unsupported_sigscheme: u64,
+
+    /// No certificate had been installed yet.
+    pub no_cert_yet: u64,
+}
+
+/// Why a server resolved no certificate for a ClientHello.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(super) enum Unresolved {
+    NoSni,
+    InvalidSni,
+    SniMismatch,
+    UnsupportedSigscheme,
+    NoCertYet,
+}
+
+#[derive(Debug, Default)]
+struct UnresolvedCounts {
+    no_sni: Counter,
+    invalid_sni: Counter,
+    sni_mismatch: Counter,
+    unsupported_sigscheme: Counter,
+    no_cert_yet: Counter,
+}
+
+/// Durations of each phase of `Store::set_certificate`.
+#[derive(Debug, Default)]
+struct Install {
+    validate: Histogram<latency::Us>,
+    build: Histogram<latency::Us>,
+    publish: Histogram<latency::Us>,
+}
+
+struct Phase(&'static str);
+
+// === impl Metrics ===
+
+impl Metrics {
+    pub(super) fn installed(
+        &self,
+        expiry: SystemTime,
+        validate: Duration,
+        build: Duration,
+        publish: Duration,
+    ) {
+        self.installs.incr();
+        *self.cert_expiry.lock() = Some(expiry);
+        self.install.validate.add(validate);
+        self.install.build.add(build);
+        self.install.publish.add(publish);
+    }
+
+    pub(super) fn resolver_size(&self, bytes: usize) {
+        self.resolver_max_bytes
+            .fetch_max(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(super) fn roots_loaded(&self, expiry: Option<SystemTime>) {
+        *self.roots_expiry.lock() = expiry;
+    }
+
+    /// Records a ClientHello for which no certificate was resolved.
+    ///
+    /// Counts are not labeled by name, since clients control the SNI value
+    /// and could otherwise create arbitrarily many series.
+    pub(super) fn unresolved(&self, reason: Unresolved) {
+        self.unresolved.get(reason).incr();
+    }
+
+    pub(super) fn sni_mismatches(&self) -> u64 {
+        self.unresolved.sni_mismatch.value() as u64
+    }
+
+    pub(super) fn resolution_failures(&self) -> ResolutionFailures {
+        let count = |reason| self.unresolved.get(reason).value() as u64;
+        ResolutionFailures {
+            no_sni: count(Unresolved::NoSni),
+            invalid_sni: count(Unresolved::InvalidSni),
+            sni_mismatch: count(Unresolved::SniMismatch),
+            unsupported_sigscheme: count(Unresolved::UnsupportedSigscheme),
+            no_cert_yet: count(Unresolved::NoCertYet),
+        }
+    }
+}
+
+impl FmtMetrics for Metrics {
+    fn fmt_metrics(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        identity_cert_installs_total.fmt_help(f)?;
+        identity_cert_installs_total.fmt_metric(f, &*self.installs)?;
+
+        let cert_expiry = *self.cert_expiry.lock();
+        if let Some(Ok(dur)) = cert_expiry.map(|expiry| expiry.duration_since(UNIX_EPOCH)) {
+            identity_cert_expiration_timestamp_seconds.fmt_help(f)?;
+            identity_cert_expiration_timestamp_seconds
+                .fmt_metric(f, &Gauge::from(dur.as_secs()))?;
+        }
+
+        identity_cert_resolver_max_bytes.fmt_help(f)?;
+        identity_cert_resolver_max_bytes.fmt_metric(
+            f,
+            &Gauge::from(self.resolver_max_bytes.load(Ordering::Relaxed)),
+        )?;
+
+        identity_cert_install_duration_us.fmt_help(f)?;
+        identity_cert_install_duration_us.fmt_metric_labeled(
+            f,
+            &self.install.validate,
+            &Phase("validate"),
+        )?;
+        identity_cert_install_duration_us.fmt_metric_labeled(
+            f,
+            &self.install.build,
+            &Phase("build"),
+        )?;
+        identity_cert_install_duration_us.fmt_metric_labeled(
+            f,
+            &self.install.publish,
+            &Phase("publish"),
+        )?;
+
+        let roots_expiry = *self.roots_expiry.lock();
+        if let Some(Ok(dur)) = roots_expiry.map(|expiry| expiry.duration_since(UNIX_EPOCH)) {
+            identity_roots_expiration_timestamp_seconds.fmt_help(f)?;
+            identity_roots_expiration_timestamp_seconds
+                .fmt_metric(f, &Gauge::from(dur.as_secs()))?;
+        }
+
+        identity_sni_mismatches_total.fmt_help(f)?;
+        identity_sni_mismatches_total.fmt_metric(f, &self.unresolved.sni_mismatch)?;
+
+        identity_cert_resolution_failures_total.fmt_help(f)?;
+        for reason in Unresolved::ALL {
+            identity_cert_resolution_failures_total.fmt_metric_labeled(
+                f,
+                self.unresolved.get(reason),
+                &reason,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+// === impl Unresolved ===
+
+impl Unresolved {
+    const ALL: [Self; 5] = [
+        Self::NoSni,
+        Self::InvalidSni,
+        Self::SniMismatch,
+        Self::UnsupportedSigscheme,
+        Self::NoCertYet,
+    ];
+
+    pub(super) fn as_str(&self) -> &'static str {
+        match self {
+            Self::NoSni => "no_sni",
+            Self::InvalidSni => "invalid_sni",
+            Self::SniMismatch => "sni_mismatch",
+            Self::UnsupportedSigscheme => "unsupported_sigscheme",
+            Self::NoCertYet => "no_cert_yet",
+        }
+    }
+}
+
+impl FmtLabels for Unresolved {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "reason=\"{}\"", self.as_str())
+    }
+}
+
+// === impl UnresolvedCounts ===
+
+impl UnresolvedCounts {
+    fn get(&self, reason: Unresolved) -> &Counter {
+        match reason {
+            Unresolved::NoSni => &self.no_sni,
+            Unresolved::InvalidSni => &self.invalid_sni,
+            Unresolved::SniMismatch => &self.sni_mismatch,
+            Unresolved::UnsupportedSigscheme => &self.unsupported_sigscheme,
+            Unresolved::NoCertYet => &self.no_cert_yet,
+        }
+    }
+}
+
+// === impl Phase ===
+
+impl FmtLabels for Phase {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "phase=\"{}\"", self.0)
+    }
+}
@@ -0,0 +1,169 @@
+//! Lazily fetches OCSP responses to staple to the served certificate.
+
+use super::x509;
+use linkerd_error::Result;
+use parking_lot::RwLock;
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Weak},
+    time::{Duration, SystemTime},
+};
+use tokio_rustls::rustls;
+use tracing::{debug, warn};
+
+/// Fetches OCSP responses from the responder named by a certificate.
+///
+/// Implementations are responsible for building the OCSP request and for the
+/// transport (e.g. HTTP) used to reach the responder.
+pub trait OcspFetcher: Send + Sync + 'static {
+    /// Fetches a response for the leaf of `chain` from `responder`.
+    ///
+    /// The chain is presented leaf-first, as it is installed.
+    fn fetch(
+        &self,
+        responder: &str,
+        chain: &[rustls::Certificate],
+    ) -> Pin<Box<dyn Future<Output = Result<OcspResponse>> + Send + 'static>>;
+}
+
+/// A DER-encoded OCSP response.
+#[derive(Clone, Debug)]
+pub struct OcspResponse {
+    pub der: Vec<u8>,
+
+    /// The response's `nextUpdate` time, before which a new response is
+    /// fetched.
+    pub next_update: SystemTime,
+}
+
+/// The served certificate with a stapled OCSP response, and the time after
+/// which the response is stale.
+pub(super) type Staple = RwLock<Option<(Arc<rustls::sign::CertifiedKey>, SystemTime)>>;
+
+/// How long to wait before retrying a failed fetch, which is also the soonest
+/// that a response is refreshed.
+const RETRY: Duration = Duration::from_secs(60);
+
+/// The longest that a response is stapled before it is refreshed, so that a
+/// revocation is picked up even when responses are fresh for days.
+const MAX_REFRESH: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// Spawns a task that keeps `staple` populated with a fresh OCSP response for
+/// `key`.
+///
+/// The task completes once the staple is dropped (i.e. when the certificate is
+/// replaced). Fetch failures never prevent the certificate from being served:
+/// at worst, it is served without a staple. Only responses that report the
+/// certificate as good are stapled. Responses' freshness is judged by `clock`,
+/// or by the system clock if none is configured.
+pub(super) fn spawn_refresh(
+    fetcher: Arc<dyn OcspFetcher>,
+    key: Arc<rustls::sign::CertifiedKey>,
+    staple: Weak<Staple>,
+    clock: Option<Arc<dyn Fn() -> SystemTime + Send + Sync>>,
+) {
+    let leaf = key.cert.first().map(|leaf| {
+        x509::Cert::parse(leaf.as_ref())
+            .and_then(|leaf| Ok((leaf.ocsp_responder()?, leaf.serial().to_vec())))
+    });
+    let (responder, serial) = match leaf {
+        Some(Ok((Some(responder), serial))) => (responder.to_string(), serial),
+        Some(Ok((None, _))) | None => {
+            debug!("Certificate names no OCSP responder");
+            return;
+        }
+        Some(Err(error)) => {
+            debug!(%error, "Could not read the certificate's OCSP responder");
+            return;
+        }
+    };
+    let rt = match tokio::runtime::Handle::try_current() {
+        Ok(rt) => rt,
+        Err(_) => {
+            debug!("No runtime; OCSP responses will not be stapled");
+            return;
+        }
+    };
+
+    rt.spawn(async move {
+        loop {
+            let rsp = fetcher.fetch(&responder, &key.cert).await;
+            let staple = match staple.upgrade() {
+                Some(staple) => staple,
+                None => return,
+            };
+
+            let now = match clock {
+                Some(ref clock) => clock(),
+                None => SystemTime::now(),
+            };
+            let refresh = match rsp {
+                Ok(OcspResponse { der, next_update }) => match x509::ocsp_status(&der, &serial) {
+                    Ok(Some(x509::OcspStatus::Good)) => {
+                        debug!(%responder, "Stapling OCSP response");
+                        let mut stapled = (*key).clone();
+                        stapled.ocsp = Some(der);
+                        *staple.write() = Some((Arc::new(stapled), next_update));
+                        refresh_after(next_update, now)
+                    }
+                    Ok(Some(x509::OcspStatus::Revoked)) => {
+                        warn!(%responder, "OCSP responder reports the certificate as revoked");
+                        *staple.write() = None;
+                        RETRY
+                    }
+                    status => {
+                        warn!(%responder, ?status, "Ignoring OCSP response without a good status");
+                        clear_stale(&staple, now);
+                        RETRY
+                    }
+                },
+                Err(error) => {
+                    warn!(%responder, %error, "Failed to fetch OCSP response");
+                    clear_stale(&staple, now);
+                    RETRY
+                }
+            };
+            drop(staple);
+
+            tokio::time::sleep(refresh).await;
+        }
+    });
+}
+
+/// Returns how long to wait before refreshing a response that is fresh until
+/// `next_update`: halfway there, but no sooner than `RETRY` (so that a nearly
+/// stale response doesn't cause a burst of fetches) and no later than
+/// `MAX_REFRESH`.
+fn refresh_after(next_update: SystemTime, now: SystemTime) -> Duration {
+    next_update
+        .duration_since(now)
+        .map_or(RETRY, |d| (d / 2).clamp(RETRY, MAX_REFRESH))
+}
+
+/// Drops the stapled response if it is no longer fresh.
+fn clear_stale(staple: &Staple, now: SystemTime) {
+    let mut staple = staple.write();
+    if matches!(*staple, Some((_, next_update)) if next_update <= now) {
+        *staple = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refreshes_are_bounded() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let refresh = |fresh_for| refresh_after(now + fresh_for, now);
+        assert_eq!(
+            refresh(Duration::from_secs(60 * 60)),
+            Duration::from_secs(30 * 60)
+        );
+        assert_eq!(refresh(Duration::from_secs(10)), RETRY);
+        assert_eq!(refresh(Duration::ZERO), RETRY);
+        assert_eq!(refresh_after(now - Duration::from_secs(10), now), RETRY);
+        assert_eq!(refresh(Duration::from_secs(7 * 24 * 60 * 60)), MAX_REFRESH);
+    }
+}
@@ -1,6 +1,10 @@
+use super::{
+    params::TlsParams,
+    store::{NoClientCert, RotationInfo},
+};
 use crate::{NewClient, Server};
 use linkerd_identity::Name;
-use std::sync::Arc;
+use std::{sync::Arc, time::SystemTime};
 use tokio::sync::watch;
 use tokio_rustls::rustls;
 
@@ -8,8 +12,42 @@ use tokio_rustls::rustls;
 #[derive(Clone)]
 pub struct Receiver {
     name: Name,
+    params: TlsParams,
+    clock: Option<Arc<dyn Fn() -> SystemTime + Send + Sync>>,
+    leaf_rx: watch::Receiver<Option<Leaf>>,
+    rotation_rx: watch::Receiver<Option<RotationInfo>>,
     client_rx: watch::Receiver<Arc<rustls::ClientConfig>>,
-    server_rx: watch::Receiver<Arc<rustls::ServerConfig>>,
+    server_rx: watch::Receiver<ServerState>,
+}
+
+/// Indicates whether the local identity can currently be used, e.g. to answer
+/// readiness probes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Readiness {
+    /// No certificate has been installed yet.
+    NotProvisioned,
+
+    /// A certificate is installed and has not expired.
+    Ready,
+
+    /// The installed certificate has expired.
+    Expired,
+}
+
+/// Describes the installed leaf certificate.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Leaf {
+    pub(super) not_after: SystemTime,
+    pub(super) sha256: [u8; 32],
+}
+
+/// A server configuration and the leaf that it presents, which are published
+/// together so that the leaf a receiver reports is always the one its servers
+/// present.
+#[derive(Clone)]
+pub(crate) struct ServerState {
+    pub(crate) config: Arc<rustls::ServerConfig>,
+    pub(crate) leaf: Option<Leaf>,
 }
 
 // === impl Receiver ===
@@ -17,11 +55,19 @@ pub struct Receiver {
 impl Receiver {
     pub(super) fn new(
         name: Name,
+        params: TlsParams,
+        clock: Option<Arc<dyn Fn() -> SystemTime + Send + Sync>>,
+        leaf_rx: watch::Receiver<Option<Leaf>>,
+        rotation_rx: watch::Receiver<Option<RotationInfo>>,
         client_rx: watch::Receiver<Arc<rustls::ClientConfig>>,
-        server_rx: watch::Receiver<Arc<rustls::ServerConfig>>,
+        server_rx: watch::Receiver<ServerState>,
     ) -> Self {
         Self {
             name,
+            params,
+            clock,
+            leaf_rx,
+            rotation_rx,
             client_rx,
             server_rx,
         }
@@ -32,21 +78,163 @@ impl Receiver {
         &self.name
     }
 
+    /// Returns the cipher suites enabled on client and server configurations.
+    pub fn cipher_suites(&self) -> &[rustls::SupportedCipherSuite] {
+        &self.params.cipher_suites
+    }
+
+    /// Returns the key exchange groups enabled on client and server
+    /// configurations.
+    pub fn kx_groups(&self) -> &[&'static rustls::SupportedKxGroup] {
+        &self.params.kx_groups
+    }
+
+    /// Returns whether a certificate is installed and, if so, whether it has
+    /// expired according to the configured clock.
+    pub fn readiness(&self) -> Readiness {
+        let expiry = match self.server_rx.borrow().leaf {
+            Some(ref leaf) => leaf.not_after,
+            None => return Readiness::NotProvisioned,
+        };
+        let now = match self.clock {
+            Some(ref clock) => clock(),
+            None => SystemTime::now(),
+        };
+        if now < expiry {
+            Readiness::Ready
+        } else {
+            Readiness::Expired
+        }
+    }
+
+    /// Returns the SHA-256 digest of the installed leaf certificate, or `None`
+    /// if no certificate has been installed.
+    ///
+    /// Receivers that have installed the same certificate report the same
+    /// digest, so this may be compared across instances that share an
+    /// identity to confirm that they have all rotated.
+    pub fn identity_digest(&self) -> Option<[u8; 32]> {
+        self.server_rx.borrow().leaf.map(|leaf| leaf.sha256)
+    }
+
+    /// Returns the SHA-256 fingerprint of the installed leaf certificate, as
+    /// lowercase hex (e.g. for admin endpoints), or `None` if no certificate
+    /// has been installed.
+    ///
+    /// This encodes `identity_digest`, which is computed from the certificate
+    /// that servers present, so it changes with each rotation.
+    pub fn leaf_fingerprint(&self) -> Option<String> {
+        self.identity_digest().map(hex::encode)
+    }
+
+    /// Returns the expiry of the installed leaf certificate, or `None` if no
+    /// certificate has been installed.
+    ///
+    /// The store publishes each leaf with the server configuration that
+    /// presents it, so this always describes the certificate that servers
+    /// built from this receiver present.
+    pub fn cert_expiry(&self) -> Option<SystemTime> {
+        self.server_rx.borrow().leaf.map(|leaf| leaf.not_after)
+    }
+
+    /// Returns a watch that is updated as each leaf certificate is installed,
+    /// e.g. to alert on impending expiry.
+    ///
+    /// Each leaf is sent once the configurations that present it have been
+    /// published.
+    pub fn leaf_updates(&self) -> watch::Receiver<Option<Leaf>> {
+        self.leaf_rx.clone()
+    }
+
+    /// Returns a watch that describes each certificate as it is published,
+    /// e.g. so that a task may audit every rotation.
+    ///
+    /// Each description is sent once the client and server configurations
+    /// that present the certificate have been published, and its generation
+    /// increases with each certificate the store publishes. Like other
+    /// watches, a slow observer may only see the latest rotation, though the
+    /// generation reveals how many it missed.
+    pub fn rotations(&self) -> watch::Receiver<Option<RotationInfo>> {
+        self.rotation_rx.clone()
+    }
+
+    /// Completes once the store that publishes this receiver's configurations
+    /// is dropped, e.g. so that a supervising task can react to the
+    /// credentials being torn down.
+    ///
+    /// This watches its own copy of the receiver's channels, so it doesn't
+    /// mark any update as seen.
+    pub async fn closed(&self) {
+        let mut leaf_rx = self.leaf_rx.clone();
+        while leaf_rx.changed().await.is_ok() {}
+    }
+
     /// Returns a `NewClient` that can be used to establish TLS on client connections.
     pub fn new_client(&self) -> NewClient {
         NewClient::new(self.client_rx.clone())
     }
 
+    /// Returns a copy of the current client configuration that never presents
+    /// a client certificate.
+    ///
+    /// This is intended for upstreams that don't participate in the mesh and
+    /// may fail handshakes when offered a client certificate. Server
+    /// certificates are still verified against the proxy's trust roots.
+    pub fn client_config_without_client_auth(&self) -> Arc<rustls::ClientConfig> {
+        let mut config = (**self.client_rx.borrow()).clone();
+        config.client_auth_cert_resolver = Arc::new(NoClientCert);
+        config.into()
+    }
+
     /// Returns a `Server` that can be used to terminate TLS on server connections.
     pub fn server(&self) -> Server {
         Server::new(self.name.clone(), self.server_rx.clone())
     }
+
+    /// Returns the current client configuration, e.g. for diagnostics.
+    ///
+    /// This only borrows the latest configuration, so it doesn't mark it as
+    /// seen: clients built from this receiver still observe every update.
+    pub fn client_config(&self) -> Arc<rustls::ClientConfig> {
+        self.client_rx.borrow().clone()
+    }
+
+    /// Returns the current server configuration and the leaf it presents, as
+    /// published together.
+    #[cfg(test)]
+    pub(crate) fn server_state(&self) -> ServerState {
+        self.server_rx.borrow().clone()
+    }
+
+    /// Returns the current server configuration, e.g. for diagnostics.
+    ///
+    /// This only borrows the latest configuration, so it doesn't mark it as
+    /// seen: servers built from this receiver still observe every update.
+    pub fn server_config(&self) -> Arc<rustls::ServerConfig> {
+        self.server_rx.borrow().config.clone()
+    }
+}
+
+// === impl Leaf ===
+
+impl Leaf {
+    /// Returns the time after which the certificate is no longer valid.
+    pub fn expiry(&self) -> SystemTime {
+        self.not_after
+    }
+
+    /// Returns the SHA-256 digest of the certificate's DER encoding.
+    pub fn sha256(&self) -> [u8; 32] {
+        self.sha256
+    }
 }
 
 impl std::fmt::Debug for Receiver {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Receiver")
             .field("name", &self.name)
+            .field("params", &self.params)
+            .field("leaf", &self.server_rx.borrow().leaf)
             .finish()
     }
 }
@@ -67,6 +255,10 @@ mod tests {
             .with_cert_resolver(Arc::new(rustls::server::ResolvesServerCertUsingSni::new()))
     }
 
+    fn server_state(config: Arc<rustls::ServerConfig>) -> ServerState {
+        ServerState { config, leaf: None }
+    }
+
     /// Returns the simplest default rustls client config.
     ///
     /// This configuration will fail to handshake with any TLS servers, because
@@ -82,10 +274,14 @@ mod tests {
     #[tokio::test]
     async fn test_server() {
         let init_config = Arc::new(empty_server_config());
-        let (server_tx, server_rx) = watch::channel(init_config.clone());
+        let (server_tx, server_rx) = watch::channel(server_state(init_config.clone()));
         let (_, client_rx) = watch::channel(Arc::new(empty_client_config()));
         let receiver = Receiver {
             name: "example".parse().unwrap(),
+            params: TlsParams::new(&Default::default()).unwrap(),
+            clock: None,
+            leaf_rx: watch::channel(None).1,
+            rotation_rx: watch::channel(None).1,
             server_rx,
             client_rx,
         };
@@ -96,7 +292,7 @@ mod tests {
 
         let server_config = Arc::new(empty_server_config());
         server_tx
-            .send(server_config.clone())
+            .send(server_state(server_config.clone()))
             .expect("receiver is held");
 
         assert!(Arc::ptr_eq(&server.config(), &server_config));
@@ -105,10 +301,14 @@ mod tests {
     #[tokio::test]
     async fn test_spawn_server_with_alpn() {
         let init_config = Arc::new(empty_server_config());
-        let (server_tx, server_rx) = watch::channel(init_config.clone());
+        let (server_tx, server_rx) = watch::channel(server_state(init_config.clone()));
         let (_, client_rx) = watch::channel(Arc::new(empty_client_config()));
         let receiver = Receiver {
             name: "example".parse().unwrap(),
+            params: TlsParams::new(&Default::default()).unwrap(),
+            clock: None,
+            leaf_rx: watch::channel(None).1,
+            rotation_rx: watch::channel(None).1,
             server_rx,
             client_rx,
         };
@@ -125,7 +325,7 @@ mod tests {
         let update_config = Arc::new(empty_server_config());
         assert!(!Arc::ptr_eq(&update_config, &init_config));
         server_tx
-            .send(update_config.clone())
+            .send(server_state(update_config.clone()))
             .expect("receiver is held");
 
         // Give the update task a chance to run.
@@ -1,41 +1,332 @@
-use super::params::*;
-use linkerd_error::Result;
+use super::{
+    metrics::{Metrics, ResolutionFailures, Unresolved},
+    ocsp,
+    params::*,
+    receiver::{Leaf, ServerState},
+    spki_key_type,
+    verify::{
+        ClientCaHints, MaxIntermediates, MaxLifetime, PermittedDomains, PinnedIntermediates,
+        PinnedSerials, SignatureAlgorithms, StapledOcsp, TrustDomains,
+    },
+    x509, AnyKey, CertKeyAlgorithmMismatch, ClientAuth, CsrSanMismatch, CsrSanPolicy,
+    EmptyCertificate, EmptyChain, Expired, IdentityLimit, IdentityMismatch, InvalidTrustRoots,
+    KeyIdentifierPolicy, KeyMismatch, MisorderedChain, MissingKeyIdentifier, NoCertificate,
+    NotYetValid, Options, OwnIdentity, PathLenExceeded, TooManyIdentities, UnresolvableIdentity,
+    UnsupportedKeyAlgorithm, UntrustedChain,
+};
+use linkerd_error::{Error, Result};
 use linkerd_identity as id;
-use ring::{rand, signature::EcdsaKeyPair};
-use std::{convert::TryFrom, sync::Arc};
-use tokio::sync::watch;
-use tokio_rustls::rustls;
-use tracing::debug;
+use parking_lot::Mutex;
+use ring::{
+    rand,
+    signature::{self, KeyPair},
+};
+use std::{
+    convert::{TryFrom, TryInto},
+    sync::Arc,
+};
+use tokio::{sync::watch, time::Instant};
+use tokio_rustls::rustls::{self, client::ServerCertVerifier, server::ResolvesServerCert};
+use tracing::{debug, debug_span, field, trace, warn};
 
+/// Holds the credentials for a local identity.
+///
+/// A store's name, key, and CSR are fixed when it is created by `watch`, and
+/// clients always present its own certificate. Servers may also present
+/// certificates for other identities that its key is certified for, selected
+/// by SNI (see `Store::set_certificate_for`).
 pub struct Store {
+    params: TlsParams,
     roots: rustls::RootCertStore,
+    roots_expiry: Option<std::time::SystemTime>,
+    roots_sha256: ring::digest::Digest,
+    trusted_roots: Vec<TrustedRoot>,
+    roots_info: TrustRootsInfo,
     server_cert_verifier: Arc<dyn rustls::client::ServerCertVerifier>,
-    key: Arc<EcdsaKeyPair>,
+    custom_verifier: bool,
+    key: AnyKey,
     csr: Arc<[u8]>,
     name: id::Name,
+    options: Options,
     client_tx: watch::Sender<Arc<rustls::ClientConfig>>,
-    server_tx: watch::Sender<Arc<rustls::ServerConfig>>,
+    server_tx: watch::Sender<ServerState>,
+    leaf_tx: watch::Sender<Option<Leaf>>,
+    rotation_tx: watch::Sender<Option<RotationInfo>>,
+    generation: u64,
+    leaf: Option<Leaf>,
+    alpn_protocols: Vec<Vec<u8>>,
+    resolver: Option<Arc<CertResolver>>,
+
+    /// Presents a separate chain in client handshakes, when one is installed
+    /// with `Store::install_certificates`. Otherwise, clients present the
+    /// server's chain.
+    client_resolver: Option<Arc<CertResolver>>,
+
+    /// Certificates for other identities, which servers present to clients
+    /// that request them via SNI.
+    identities: Vec<Identity>,
+
+    rng: Option<Rng>,
+}
+
+/// The outcome of [`Store::install_certificate`], [`Store::install_certificates`],
+/// or [`Store::install_chain`].
+#[derive(Debug)]
+pub enum Install {
+    /// The certificate was validated and new configurations were published.
+    Published,
+
+    /// The certificate was validated, but no receivers remain to observe the
+    /// new configurations.
+    NoReceivers,
+
+    /// The certificate was rejected and no configurations were published.
+    ValidationFailed(Error),
+}
+
+/// Describes an installed certificate, for audit logging.
+#[derive(Clone, Debug)]
+pub struct InstallRecord {
+    pub identity: id::Name,
+
+    /// The SHA-256 digest of the leaf certificate.
+    pub leaf_sha256: [u8; 32],
+
+    pub not_before: std::time::SystemTime,
+    pub not_after: std::time::SystemTime,
+
+    /// The DER-encoded distinguished name of the leaf's issuer.
+    pub issuer: Vec<u8>,
+
+    /// The number of certificates in the chain, including the leaf.
+    pub chain_len: usize,
+
+    /// The SHA-256 digest of the trust roots the chain was validated against.
+    pub roots_sha256: [u8; 32],
+}
+
+/// Describes a published certificate, e.g. so that rotations may be audited
+/// without inspecting configurations (see `Receiver::rotations`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RotationInfo {
+    /// Counts the certificates that the store has published, starting at 1.
+    pub generation: u64,
+
+    /// The leaf's big-endian serial number, without leading zero octets.
+    pub serial: Vec<u8>,
+
+    /// The SHA-256 digest of the leaf certificate.
+    pub leaf_sha256: [u8; 32],
+
+    pub not_before: std::time::SystemTime,
+    pub not_after: std::time::SystemTime,
+}
+
+/// How the certificates after the leaf in an installed chain must be ordered.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Order {
+    /// Each certificate must issue the one before it.
+    Path,
+
+    /// Certificates may be presented in any order.
+    Any,
+}
+
+/// Describes a trust root that a store verifies peers against, for debugging
+/// (e.g. `CN=root.linkerd.cluster.local, fp=1a2b...`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TrustedRoot {
+    /// The root's subject, as an RFC 4514 distinguished name.
+    pub subject: String,
+
+    /// The SHA-256 digest of the root certificate.
+    pub sha256: [u8; 32],
+}
+
+/// Counts the trust roots that a store loaded, e.g. to check that every
+/// expected root was usable.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TrustRootsInfo {
+    /// The number of roots that were added.
+    pub added: usize,
+
+    /// The number of certificates that were skipped because they couldn't be
+    /// used as trust anchors.
+    pub skipped: usize,
+
+    /// The number of certificates that were skipped because they had already
+    /// been added.
+    pub duplicates: usize,
+
+    /// The number of added roots that had expired when they were loaded.
+    pub expired: usize,
 }
 
+/// Describes a ClientHello received by a server, for debugging.
+#[derive(Clone, Debug)]
+pub struct ClientHelloRecord {
+    /// The SNI value, if the client sent one.
+    pub server_name: Option<String>,
+
+    /// The signature schemes offered by the client, in its order of
+    /// preference.
+    pub signature_schemes: Vec<rustls::SignatureScheme>,
+
+    /// The ALPN protocols offered by the client, if it sent the extension.
+    pub alpn_protocols: Option<Vec<Vec<u8>>>,
+
+    /// The cipher suites offered by the client, in its order of preference.
+    pub cipher_suites: Vec<rustls::CipherSuite>,
+}
+
+/// A source of randomness for signatures, used in place of `SystemRandom` so
+/// that tests may sign deterministically.
+type Rng = Arc<dyn rand::SecureRandom + Send + Sync>;
+
+/// The trust roots that a store validates certificates against, while they
+/// are swapped by `Store::rotate`.
+struct Roots {
+    roots: rustls::RootCertStore,
+    expiry: Option<std::time::SystemTime>,
+    sha256: ring::digest::Digest,
+    trusted: Vec<TrustedRoot>,
+    info: TrustRootsInfo,
+    verifier: Arc<dyn rustls::client::ServerCertVerifier>,
+}
+
+/// Signs handshakes with a key, using the scheme that was negotiated for it.
+struct KeySigner {
+    key: AnyKey,
+    scheme: rustls::SignatureScheme,
+    offload: bool,
+    rng: Option<Rng>,
+}
+
+/// A key whose signatures are computed with `tokio::task::block_in_place` on
+/// multi-threaded runtimes (see `Options::offload_rsa_signatures`), or with
+/// an injected source of randomness.
 #[derive(Clone)]
-struct Key(Arc<EcdsaKeyPair>);
+struct ConfiguredKey {
+    key: AnyKey,
+    offload: bool,
+    rng: Option<Rng>,
+}
 
 #[derive(Clone)]
-struct CertResolver(Arc<rustls::sign::CertifiedKey>);
+struct CertResolver {
+    key: Arc<rustls::sign::CertifiedKey>,
+
+    /// The type of the certificate's key, which determines the signature
+    /// schemes it may use.
+    key_type: KeyType,
+
+    /// The leaf certificate's expiry, if it could be determined.
+    not_after: Option<std::time::SystemTime>,
+
+    /// When set, every resolution decision is logged at the TRACE level.
+    trace_decisions: bool,
+
+    /// When set, our certificate is served to clients that send no SNI.
+    serve_without_sni: bool,
+
+    /// When set, called with a summary of each ClientHello.
+    on_client_hello: Option<Arc<dyn Fn(&ClientHelloRecord) + Send + Sync>>,
+
+    /// The certificate with a stapled OCSP response, once one is fetched.
+    staple: Arc<ocsp::Staple>,
 
+    /// Counts ClientHellos for which no certificate is resolved.
+    metrics: Metrics,
+
+    /// Determines whether the leaf has expired and whether a stapled response
+    /// is fresh (see `Options::clock`).
+    clock: Option<Arc<dyn Fn() -> std::time::SystemTime + Send + Sync>>,
+}
+
+/// Resolves server certificates for the identity that a ClientHello's SNI
+/// names, or else with our own certificate.
+struct SniResolver {
+    primary: Arc<CertResolver>,
+    identities: Vec<Identity>,
+}
+
+/// A certificate for another local identity (see `Store::set_certificate_for`).
+#[derive(Clone)]
+struct Identity {
+    name: id::Name,
+    resolver: Arc<CertResolver>,
+
+    /// When a server last resolved the certificate (or when it was first
+    /// installed, if none has), which orders evictions under
+    /// `IdentityLimit::EvictLeastRecentlyUsed`.
+    last_resolved: Arc<Mutex<Instant>>,
+}
+
+/// A server certificate resolver for use until a certificate is installed,
+/// which only presents the bootstrap certificate (if one is configured).
+pub(super) struct AwaitingCert {
+    bootstrap: Option<Arc<rustls::server::ResolvesServerCertUsingSni>>,
+    metrics: Metrics,
+}
+
+/// A client certificate resolver that never presents a certificate.
+#[derive(Clone, Debug)]
+pub(super) struct NoClientCert;
+
+/// Returns a builder for client configurations that verify servers with the
+/// provided verifier.
+///
+/// Client certificates are only presented when the server requests one during
+/// the handshake. Rustls does not implement TLS 1.3 post-handshake client
+/// authentication, so the `post_handshake_auth` extension is never offered and
+/// a server cannot request a client certificate after the handshake completes.
+/// Upstreams that require client authentication must request it up front.
 pub(super) fn client_config_builder(
+    params: &TlsParams,
     cert_verifier: Arc<dyn rustls::client::ServerCertVerifier>,
 ) -> rustls::ConfigBuilder<rustls::ClientConfig, rustls::client::WantsClientCert> {
+    let cert_verifier: Arc<dyn rustls::client::ServerCertVerifier> =
+        Arc::new(StapledOcsp::new(cert_verifier));
+    let cert_verifier: Arc<dyn rustls::client::ServerCertVerifier> = match params.allowed_serials {
+        Some(ref serials) => Arc::new(PinnedSerials::new(cert_verifier, serials.clone())),
+        None => cert_verifier,
+    };
+    let cert_verifier: Arc<dyn rustls::client::ServerCertVerifier> =
+        match params.pinned_intermediates {
+            Some(ref pins) => Arc::new(PinnedIntermediates::new(cert_verifier, pins.clone())),
+            None => cert_verifier,
+        };
+    let cert_verifier: Arc<dyn rustls::client::ServerCertVerifier> =
+        match params.trust_domain_migration {
+            Some(ref migration) => Arc::new(TrustDomains::new(cert_verifier, migration.clone())),
+            None => cert_verifier,
+        };
+    let cert_verifier: Arc<dyn rustls::client::ServerCertVerifier> = match params.max_peer_lifetime
+    {
+        Some(max) => Arc::new(MaxLifetime::new(cert_verifier, max)),
+        None => cert_verifier,
+    };
+    let cert_verifier: Arc<dyn rustls::client::ServerCertVerifier> =
+        match params.signature_algorithms {
+            Some(ref allowed) => Arc::new(SignatureAlgorithms::new(cert_verifier, allowed.clone())),
+            None => cert_verifier,
+        };
+    let cert_verifier: Arc<dyn rustls::client::ServerCertVerifier> = match params
+        .permitted_dns_domains
+    {
+        Some(ref permitted) => Arc::new(PermittedDomains::new(cert_verifier, permitted.clone())),
+        None => cert_verifier,
+    };
+    let cert_verifier: Arc<dyn rustls::client::ServerCertVerifier> = match params.max_intermediates
+    {
+        Some(max) => Arc::new(MaxIntermediates::new(cert_verifier, max)),
+        None => cert_verifier,
+    };
     rustls::ClientConfig::builder()
-        .with_cipher_suites(TLS_SUPPORTED_CIPHERSUITES)
-        .with_safe_default_kx_groups()
-        .with_protocol_versions(TLS_VERSIONS)
+        .with_cipher_suites(&params.cipher_suites)
+        .with_kx_groups(&params.kx_groups)
+        .with_protocol_versions(params.versions)
         .expect("client config must be valid")
-        // XXX: Rustls's built-in verifiers don't let us tweak things as fully
-        // as we'd like (e.g. controlling the set of trusted signature
-        // algorithms), but they provide good enough defaults for now.
-        // TODO: lock down the verification further.
-        //
         // NOTE(eliza): Rustls considers setting a custom server cert verifier
         // to be a "dangerous configuration", but we're doing *exactly* what its
         // builder API does internally. However, we want to share the verifier
@@ -44,85 +335,1389 @@ pub(super) fn client_config_builder(
         .with_custom_certificate_verifier(cert_verifier)
 }
 
+/// Returns a verifier that validates servers against `roots`.
+pub(super) fn root_verifier(
+    roots: &rustls::RootCertStore,
+) -> Arc<dyn rustls::client::ServerCertVerifier> {
+    Arc::new(rustls::client::WebPkiVerifier::new(
+        roots.clone(),
+        None, // no certificate transparency policy
+    ))
+}
+
+/// Returns the client session resumption configuration.
+///
+/// Session resumption is disabled unless a session store is configured (or
+/// resumption is enabled, which configures one), until resumption is more
+/// tested.
+pub(super) fn resumption(options: &Options) -> rustls::client::Resumption {
+    match options.client_sessions {
+        Some(ref sessions) => rustls::client::Resumption::store(sessions.clone()),
+        None => rustls::client::Resumption::disabled(),
+    }
+}
+
 pub(super) fn server_config(
+    params: &TlsParams,
     roots: rustls::RootCertStore,
     resolver: Arc<dyn rustls::server::ResolvesServerCert>,
 ) -> Arc<rustls::ServerConfig> {
-    // Ask TLS clients for a certificate and accept any certificate issued by our trusted CA(s).
-    //
-    // XXX: Rustls's built-in verifiers don't let us tweak things as fully as we'd like (e.g.
-    // controlling the set of trusted signature algorithms), but they provide good enough
-    // defaults for now.
-    // TODO: lock down the verification further.
-    let client_cert_verifier = Arc::new(
-        rustls::server::AllowAnyAnonymousOrAuthenticatedClient::new(roots),
-    );
-    rustls::ServerConfig::builder()
-        .with_cipher_suites(TLS_SUPPORTED_CIPHERSUITES)
-        .with_safe_default_kx_groups()
-        .with_protocol_versions(TLS_VERSIONS)
+    // Ask TLS clients for a certificate (requiring one, if configured) and accept any certificate
+    // issued by our trusted CA(s) that satisfies the configured constraints.
+    let client_cert_verifier: Arc<dyn rustls::server::ClientCertVerifier> = match params.client_auth
+    {
+        ClientAuth::Optional => Arc::new(
+            rustls::server::AllowAnyAnonymousOrAuthenticatedClient::new(roots),
+        ),
+        ClientAuth::Required => Arc::new(rustls::server::AllowAnyAuthenticatedClient::new(roots)),
+    };
+    let client_cert_verifier: Arc<dyn rustls::server::ClientCertVerifier> = match params
+        .allowed_serials
+    {
+        Some(ref serials) => Arc::new(PinnedSerials::new(client_cert_verifier, serials.clone())),
+        None => client_cert_verifier,
+    };
+    let client_cert_verifier: Arc<dyn rustls::server::ClientCertVerifier> = match params
+        .pinned_intermediates
+    {
+        Some(ref pins) => Arc::new(PinnedIntermediates::new(client_cert_verifier, pins.clone())),
+        None => client_cert_verifier,
+    };
+    let client_cert_verifier: Arc<dyn rustls::server::ClientCertVerifier> =
+        match params.max_peer_lifetime {
+            Some(max) => Arc::new(MaxLifetime::new(client_cert_verifier, max)),
+            None => client_cert_verifier,
+        };
+    let client_cert_verifier: Arc<dyn rustls::server::ClientCertVerifier> =
+        match params.signature_algorithms {
+            Some(ref allowed) => Arc::new(SignatureAlgorithms::new(
+                client_cert_verifier,
+                allowed.clone(),
+            )),
+            None => client_cert_verifier,
+        };
+    let client_cert_verifier: Arc<dyn rustls::server::ClientCertVerifier> =
+        match params.max_intermediates {
+            Some(max) => Arc::new(MaxIntermediates::new(client_cert_verifier, max)),
+            None => client_cert_verifier,
+        };
+    let client_cert_verifier: Arc<dyn rustls::server::ClientCertVerifier> = match params
+        .client_ca_hints
+    {
+        Some(ref subjects) => Arc::new(ClientCaHints::new(client_cert_verifier, subjects.clone())),
+        None => client_cert_verifier,
+    };
+    let mut config = rustls::ServerConfig::builder()
+        .with_cipher_suites(&params.cipher_suites)
+        .with_kx_groups(&params.kx_groups)
+        .with_protocol_versions(params.versions)
         .expect("server config must be valid")
         .with_client_cert_verifier(client_cert_verifier)
-        .with_cert_resolver(resolver)
-        .into()
+        .with_cert_resolver(resolver);
+    if let Some(ref ticketer) = params.ticketer {
+        config.ticketer = ticketer.0.clone();
+    }
+    config.into()
+}
+
+// === impl TrustedRoot ===
+
+impl TrustedRoot {
+    pub(super) fn from_der(der: &[u8]) -> Self {
+        let subject = x509::Cert::parse(der)
+            .and_then(|c| x509::format_name(c.subject()))
+            .unwrap_or_else(|_| String::from("<malformed>"));
+        Self {
+            subject,
+            sha256: sha256(der),
+        }
+    }
+}
+
+impl std::fmt::Display for TrustedRoot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}, fp={}", self.subject, hex::encode(self.sha256))
+    }
+}
+
+// === impl Install ===
+
+impl Install {
+    /// Fails if the certificate was rejected. Certificates that were installed
+    /// without receivers to observe them are not errors.
+    fn into_result(self) -> Result<()> {
+        match self {
+            Install::Published | Install::NoReceivers => Ok(()),
+            Install::ValidationFailed(error) => Err(error),
+        }
+    }
 }
 
 // === impl Store ===
 
 impl Store {
+    #[allow(clippy::too_many_arguments)]
     pub(super) fn new(
+        params: TlsParams,
         roots: rustls::RootCertStore,
+        roots_expiry: Option<std::time::SystemTime>,
+        roots_sha256: ring::digest::Digest,
+        trusted_roots: Vec<TrustedRoot>,
+        roots_info: TrustRootsInfo,
         server_cert_verifier: Arc<dyn rustls::client::ServerCertVerifier>,
-        key: EcdsaKeyPair,
+        custom_verifier: bool,
+        key: AnyKey,
         csr: &[u8],
         name: id::Name,
+        options: Options,
         client_tx: watch::Sender<Arc<rustls::ClientConfig>>,
-        server_tx: watch::Sender<Arc<rustls::ServerConfig>>,
+        server_tx: watch::Sender<ServerState>,
+        leaf_tx: watch::Sender<Option<Leaf>>,
+        rotation_tx: watch::Sender<Option<RotationInfo>>,
     ) -> Self {
         Self {
+            params,
             roots,
-            key: Arc::new(key),
+            roots_expiry,
+            roots_sha256,
+            trusted_roots,
+            roots_info,
+            key,
             server_cert_verifier,
+            custom_verifier,
             csr: csr.into(),
             name,
+            alpn_protocols: options.alpn_protocols.clone(),
+            options,
             client_tx,
             server_tx,
+            leaf_tx,
+            rotation_tx,
+            generation: 0,
+            leaf: None,
+            resolver: None,
+            client_resolver: None,
+            identities: Vec::new(),
+            rng: None,
         }
     }
 
-    /// Builds a new TLS client configuration.
-    fn client_config(&self, resolver: Arc<CertResolver>) -> Arc<rustls::ClientConfig> {
-        let mut cfg = client_config_builder(self.server_cert_verifier.clone())
-            .with_client_cert_resolver(resolver);
+    /// Signs with `rng` rather than the system's source of randomness, so
+    /// that tests may assert exact signatures.
+    ///
+    /// This applies to certificates that are installed afterwards.
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn set_rng_for_test(&mut self, rng: Arc<dyn rand::SecureRandom + Send + Sync>) {
+        self.rng = Some(rng);
+    }
+
+    /// Validates the certificate and publishes TLS client and server
+    /// configurations using it.
+    ///
+    /// Each intermediate must issue the certificate before it, and the last
+    /// certificate must be a trust root or be issued by one, so that a
+    /// misordered or incomplete chain is rejected with the certificate at which
+    /// it breaks, rather than failing peers' handshakes.
+    ///
+    /// Unlike `Credentials::set_certificate`, this distinguishes a rejected
+    /// certificate from configurations that were published without any
+    /// receivers to observe them (e.g. because the proxy is shutting down).
+    pub fn install_certificate(
+        &mut self,
+        leaf: id::DerX509,
+        intermediates: Vec<id::DerX509>,
+        expiry: std::time::SystemTime,
+    ) -> Install {
+        self.install_certificate_with_ocsp(leaf, intermediates, None, expiry)
+    }
+
+    /// Like `install_certificate`, but staples a DER-encoded OCSP response to
+    /// the certificate when it is served.
+    ///
+    /// The response is replaced by each installation, so a certificate that
+    /// is installed without one is served without a staple (unless
+    /// `Options::ocsp_fetcher` fetches one). A fresh fetched response is
+    /// preferred to this one.
+    pub fn install_certificate_with_ocsp(
+        &mut self,
+        leaf: id::DerX509,
+        intermediates: Vec<id::DerX509>,
+        ocsp: Option<Vec<u8>>,
+        expiry: std::time::SystemTime,
+    ) -> Install {
+        let mut chain = Vec::with_capacity(intermediates.len() + 1);
+        chain.push(leaf);
+        chain.extend(intermediates);
+        self.install(chain, ocsp, expiry, Order::Path)
+    }
+
+    /// Validates and installs a certificate chain that is presented to peers
+    /// exactly in the given order.
+    ///
+    /// The first certificate must be the leaf; the remaining certificates are
+    /// used for validation in any order but are never reordered or omitted
+    /// when presented.
+    pub fn install_chain(
+        &mut self,
+        chain: Vec<id::DerX509>,
+        expiry: std::time::SystemTime,
+    ) -> Install {
+        self.install(chain, None, expiry, Order::Any)
+    }
+
+    /// Like `install_certificate`, but presents a separate chain when acting
+    /// as a client, e.g. for PKIs that issue distinct certificates for server
+    /// and client authentication.
+    ///
+    /// The server chain is validated as by `install_certificate`; the client
+    /// chain is validated in the same way, except that its leaf must be usable
+    /// for client authentication rather than server authentication. Both must
+    /// certify our key. If either is rejected, nothing is installed. A
+    /// subsequent `install_certificate` installs a single chain for both
+    /// roles again.
+    pub fn install_certificates(
+        &mut self,
+        server_leaf: id::DerX509,
+        server_intermediates: Vec<id::DerX509>,
+        client_leaf: id::DerX509,
+        client_intermediates: Vec<id::DerX509>,
+        expiry: std::time::SystemTime,
+    ) -> Install {
+        let t0 = Instant::now();
+
+        let client = std::iter::once(client_leaf).chain(client_intermediates);
+        let client = match Self::certificates(client.collect()) {
+            Ok(chain) => chain,
+            Err(error) => return Install::ValidationFailed(error),
+        };
+        if let Err(error) = self.check_client_auth(&client) {
+            return Install::ValidationFailed(error);
+        }
+        let server = std::iter::once(server_leaf).chain(server_intermediates);
+        let server = match Self::certificates(server.collect()) {
+            Ok(chain) => chain,
+            Err(error) => return Install::ValidationFailed(error),
+        };
+        if let Err(error) = self.check(&server, Order::Path) {
+            return Install::ValidationFailed(error);
+        }
+        self.publish(server, Some(client), None, expiry, t0)
+    }
+
+    /// Like `Credentials::set_certificate`, but presents a separate chain when
+    /// acting as a client (see `install_certificates`).
+    pub fn set_certificates(
+        &mut self,
+        server_leaf: id::DerX509,
+        server_intermediates: Vec<id::DerX509>,
+        client_leaf: id::DerX509,
+        client_intermediates: Vec<id::DerX509>,
+        expiry: std::time::SystemTime,
+    ) -> Result<()> {
+        self.install_certificates(
+            server_leaf,
+            server_intermediates,
+            client_leaf,
+            client_intermediates,
+            expiry,
+        )
+        .into_result()
+    }
+
+    /// Validates a certificate for another local identity and presents it to
+    /// clients that request that identity via SNI, e.g. to terminate TLS for
+    /// several identities on one listener.
+    ///
+    /// The chain must certify our key, and is validated as by
+    /// `install_certificate` but for `identity` (and without comparing its
+    /// SANs with our CSR's, which requests our own identity). A certificate
+    /// for an identity replaces the one installed for it. ClientHellos that
+    /// name no other identity, including those without SNI, are resolved as
+    /// they would be otherwise.
+    ///
+    /// Returns an error if our own certificate hasn't been installed yet,
+    /// since servers present no certificates until it is. When
+    /// `Options::max_identities` are already installed, a certificate for a
+    /// new identity is handled as `Options::identity_limit` configures.
+    pub fn set_certificate_for(
+        &mut self,
+        identity: id::Name,
+        leaf: id::DerX509,
+        intermediates: Vec<id::DerX509>,
+    ) -> Result<()> {
+        let primary = self.resolver.clone().ok_or(NoCertificate(()))?;
+        if identity == self.name {
+            return Err(OwnIdentity(identity).into());
+        }
+        let chain = Self::certificates(std::iter::once(leaf).chain(intermediates).collect())?;
+        self.check_suitable_for(&identity, &chain, Order::Path)?;
+        self.validate_with(&*self.server_cert_verifier, &chain, &identity)?;
+
+        let resolver = self.resolver(chain, None);
+        if let Some(installed) = self.identities.iter_mut().find(|i| i.name == identity) {
+            installed.resolver = resolver;
+        } else {
+            self.make_room_for_identity()?;
+            self.identities.push(Identity {
+                name: identity,
+                resolver,
+                last_resolved: Arc::new(Mutex::new(Instant::now())),
+            });
+        }
+        self.options.metrics.resolver_size(self.resolver_size());
+        self.publish_configs(primary);
+        Ok(())
+    }
+
+    /// Ensures that another identity may be installed without exceeding
+    /// `Options::max_identities`, evicting one if `Options::identity_limit`
+    /// permits.
+    ///
+    /// An identity whose certificate is held outside the store (i.e. by a
+    /// handshake that is presenting it) is never evicted. Connections that
+    /// have completed their handshakes are unaffected by an eviction, since
+    /// their certificates have already been presented.
+    fn make_room_for_identity(&mut self) -> Result<(), TooManyIdentities> {
+        let max = match self.options.max_identities {
+            Some(max) if self.identities.len() >= max => max,
+            _ => return Ok(()),
+        };
+        if self.options.identity_limit == IdentityLimit::Reject {
+            return Err(TooManyIdentities { max });
+        }
+        let evicted = self
+            .identities
+            .iter()
+            .enumerate()
+            .filter(|(_, i)| Arc::strong_count(&i.resolver.key) == 1)
+            .min_by_key(|(_, i)| *i.last_resolved.lock())
+            .map(|(idx, _)| idx)
+            .ok_or(TooManyIdentities { max })?;
+        let evicted = self.identities.remove(evicted);
+        debug!(identity = %evicted.name, "Evicted the least recently used identity");
+        Ok(())
+    }
+
+    /// Stops presenting the certificate installed for another local identity
+    /// by `set_certificate_for`, returning whether one was installed.
+    pub fn remove_certificate_for(&mut self, identity: &id::Name) -> bool {
+        let installed = self.identities.len();
+        self.identities.retain(|i| i.name != *identity);
+        if self.identities.len() == installed {
+            return false;
+        }
+        if let Some(resolver) = self.resolver.clone() {
+            self.publish_configs(resolver);
+        }
+        true
+    }
+
+    /// Returns the other local identities that servers present certificates
+    /// for, in the order they were first installed.
+    pub fn identities(&self) -> Vec<id::Name> {
+        self.identities.iter().map(|i| i.name.clone()).collect()
+    }
+
+    fn install(
+        &mut self,
+        chain: Vec<id::DerX509>,
+        ocsp: Option<Vec<u8>>,
+        expiry: std::time::SystemTime,
+        order: Order,
+    ) -> Install {
+        let t0 = Instant::now();
+
+        let chain = match Self::certificates(chain) {
+            Ok(chain) => chain,
+            Err(error) => return Install::ValidationFailed(error),
+        };
+        if let Err(error) = self.check(&chain, order) {
+            return Install::ValidationFailed(error);
+        }
+        self.publish(chain, None, ocsp, expiry, t0)
+    }
+
+    /// Converts a chain to be installed, rejecting empty chains and
+    /// certificates.
+    fn certificates(chain: Vec<id::DerX509>) -> Result<Vec<rustls::Certificate>> {
+        if chain.is_empty() {
+            return Err(EmptyChain(()).into());
+        }
+        // e.g. from a malformed identity service response, which would
+        // otherwise only be described as malformed DER.
+        if let Some(index) = chain.iter().position(|id::DerX509(der)| der.is_empty()) {
+            return Err(EmptyCertificate(index).into());
+        }
+        Ok(chain
+            .into_iter()
+            .map(|id::DerX509(der)| rustls::Certificate(der))
+            .collect())
+    }
+
+    /// Installs a certificate without validating it, so that tests may
+    /// install certificates that the store would reject (e.g. expired ones).
+    #[cfg(feature = "test-util")]
+    pub(crate) fn install_unverified_for_test(&mut self, leaf: id::DerX509) -> Install {
+        let chain = vec![rustls::Certificate(leaf.0)];
+        let expiry = self.now();
+        self.publish(chain, None, None, expiry, Instant::now())
+    }
+
+    /// Publishes configurations that present a validated chain, and a separate
+    /// validated chain in client handshakes if one is provided.
+    fn publish(
+        &mut self,
+        chain: Vec<rustls::Certificate>,
+        client_chain: Option<Vec<rustls::Certificate>>,
+        ocsp: Option<Vec<u8>>,
+        expiry: std::time::SystemTime,
+        t0: Instant,
+    ) -> Install {
+        let record = match self.options.on_install {
+            Some(_) => match self.install_record(&chain) {
+                Ok(record) => Some(record),
+                Err(error) => return Install::ValidationFailed(error),
+            },
+            None => None,
+        };
+        let t1 = Instant::now();
+
+        self.client_resolver = client_chain.map(|chain| self.resolver(chain, None));
+        let resolver = self.resolver(chain, ocsp);
+        if let Some(ref fetcher) = self.options.ocsp_fetcher {
+            ocsp::spawn_refresh(
+                fetcher.clone(),
+                resolver.key.clone(),
+                Arc::downgrade(&resolver.staple),
+                self.options.clock.clone(),
+            );
+        }
+
+        let leaf = Leaf {
+            not_after: resolver.not_after.unwrap_or(expiry),
+            sha256: sha256(resolver.key.cert[0].as_ref()),
+        };
+        self.generation += 1;
+        let rotation = self.rotation_info(&resolver.key.cert[0], leaf.not_after);
+        self.resolver = Some(resolver.clone());
+        self.leaf = Some(leaf);
+        self.options.metrics.resolver_size(self.resolver_size());
+        let t2 = Instant::now();
+
+        // Publish the new configs, and then describe them.
+        let (client_published, server_published) = self.publish_configs(resolver);
+        let _ = self.leaf_tx.send(Some(leaf));
+        let _ = self.rotation_tx.send(Some(rotation));
+        let t3 = Instant::now();
+
+        self.options.metrics.installed(
+            leaf.not_after,
+            t1.saturating_duration_since(t0),
+            t2.saturating_duration_since(t1),
+            t3.saturating_duration_since(t2),
+        );
+        if let (Some(on_install), Some(record)) = (&self.options.on_install, record) {
+            on_install(&record);
+        }
+        if let Some(ref on_rotation) = self.options.on_rotation {
+            if client_published && server_published {
+                on_rotation(leaf.not_after);
+            }
+        }
+        self.check_expiry();
+
+        if client_published || server_published {
+            Install::Published
+        } else {
+            Install::NoReceivers
+        }
+    }
+
+    /// Describes the leaf of the chain being published as the current
+    /// generation.
+    ///
+    /// Published chains have been validated, so they parse; fields that can't
+    /// be read are left empty rather than failing the installation.
+    fn rotation_info(
+        &self,
+        leaf: &rustls::Certificate,
+        not_after: std::time::SystemTime,
+    ) -> RotationInfo {
+        let cert = x509::Cert::parse(leaf.as_ref()).ok();
+        RotationInfo {
+            generation: self.generation,
+            serial: cert
+                .as_ref()
+                .map_or_else(Vec::new, |cert| cert.serial().to_vec()),
+            leaf_sha256: sha256(leaf.as_ref()),
+            not_before: cert
+                .and_then(|cert| cert.validity().ok())
+                .map_or(std::time::UNIX_EPOCH, |(not_before, _)| not_before),
+            not_after,
+        }
+    }
+
+    fn install_record(&self, chain: &[rustls::Certificate]) -> Result<InstallRecord> {
+        let leaf = chain[0].as_ref();
+        let cert = x509::Cert::parse(leaf)?;
+        let (not_before, not_after) = cert.validity()?;
+        Ok(InstallRecord {
+            identity: self.name.clone(),
+            leaf_sha256: sha256(leaf),
+            not_before,
+            not_after,
+            issuer: cert.issuer().to_vec(),
+            chain_len: chain.len(),
+            roots_sha256: self
+                .roots_sha256
+                .as_ref()
+                .try_into()
+                .expect("SHA-256 digests must be 32 bytes"),
+        })
+    }
+
+    /// Logs a warning (and calls `Options::on_expiry_warning`) if the
+    /// installed certificate expires within the `Options::expiry_warning`
+    /// threshold, returning its expiry if so.
+    ///
+    /// This is checked as each certificate is installed, but callers should
+    /// also call it periodically (e.g. from an hourly timer) so that the
+    /// warning is logged when no new certificate arrives.
+    pub fn check_expiry(&self) -> Option<std::time::SystemTime> {
+        let expiry = self.leaf?.not_after;
+        let threshold = self.options.expiry_warning.unwrap_or(EXPIRY_WARNING);
+        let now = self.now();
+        if now + threshold < expiry {
+            return None;
+        }
+
+        match expiry.duration_since(now) {
+            Ok(remaining) => warn!(
+                identity = %self.name,
+                expires_in = ?remaining,
+                "Certificate expires soon; has the identity controller stopped issuing certificates?"
+            ),
+            Err(_) => warn!(identity = %self.name, "Certificate has expired"),
+        }
+        if let Some(ref on_expiry_warning) = self.options.on_expiry_warning {
+            on_expiry_warning(expiry);
+        }
+        Some(expiry)
+    }
+
+    /// Returns the time at which the earliest-expiring trust root expires.
+    pub fn roots_earliest_expiry(&self) -> Option<std::time::SystemTime> {
+        self.roots_expiry
+    }
+
+    /// Describes the trust roots that were loaded, in the order they were
+    /// read.
+    ///
+    /// Roots are described as they are loaded, so this doesn't parse them.
+    pub fn trusted_roots(&self) -> Vec<TrustedRoot> {
+        self.trusted_roots.clone()
+    }
+
+    /// Returns the number of trust roots that were added, skipped, and found
+    /// to be duplicated or expired when the current roots were loaded.
+    ///
+    /// Skipped, duplicate, and expired roots are otherwise only logged.
+    pub fn trust_roots_info(&self) -> TrustRootsInfo {
+        self.roots_info
+    }
+
+    /// Verifies that `leaf` is valid for our identity, discovering a path to a
+    /// trust anchor through any of the certificates in `pool`.
+    ///
+    /// Certificates in `trusted` are accepted as trust anchors in addition to
+    /// the configured roots, so that a chain may terminate at an intermediate.
+    /// Nothing is installed.
+    pub fn verify_with_pool(
+        &self,
+        id::DerX509(leaf): &id::DerX509,
+        pool: &[id::DerX509],
+        trusted: &[id::DerX509],
+    ) -> Result<()> {
+        let mut roots = self.roots.clone();
+        for id::DerX509(der) in trusted {
+            roots
+                .add(&rustls::Certificate(der.clone()))
+                .map_err(|_| InvalidTrustRoots(()))?;
+        }
+        let verifier = rustls::client::WebPkiVerifier::new(roots, None);
+
+        let name = rustls::ServerName::try_from(self.name.as_str())
+            .expect("server name must be a valid DNS name");
+        let pool = pool
+            .iter()
+            .map(|id::DerX509(der)| rustls::Certificate(der.clone()))
+            .collect::<Vec<_>>();
+        verifier.verify_server_cert(
+            &rustls::Certificate(leaf.clone()),
+            &pool,
+            &name,
+            &mut std::iter::empty(),
+            &[],
+            self.now(),
+        )?;
+        Ok(())
+    }
+
+    /// Verifies that a chain, leaf first, is valid for `expected_name` against
+    /// our trust roots, e.g. to check a certificate fetched out of band from
+    /// another proxy. Nothing is installed.
+    ///
+    /// This is the verification that installed certificates pass. It doesn't
+    /// apply the constraints that client configurations place on peers (e.g.
+    /// `Options::allowed_serials`).
+    pub fn verify_peer(
+        &self,
+        certs: &[rustls::Certificate],
+        expected_name: &id::Name,
+    ) -> Result<()> {
+        if certs.is_empty() {
+            return Err(EmptyChain(()).into());
+        }
+        self.validate_with(&*self.server_cert_verifier, certs, expected_name)
+    }
+
+    /// Returns the signature schemes that handshakes may be signed with using
+    /// this store's credentials, in order of preference: those that its key
+    /// signs with which its verifier also accepts from peers.
+    ///
+    /// Servers and clients sign with the first of these that the peer offers.
+    /// `Options::signature_algorithms` restricts the algorithms that peers'
+    /// certificates are signed with, not handshake signatures, so it doesn't
+    /// narrow these schemes.
+    pub fn supported_signature_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        let accepted = self.server_cert_verifier.supported_verify_schemes();
+        self.key
+            .schemes()
+            .iter()
+            .copied()
+            .filter(|scheme| accepted.contains(scheme))
+            .collect()
+    }
+
+    /// Returns the approximate memory used by the resolvers' certificates,
+    /// i.e. the total size of the DER encodings of every chain that is
+    /// presented: our own, a separate client chain, and those for other
+    /// identities. This is zero if no certificate is installed.
+    ///
+    /// The largest size observed is also recorded by `Metrics`.
+    pub fn resolver_size(&self) -> usize {
+        let others = self.identities.iter().map(|i| &i.resolver);
+        self.resolver
+            .iter()
+            .chain(&self.client_resolver)
+            .chain(others)
+            .map(|r| r.size())
+            .sum()
+    }
+
+    /// Returns the number of ClientHellos that requested an SNI name the
+    /// installed certificates were not valid for, e.g. because requests were
+    /// misrouted to this proxy.
+    ///
+    /// Each mismatch is also logged at the DEBUG level with the requested name
+    /// and the certificate's names, and counted by `Metrics`.
+    pub fn sni_mismatches(&self) -> u64 {
+        self.options.metrics.sni_mismatches()
+    }
+
+    /// Returns the number of ClientHellos for which servers resolved no
+    /// certificate, by reason, e.g. to see why handshakes are failing without
+    /// enabling debug logging.
+    ///
+    /// These are also counted by `Metrics`.
+    pub fn resolution_failures(&self) -> ResolutionFailures {
+        self.options.metrics.resolution_failures()
+    }
+
+    /// Replaces the trust roots with those in a PEM bundle, republishing
+    /// configurations that verify peers against them.
+    ///
+    /// The installed certificates, if any (including those for other
+    /// identities), must validate against the new roots. If the bundle can't
+    /// be read or contains no usable roots, or if an installed certificate
+    /// doesn't validate, the current roots are kept and an error is returned.
+    ///
+    /// Before a certificate is installed, only the client configuration is
+    /// republished: the server configuration presents no mesh identity, and
+    /// configurations published once a certificate is installed use the new
+    /// roots.
+    pub fn set_trust_anchors(&mut self, roots_pem: &str) -> Result<()> {
+        let (roots, roots_expiry, roots_sha256, trusted_roots, roots_info) =
+            super::load_roots(&[roots_pem], self.now())?;
+        let verifier = self.verifier_for(&roots);
+        if let Some(ref resolver) = self.resolver {
+            self.validate_with(&*verifier, &resolver.key.cert, &self.name)?;
+        }
+        if let Some(ref resolver) = self.client_resolver {
+            self.validate_client_auth(roots.clone(), &resolver.key.cert)?;
+        }
+        for i in &self.identities {
+            self.validate_with(&*verifier, &i.resolver.key.cert, &i.name)?;
+        }
+
+        self.roots = roots;
+        self.roots_expiry = roots_expiry;
+        self.roots_sha256 = roots_sha256;
+        self.trusted_roots = trusted_roots;
+        self.roots_info = roots_info;
+        self.server_cert_verifier = verifier;
+        self.options.metrics.roots_loaded(roots_expiry);
+
+        match self.resolver.clone() {
+            Some(resolver) => {
+                self.publish_configs(resolver);
+            }
+            None => {
+                let mut client =
+                    client_config_builder(&self.params, self.server_cert_verifier.clone())
+                        .with_no_client_auth();
+                client.resumption = resumption(&self.options);
+                client.alpn_protocols = self.alpn_protocols.clone();
+                if self.client_tx.send(Arc::new(client)).is_err() {
+                    debug!("No receivers remain for TLS configuration updates");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces the trust roots and installs a certificate that chains to them
+    /// at once, e.g. when migrating to a new CA.
+    ///
+    /// Setting the roots and the certificate separately would leave the store
+    /// briefly trusting roots that the installed certificate doesn't chain to
+    /// (or presenting a certificate that doesn't chain to its roots); here,
+    /// the chain is validated against the new roots as by
+    /// `install_certificate` before either is published. If the bundle can't
+    /// be read, the chain is rejected, or a certificate installed for another
+    /// identity doesn't validate against the new roots, the current roots and
+    /// certificate are kept and nothing is published.
+    pub fn rotate(
+        &mut self,
+        roots_pem: &str,
+        leaf: id::DerX509,
+        intermediates: Vec<id::DerX509>,
+        expiry: std::time::SystemTime,
+    ) -> Install {
+        let (roots, roots_expiry, roots_sha256, trusted_roots, roots_info) =
+            match super::load_roots(&[roots_pem], self.now()) {
+                Ok(loaded) => loaded,
+                Err(error) => return Install::ValidationFailed(error.into()),
+            };
+        let verifier = self.verifier_for(&roots);
+        for i in &self.identities {
+            if let Err(error) = self.validate_with(&*verifier, &i.resolver.key.cert, &i.name) {
+                return Install::ValidationFailed(error);
+            }
+        }
+        let current = self.replace_roots(Roots {
+            roots,
+            expiry: roots_expiry,
+            sha256: roots_sha256,
+            trusted: trusted_roots,
+            info: roots_info,
+            verifier,
+        });
+
+        let installed = self.install_certificate(leaf, intermediates, expiry);
+        if let Install::ValidationFailed(_) = installed {
+            self.replace_roots(current);
+        } else {
+            self.options.metrics.roots_loaded(roots_expiry);
+        }
+        installed
+    }
+
+    /// Returns the verifier that validates servers and our own certificates
+    /// once `roots` are trusted, which is unchanged if a custom verifier is
+    /// configured (see `watch_with_verifier`).
+    fn verifier_for(
+        &self,
+        roots: &rustls::RootCertStore,
+    ) -> Arc<dyn rustls::client::ServerCertVerifier> {
+        if self.custom_verifier {
+            return self.server_cert_verifier.clone();
+        }
+        root_verifier(roots)
+    }
+
+    /// Replaces the trust roots without publishing configurations that use
+    /// them, returning the previous roots.
+    fn replace_roots(&mut self, roots: Roots) -> Roots {
+        Roots {
+            roots: std::mem::replace(&mut self.roots, roots.roots),
+            expiry: std::mem::replace(&mut self.roots_expiry, roots.expiry),
+            sha256: std::mem::replace(&mut self.roots_sha256, roots.sha256),
+            trusted: std::mem::replace(&mut self.trusted_roots, roots.trusted),
+            info: std::mem::replace(&mut self.roots_info, roots.info),
+            verifier: std::mem::replace(&mut self.server_cert_verifier, roots.verifier),
+        }
+    }
+
+    /// Replaces the private key, e.g. when it is re-provisioned on a different
+    /// cadence than the certificate, republishing configurations that sign
+    /// with it.
+    ///
+    /// The key is read as by `watch`, except that it may not be encrypted: the
+    /// store doesn't retain the key password. If certificates are installed
+    /// (including a separate client certificate and certificates for other
+    /// identities), the new key must be the one they certify, or they could no
+    /// longer be presented; otherwise, the current key is kept and an error is
+    /// returned. A generated CSR (see
+    /// `Options::generate_csr`) is regenerated for the new key, but a
+    /// configured CSR is unchanged.
+    pub fn set_key(&mut self, key_pkcs8: &[u8]) -> Result<()> {
+        let key = super::read_key_with_password(&super::key_der(key_pkcs8)?, None)?;
+        let others = self.identities.iter().map(|i| &i.resolver);
+        let installed = self
+            .resolver
+            .iter()
+            .chain(&self.client_resolver)
+            .chain(others);
+        for resolver in installed {
+            let leaf = x509::Cert::parse(resolver.key.cert[0].as_ref())?;
+            if leaf.public_key()? != key.public_key() {
+                return Err(KeyMismatch(()).into());
+            }
+        }
+        let csr = match self.options.generate_csr {
+            Some(ref template) => super::csr::generate(template, &self.name, &key)?.into(),
+            None => self.csr.clone(),
+        };
+
+        self.key = key;
+        self.csr = csr;
+        self.client_resolver = self
+            .client_resolver
+            .take()
+            .map(|installed| self.resolver(installed.key.cert.clone(), None));
+        self.identities = std::mem::take(&mut self.identities)
+            .into_iter()
+            .map(|i| Identity {
+                resolver: self.resolver(i.resolver.key.cert.clone(), None),
+                ..i
+            })
+            .collect();
+        let installed = match self.resolver.take() {
+            Some(installed) => installed,
+            None => return Ok(()),
+        };
+        let resolver = self.resolver(installed.key.cert.clone(), installed.key.ocsp.clone());
+        if let Some(ref fetcher) = self.options.ocsp_fetcher {
+            ocsp::spawn_refresh(
+                fetcher.clone(),
+                resolver.key.clone(),
+                Arc::downgrade(&resolver.staple),
+                self.options.clock.clone(),
+            );
+        }
+        self.resolver = Some(resolver.clone());
+        self.publish_configs(resolver);
+        Ok(())
+    }
+
+    /// Sets the ALPN protocols offered by client configurations and accepted by
+    /// server configurations, republishing them for the installed certificate.
+    ///
+    /// The protocols also apply to configurations published when subsequent
+    /// certificates are installed. Returns an error if no certificate has been
+    /// installed yet, in which case the protocols are unchanged and nothing is
+    /// republished.
+    pub fn set_alpn(&mut self, protocols: Vec<Vec<u8>>) -> Result<()> {
+        let resolver = self.resolver.clone().ok_or(NoCertificate(()))?;
+        self.alpn_protocols = protocols;
+        self.publish_configs(resolver);
+        Ok(())
+    }
+
+    /// Rebuilds the client and server configurations for the installed
+    /// certificate and publishes them, e.g. so that receivers observe
+    /// settings that configurations are built from without a rotation.
+    ///
+    /// The configurations present the installed chain, which was validated
+    /// when it was installed. Returns an error if no certificate has been
+    /// installed yet, in which case nothing is published.
+    pub fn republish(&self) -> Result<()> {
+        let resolver = self.resolver.clone().ok_or(NoCertificate(()))?;
+        self.publish_configs(resolver);
+        Ok(())
+    }
+
+    /// Checks that the server resolver selects the installed certificate when
+    /// a client requests our own identity via SNI.
+    pub fn self_resolve_check(&self) -> Result<()> {
+        let resolver = self.resolver.as_ref().ok_or(NoCertificate(()))?;
+        match resolver.resolve_server(Some(self.name.as_str()), self.key.schemes()) {
+            Some(key) if key.cert == resolver.key.cert => Ok(()),
+            _ => Err(UnresolvableIdentity(self.name.clone()).into()),
+        }
+    }
+
+    /// Replaces the installed resolver without validating the chain, so that
+    /// tests can exercise certificates that would not be installed.
+    #[cfg(test)]
+    pub(crate) fn set_resolver_unchecked(&mut self, chain: Vec<rustls::Certificate>) {
+        self.resolver = Some(self.resolver(chain, None));
+    }
+
+    /// Clones the key presented for another identity, as a handshake presenting
+    /// it would, so that tests can observe how the store treats identities
+    /// that are in use.
+    #[cfg(test)]
+    pub(crate) fn hold_identity(
+        &self,
+        identity: &id::Name,
+    ) -> Option<Arc<rustls::sign::CertifiedKey>> {
+        self.identities
+            .iter()
+            .find(|i| i.name == *identity)
+            .map(|i| i.resolver.key.clone())
+    }
+
+    /// Returns whether the server resolver presents a certificate to clients
+    /// that request `sni`, which rustls may not have validated.
+    #[cfg(test)]
+    pub(crate) fn resolves_sni(&self, sni: &str) -> bool {
+        self.resolver
+            .as_ref()
+            .and_then(|r| r.resolve_server(Some(sni), self.key.schemes()))
+            .is_some()
+    }
+
+    fn resolver(
+        &self,
+        chain: Vec<rustls::Certificate>,
+        ocsp: Option<Vec<u8>>,
+    ) -> Arc<CertResolver> {
+        let not_after = chain
+            .first()
+            .and_then(|leaf| x509::Cert::parse(leaf.as_ref()).ok())
+            .and_then(|leaf| leaf.not_after().ok());
+        let offload = self.options.offload_rsa_signatures && matches!(self.key, AnyKey::Rsa(_));
+        let signing_key: Arc<dyn rustls::sign::SigningKey> = if offload || self.rng.is_some() {
+            Arc::new(ConfiguredKey {
+                key: self.key.clone(),
+                offload,
+                rng: self.rng.clone(),
+            })
+        } else {
+            Arc::new(self.key.clone())
+        };
+        let mut key = rustls::sign::CertifiedKey::new(chain, signing_key);
+        key.ocsp = ocsp;
+        Arc::new(CertResolver {
+            not_after,
+            key_type: self.key.key_type(),
+            key: Arc::new(key),
+            trace_decisions: self.options.trace_resolver,
+            serve_without_sni: self.options.serve_without_sni,
+            on_client_hello: self.options.on_client_hello.clone(),
+            staple: Default::default(),
+            metrics: self.options.metrics.clone(),
+            clock: self.options.clock.clone(),
+        })
+    }
 
-        // Disable session resumption for the time-being until resumption is
-        // more tested.
-        cfg.resumption = rustls::client::Resumption::disabled();
+    /// Builds client and server configurations that present `resolver`'s chain
+    /// and publishes them, returning whether each had receivers.
+    ///
+    /// The server configuration is published with the installed leaf, so
+    /// receivers never observe one without the other.
+    fn publish_configs(&self, resolver: Arc<CertResolver>) -> (bool, bool) {
+        let client_published = self
+            .client_tx
+            .send(self.client_config(resolver.clone()))
+            .is_ok();
+        let server = ServerState {
+            config: self.server_config(resolver),
+            leaf: self.leaf,
+        };
+        let server_published = self.server_tx.send(server).is_ok();
+        if !(client_published || server_published) {
+            debug!("No receivers remain for TLS configuration updates");
+        }
+        (client_published, server_published)
+    }
 
+    /// Builds a new TLS client configuration, which presents the server's
+    /// chain (from `resolver`) unless a separate client chain is installed.
+    fn client_config(&self, resolver: Arc<CertResolver>) -> Arc<rustls::ClientConfig> {
+        let resolver = self.client_resolver.clone().unwrap_or(resolver);
+        let mut cfg = client_config_builder(&self.params, self.server_cert_verifier.clone())
+            .with_client_cert_resolver(resolver);
+        cfg.resumption = resumption(&self.options);
+        cfg.alpn_protocols = self.alpn_protocols.clone();
         cfg.into()
     }
 
+    /// Builds a new TLS server configuration, which presents the certificate
+    /// for the identity a client requests via SNI, if one is installed, or
+    /// else our own (from `resolver`).
+    fn server_config(&self, resolver: Arc<CertResolver>) -> Arc<rustls::ServerConfig> {
+        let resolver: Arc<dyn ResolvesServerCert> = if self.identities.is_empty() {
+            resolver
+        } else {
+            Arc::new(SniResolver {
+                primary: resolver,
+                identities: self.identities.clone(),
+            })
+        };
+        let mut cfg = server_config(&self.params, self.roots.clone(), resolver);
+        // The configuration was just built, so it isn't shared (or copied).
+        Arc::make_mut(&mut cfg).alpn_protocols = self.alpn_protocols.clone();
+        cfg
+    }
+
     /// Ensures the certificate is valid for the services we terminate for TLS. This assumes that
     /// server cert validation does the same or more validation than client cert validation.
     fn validate(&self, certs: &[rustls::Certificate]) -> Result<()> {
-        let name = rustls::ServerName::try_from(self.name.as_str())
+        self.verify_peer(certs, &self.name)
+    }
+
+    fn validate_with(
+        &self,
+        verifier: &dyn rustls::client::ServerCertVerifier,
+        certs: &[rustls::Certificate],
+        name: &id::Name,
+    ) -> Result<()> {
+        let name = rustls::ServerName::try_from(name.as_str())
             .expect("server name must be a valid DNS name");
         static NO_OCSP: &[u8] = &[];
         let end_entity = &certs[0];
         let intermediates = &certs[1..];
         let no_scts = &mut std::iter::empty();
-        let now = std::time::SystemTime::now();
-        self.server_cert_verifier.verify_server_cert(
-            end_entity,
-            intermediates,
-            &name,
-            no_scts,
-            NO_OCSP,
-            now,
-        )?;
+        let now = self.now();
+        verifier.verify_server_cert(end_entity, intermediates, &name, no_scts, NO_OCSP, now)?;
         debug!("Certified");
         Ok(())
     }
+
+    /// Verifies a chain as servers verify clients' certificates against
+    /// `roots`.
+    fn validate_client_auth(
+        &self,
+        roots: rustls::RootCertStore,
+        certs: &[rustls::Certificate],
+    ) -> Result<()> {
+        let verifier = rustls::server::AllowAnyAuthenticatedClient::new(roots);
+        rustls::server::ClientCertVerifier::verify_client_cert(
+            &verifier,
+            &certs[0],
+            &certs[1..],
+            self.now(),
+        )?;
+        debug!("Certified for client authentication");
+        Ok(())
+    }
+
+    /// Returns the current time according to the configured clock.
+    fn now(&self) -> std::time::SystemTime {
+        match self.options.clock {
+            Some(ref clock) => clock(),
+            None => std::time::SystemTime::now(),
+        }
+    }
+
+    /// Checks that the certificate chain is suitable for our identity.
+    fn check(&self, chain: &[rustls::Certificate], order: Order) -> Result<()> {
+        self.check_suitable(chain, order)?;
+
+        // Use the client's verifier to validate the certificate for our local name.
+        self.validate(chain)
+    }
+
+    /// Checks that a certificate chain is suitable for our identity when
+    /// presented in client handshakes, i.e. that servers would accept it.
+    fn check_client_auth(&self, chain: &[rustls::Certificate]) -> Result<()> {
+        self.check_suitable(chain, Order::Path)?;
+        self.validate_client_auth(self.roots.clone(), chain)
+    }
+
+    /// Checks everything about the chain but the path to a trust root, which
+    /// depends on the role in which it is presented.
+    fn check_suitable(&self, chain: &[rustls::Certificate], order: Order) -> Result<()> {
+        self.check_suitable_for(&self.name, chain, order)
+    }
+
+    /// Like `check_suitable`, but for a chain that is presented for
+    /// `identity`. Only chains for our own identity are compared with our CSR.
+    fn check_suitable_for(
+        &self,
+        identity: &id::Name,
+        chain: &[rustls::Certificate],
+        order: Order,
+    ) -> Result<()> {
+        let sans = webpki::EndEntityCert::try_from(chain[0].as_ref())?
+            .dns_names()?
+            .map(|name| <&str>::from(name).to_string())
+            .collect::<Vec<_>>();
+        self.check_identity(identity, sans.clone())?;
+        self.check_key(&chain[0])?;
+        self.check_trust_domain(&chain[0], &sans)?;
+        if *identity == self.name {
+            self.check_csr_sans(&sans)?;
+        }
+        self.check_key_identifiers(chain)?;
+        self.check_path_len(chain)?;
+        if order == Order::Path {
+            self.check_chain_order(chain)?;
+        }
+        self.check_validity(&chain[0])?;
+        Ok(())
+    }
+
+    /// Ensures the leaf certificate is valid at the current time.
+    ///
+    /// Verification would also reject such certificates, but this check
+    /// reports the validity period, which distinguishes a stale certificate
+    /// from a clock that disagrees with the issuer's.
+    fn check_validity(&self, leaf: &rustls::Certificate) -> Result<()> {
+        let (not_before, not_after) = x509::Cert::parse(leaf.as_ref())?.validity()?;
+        let now = self.now();
+        if now < not_before {
+            return Err(NotYetValid { not_before, now }.into());
+        }
+        if not_after < now {
+            return Err(Expired { not_after, now }.into());
+        }
+        Ok(())
+    }
+
+    /// Ensures the leaf certificate names the identity among its DNS SANs.
+    ///
+    /// Verification would also reject most such certificates, but this check
+    /// reports the most common issuance problem more precisely. It also
+    /// rejects certificates that verification accepts for our name without
+    /// having been issued for it; for instance, a wildcard SAN never matches.
+    /// Names are compared case-insensitively, as DNS names are.
+    fn check_identity(&self, identity: &id::Name, sans: Vec<String>) -> Result<()> {
+        if sans
+            .iter()
+            .any(|san| san.eq_ignore_ascii_case(identity.as_str()))
+        {
+            return Ok(());
+        }
+
+        Err(IdentityMismatch {
+            identity: identity.clone(),
+            sans,
+        }
+        .into())
+    }
+
+    /// Ensures the leaf certificate was issued for our private key.
+    ///
+    /// Certificates for keys of another type are described as such, since
+    /// they usually indicate that the wrong key was provisioned.
+    fn check_key(&self, leaf: &rustls::Certificate) -> Result<()> {
+        let cert = x509::Cert::parse(leaf.as_ref())?;
+        let (algorithm, parameters, _) = x509::spki_parts(cert.spki())?;
+        let certificate = match spki_key_type(algorithm, parameters) {
+            Ok(key_type) => key_type.name(),
+            Err(UnsupportedKeyAlgorithm(algorithm)) => algorithm,
+        };
+        let key = self.key.key_type().name();
+        if certificate != key {
+            return Err(CertKeyAlgorithmMismatch { certificate, key }.into());
+        }
+        if cert.public_key()? != self.key.public_key() {
+            return Err(KeyMismatch(()).into());
+        }
+        Ok(())
+    }
+
+    /// Compares the leaf certificate's DNS SANs with those requested by our
+    /// CSR, according to the configured policy.
+    fn check_csr_sans(&self, issued: &[String]) -> Result<()> {
+        let policy = self.options.csr_sans;
+        if policy == CsrSanPolicy::Ignore {
+            return Ok(());
+        }
+
+        let requested = x509::Csr::parse(&self.csr).and_then(|csr| {
+            csr.dns_names()
+                .map(|names| names.into_iter().map(String::from).collect::<Vec<_>>())
+        });
+        let mismatch = match requested {
+            Ok(requested) => {
+                let contains = |names: &[String], name: &String| {
+                    names.iter().any(|n| n.eq_ignore_ascii_case(name))
+                };
+                if requested.iter().all(|n| contains(issued, n))
+                    && issued.iter().all(|n| contains(&requested, n))
+                {
+                    return Ok(());
+                }
+                CsrSanMismatch {
+                    requested,
+                    issued: issued.to_vec(),
+                }
+            }
+            Err(error) if policy == CsrSanPolicy::Require => return Err(error.into()),
+            Err(error) => {
+                warn!(%error, "Could not read SANs from CSR");
+                return Ok(());
+            }
+        };
+
+        if policy == CsrSanPolicy::Require {
+            return Err(mismatch.into());
+        }
+        warn!(requested = ?mismatch.requested, issued = ?mismatch.issued, "Certificate SANs differ from those requested");
+        Ok(())
+    }
+
+    /// Warns if the leaf certificate names no identity in the expected trust
+    /// domain, if one is configured.
+    fn check_trust_domain(&self, leaf: &rustls::Certificate, sans: &[String]) -> Result<()> {
+        let trust_domain = match self.options.trust_domain {
+            Some(ref td) => td.trim_end_matches('.'),
+            None => return Ok(()),
+        };
+
+        let uris = x509::Cert::parse(leaf.as_ref())?.uris()?;
+        let in_dns_domain = |san: &String| {
+            san.len() > trust_domain.len() + 1
+                && san
+                    .get(san.len() - trust_domain.len() - 1..)
+                    .and_then(|suffix| suffix.strip_prefix('.'))
+                    .map_or(false, |td| td.eq_ignore_ascii_case(trust_domain))
+        };
+        let in_spiffe_domain = |uri: &&str| {
+            uri.strip_prefix("spiffe://")
+                .and_then(|id| id.split('/').next())
+                .map_or(false, |td| td.eq_ignore_ascii_case(trust_domain))
+        };
+        if !sans.iter().any(in_dns_domain) && !uris.iter().any(in_spiffe_domain) {
+            warn!(
+                trust_domain,
+                ?sans,
+                ?uris,
+                "Certificate does not name an identity in the expected trust domain"
+            );
+        }
+        Ok(())
+    }
+
+    /// Ensures that each certificate in the chain is issued by the one after
+    /// it, and that the last is issued by a trust root. A root at the end of
+    /// the chain is self-issued, so it is accepted if it is trusted.
+    ///
+    /// Verification would also reject most such chains, but only as lacking an
+    /// issuer; this check reports where the chain breaks.
+    fn check_chain_order(&self, chain: &[rustls::Certificate]) -> Result<()> {
+        let certs = chain
+            .iter()
+            .map(|c| x509::Cert::parse(c.as_ref()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let describe =
+            |issuer: &[u8]| x509::format_name(issuer).unwrap_or_else(|_| hex::encode(issuer));
+
+        for (index, pair) in certs.windows(2).enumerate() {
+            if pair[0].issuer() != pair[1].subject() {
+                return Err(MisorderedChain {
+                    index,
+                    issuer: describe(pair[0].issuer()),
+                }
+                .into());
+            }
+        }
+
+        let index = certs.len() - 1;
+        let top = &certs[index];
+        let issued_by_root = self
+            .roots
+            .roots
+            .iter()
+            .any(|root| root.subject().as_ref() == top.issuer());
+        if !issued_by_root {
+            return Err(UntrustedChain {
+                index,
+                issuer: describe(top.issuer()),
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Ensures that no CA in the chain has more intermediate CAs beneath it
+    /// than its Basic Constraints path length permits.
+    ///
+    /// Verification would also reject such a chain, but this check reports
+    /// which constraint was violated.
+    fn check_path_len(&self, chain: &[rustls::Certificate]) -> Result<()> {
+        let certs = chain
+            .iter()
+            .map(|c| x509::Cert::parse(c.as_ref()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Follow each certificate's issuer through the chain, starting at the
+        // leaf. Nothing is followed past a self-issued certificate, and the
+        // walk is bounded so that it terminates on cyclic chains.
+        let mut cert = &certs[0];
+        for depth in 0..certs.len() {
+            if cert.subject() == cert.issuer() {
+                break;
+            }
+            let index = match certs
+                .iter()
+                .skip(1)
+                .position(|c| c.subject() == cert.issuer())
+            {
+                Some(i) => i + 1,
+                None => break,
+            };
+            // `depth` intermediate CAs lie between this issuer and the leaf.
+            if let Some(path_len) = certs[index].path_len_constraint()? {
+                if depth as u64 > path_len {
+                    return Err(PathLenExceeded {
+                        index,
+                        path_len,
+                        depth,
+                    }
+                    .into());
+                }
+            }
+            cert = &certs[index];
+        }
+
+        Ok(())
+    }
+
+    /// Checks that each certificate in the chain carries Subject and Authority
+    /// Key Identifiers, according to the configured policy.
+    fn check_key_identifiers(&self, certs: &[rustls::Certificate]) -> Result<()> {
+        let policy = self.options.key_identifiers;
+        if policy == KeyIdentifierPolicy::Ignore {
+            return Ok(());
+        }
+
+        for (index, c) in certs.iter().enumerate() {
+            let cert = x509::Cert::parse(c.as_ref())?;
+            for (oid, extension) in [
+                (x509::oid::SUBJECT_KEY_IDENTIFIER, "subjectKeyIdentifier"),
+                (
+                    x509::oid::AUTHORITY_KEY_IDENTIFIER,
+                    "authorityKeyIdentifier",
+                ),
+            ] {
+                if cert.has_extension(oid)? {
+                    continue;
+                }
+                if policy == KeyIdentifierPolicy::Require {
+                    return Err(MissingKeyIdentifier { index, extension }.into());
+                }
+                warn!(index, extension, "Certificate lacks key identifier");
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl id::Credentials for Store {
@@ -131,7 +1726,7 @@ impl id::Credentials for Store {
         &self.name
     }
 
-    /// Returns the CSR that was configured at proxy startup.
+    /// Returns the CSR that was configured (or generated) at proxy startup.
     fn gen_certificate_signing_request(&mut self) -> id::DerX509 {
         id::DerX509(self.csr.to_vec())
     }
@@ -139,99 +1734,309 @@ impl id::Credentials for Store {
     /// Publishes TLS client and server configurations using
     fn set_certificate(
         &mut self,
-        id::DerX509(leaf): id::DerX509,
+        leaf: id::DerX509,
         intermediates: Vec<id::DerX509>,
-        _expiry: std::time::SystemTime,
+        expiry: std::time::SystemTime,
     ) -> Result<()> {
-        let mut chain = Vec::with_capacity(intermediates.len() + 1);
-        chain.push(rustls::Certificate(leaf));
-        chain.extend(
-            intermediates
-                .into_iter()
-                .map(|id::DerX509(der)| rustls::Certificate(der)),
-        );
+        self.install_certificate(leaf, intermediates, expiry)
+            .into_result()
+    }
+}
 
-        // Use the client's verifier to validate the certificate for our local name.
-        self.validate(&chain)?;
+fn on_multi_thread_runtime() -> bool {
+    tokio::runtime::Handle::try_current().map_or(false, |handle| {
+        matches!(
+            handle.runtime_flavor(),
+            tokio::runtime::RuntimeFlavor::MultiThread
+        )
+    })
+}
 
-        let resolver = Arc::new(CertResolver(Arc::new(rustls::sign::CertifiedKey::new(
-            chain,
-            Arc::new(Key(self.key.clone())),
-        ))));
+/// Counts a ClientHello for which no certificate is resolved, and records why
+/// on the current resolver span.
+fn unresolved(metrics: &Metrics, reason: Unresolved) {
+    metrics.unresolved(reason);
+    tracing::Span::current().record("unresolved", reason.as_str());
+}
 
-        // Build new client and server TLS configs.
-        let client = self.client_config(resolver.clone());
-        let server = server_config(self.roots.clone(), resolver);
+pub(super) fn sha256(data: &[u8]) -> [u8; 32] {
+    ring::digest::digest(&ring::digest::SHA256, data)
+        .as_ref()
+        .try_into()
+        .expect("SHA-256 digests must be 32 bytes")
+}
 
-        // Publish the new configs.
-        let _ = self.client_tx.send(client);
-        let _ = self.server_tx.send(server);
+// === impl AnyKey ===
 
-        Ok(())
+impl AnyKey {
+    pub(super) fn key_type(&self) -> KeyType {
+        match self {
+            Self::EcdsaP256(_) => KeyType::EcdsaP256,
+            Self::EcdsaP384(_) => KeyType::EcdsaP384,
+            Self::Ed25519(_) => KeyType::Ed25519,
+            Self::Rsa(_) => KeyType::Rsa,
+            Self::External { key_type, .. } => *key_type,
+        }
     }
-}
 
-// === impl Key ===
+    /// Returns the signature schemes this key may use, in order of preference.
+    fn schemes(&self) -> &'static [rustls::SignatureScheme] {
+        signature_schemes(self.key_type())
+    }
 
-impl rustls::sign::SigningKey for Key {
-    fn choose_scheme(
+    /// Returns a signer for the most preferred of our schemes that is
+    /// `offered`.
+    fn signer(
         &self,
         offered: &[rustls::SignatureScheme],
+        offload: bool,
+        rng: Option<Rng>,
     ) -> Option<Box<dyn rustls::sign::Signer>> {
-        if !offered.contains(&SIGNATURE_ALG_RUSTLS_SCHEME) {
-            return None;
+        let scheme = *self.schemes().iter().find(|s| offered.contains(s))?;
+        Some(Box::new(KeySigner {
+            key: self.clone(),
+            scheme,
+            offload,
+            rng,
+        }))
+    }
+
+    /// Returns the public key, as encoded in a certificate's
+    /// SubjectPublicKeyInfo.
+    pub(super) fn public_key(&self) -> &[u8] {
+        match self {
+            Self::EcdsaP256(key) | Self::EcdsaP384(key) => key.public_key().as_ref(),
+            Self::Ed25519(key) => key.public_key().as_ref(),
+            Self::Rsa(key) => key.public_key().as_ref(),
+            Self::External { public_key, .. } => &public_key[..],
         }
+    }
+}
 
-        Some(Box::new(self.clone()))
+impl rustls::sign::SigningKey for AnyKey {
+    fn choose_scheme(
+        &self,
+        offered: &[rustls::SignatureScheme],
+    ) -> Option<Box<dyn rustls::sign::Signer>> {
+        self.signer(offered, false, None)
     }
 
     fn algorithm(&self) -> rustls::SignatureAlgorithm {
-        SIGNATURE_ALG_RUSTLS_ALGORITHM
+        signature_algorithm(self.key_type())
     }
 }
 
-impl rustls::sign::Signer for Key {
-    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, rustls::Error> {
-        let rng = rand::SystemRandom::new();
-        self.0
-            .sign(&rng, message)
-            .map(|signature| signature.as_ref().to_owned())
+// === impl ConfiguredKey ===
+
+impl rustls::sign::SigningKey for ConfiguredKey {
+    fn choose_scheme(
+        &self,
+        offered: &[rustls::SignatureScheme],
+    ) -> Option<Box<dyn rustls::sign::Signer>> {
+        self.key.signer(offered, self.offload, self.rng.clone())
+    }
+
+    fn algorithm(&self) -> rustls::SignatureAlgorithm {
+        signature_algorithm(self.key.key_type())
+    }
+}
+
+// === impl KeySigner ===
+
+impl KeySigner {
+    fn sign_inline(&self, message: &[u8]) -> Result<Vec<u8>, rustls::Error> {
+        // `SystemRandom` is a stateless handle: ring initializes the OS RNG
+        // once per process, so constructing one per signature neither
+        // allocates nor reseeds, and there's nothing to gain by caching it.
+        let system = rand::SystemRandom::new();
+        let rng: &dyn rand::SecureRandom = match self.rng {
+            Some(ref rng) => &**rng,
+            None => &system,
+        };
+        let signed = match self.key {
+            AnyKey::EcdsaP256(ref key) | AnyKey::EcdsaP384(ref key) => key
+                .sign(rng, message)
+                .map(|signature| signature.as_ref().to_owned()),
+            AnyKey::Ed25519(ref key) => Ok(key.sign(message).as_ref().to_owned()),
+            AnyKey::Rsa(ref key) => {
+                let padding: &'static dyn signature::RsaEncoding = match self.scheme {
+                    rustls::SignatureScheme::RSA_PSS_SHA512 => &signature::RSA_PSS_SHA512,
+                    rustls::SignatureScheme::RSA_PSS_SHA384 => &signature::RSA_PSS_SHA384,
+                    rustls::SignatureScheme::RSA_PSS_SHA256 => &signature::RSA_PSS_SHA256,
+                    rustls::SignatureScheme::RSA_PKCS1_SHA512 => &signature::RSA_PKCS1_SHA512,
+                    rustls::SignatureScheme::RSA_PKCS1_SHA384 => &signature::RSA_PKCS1_SHA384,
+                    rustls::SignatureScheme::RSA_PKCS1_SHA256 => &signature::RSA_PKCS1_SHA256,
+                    _ => unreachable!("RSA keys only choose RSA schemes"),
+                };
+                let mut signature = vec![0; key.public_modulus_len()];
+                key.sign(padding, rng, message, &mut signature)
+                    .map(|()| signature)
+            }
+            // External keys describe their own failures.
+            AnyKey::External { ref key, .. } => return key.sign(self.scheme, message),
+        };
+        signed
             .map_err(|ring::error::Unspecified| rustls::Error::General("Signing Failed".to_owned()))
     }
+}
+
+impl rustls::sign::Signer for KeySigner {
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, rustls::Error> {
+        // Rustls signs synchronously, from within the handshake, so handing
+        // the signature to another thread would still block this one until it
+        // completes. Instead, `block_in_place` lets the runtime move this
+        // worker's other tasks to another thread while it signs. This isn't
+        // possible on current-thread runtimes (where it would panic) or
+        // necessary outside of a runtime, so signatures are computed inline
+        // there.
+        if self.offload && on_multi_thread_runtime() {
+            return tokio::task::block_in_place(|| self.sign_inline(message));
+        }
+        self.sign_inline(message)
+    }
 
     fn scheme(&self) -> rustls::SignatureScheme {
-        SIGNATURE_ALG_RUSTLS_SCHEME
+        self.scheme
     }
 }
 
 // === impl CertResolver ===
 
 impl CertResolver {
+    /// Returns the total size of the chain's DER encoding.
+    fn size(&self) -> usize {
+        self.key.cert.iter().map(|c| c.as_ref().len()).sum()
+    }
+
+    fn now(&self) -> std::time::SystemTime {
+        match self.clock {
+            Some(ref clock) => clock(),
+            None => std::time::SystemTime::now(),
+        }
+    }
+
     #[inline]
     fn resolve_(
         &self,
         sigschemes: &[rustls::SignatureScheme],
     ) -> Option<Arc<rustls::sign::CertifiedKey>> {
-        if !sigschemes.contains(&SIGNATURE_ALG_RUSTLS_SCHEME) {
+        let schemes = signature_schemes(self.key_type);
+        if !sigschemes.iter().any(|s| schemes.contains(s)) {
             debug!("Signature scheme not supported -> no certificate");
             return None;
         }
 
-        Some(self.0.clone())
+        Some(self.key.clone())
+    }
+
+    fn resolve_server(
+        &self,
+        server_name: Option<&str>,
+        sigschemes: &[rustls::SignatureScheme],
+    ) -> Option<Arc<rustls::sign::CertifiedKey>> {
+        match server_name {
+            Some(sni) => {
+                if !self.valid_for_sni(sni) {
+                    return None;
+                }
+            }
+            None if self.serve_without_sni => {
+                debug!("no SNI -> our certificate");
+            }
+            None => {
+                unresolved(&self.metrics, Unresolved::NoSni);
+                debug!("no SNI -> no certificate");
+                return None;
+            }
+        }
+
+        let key = match self.resolve_(sigschemes) {
+            Some(key) => key,
+            None => {
+                unresolved(&self.metrics, Unresolved::UnsupportedSigscheme);
+                return None;
+            }
+        };
+
+        // Prefer the stapled certificate while its OCSP response is fresh.
+        if let Some((ref stapled, next_update)) = *self.staple.read() {
+            if self.now() < next_update {
+                return Some(stapled.clone());
+            }
+        }
+        Some(key)
+    }
+
+    /// Returns whether our certificate is valid for the name a client
+    /// requested via SNI, counting the failure if it isn't.
+    fn valid_for_sni(&self, sni: &str) -> bool {
+        // Peers control the SNI value, so a malformed name fails the
+        // handshake rather than the proxy.
+        let server_name = match webpki::DnsNameRef::try_from_ascii_str(sni) {
+            Ok(name) => webpki::SubjectNameRef::DnsName(name),
+            Err(_) => {
+                unresolved(&self.metrics, Unresolved::InvalidSni);
+                debug!(sni = ?sni, "Invalid SNI -> no certificate");
+                return false;
+            }
+        };
+
+        // Verify that our certificate is valid for the given SNI name.
+        let c = match self.key.cert.first() {
+            Some(c) => c,
+            None => return false,
+        };
+        let cert = match webpki::EndEntityCert::try_from(c.as_ref()) {
+            Ok(cert) => cert,
+            Err(error) => {
+                debug!(%error, "Local certificate is not valid for SNI");
+                return false;
+            }
+        };
+        if let Err(error) = cert.verify_is_valid_for_subject_name(server_name) {
+            unresolved(&self.metrics, Unresolved::SniMismatch);
+            let names = x509::Cert::parse(c.as_ref())
+                .and_then(|c| c.dns_names())
+                .unwrap_or_default();
+            debug!(%error, %sni, ?names, "Local certificate is not valid for SNI");
+            return false;
+        };
+        true
     }
 }
 
 impl rustls::client::ResolvesClientCert for CertResolver {
     fn resolve(
         &self,
-        _acceptable_issuers: &[&[u8]],
+        acceptable_issuers: &[&[u8]],
         sigschemes: &[rustls::SignatureScheme],
     ) -> Option<Arc<rustls::sign::CertifiedKey>> {
-        self.resolve_(sigschemes)
+        let span = debug_span!(
+            "resolve_client_cert",
+            acceptable_issuers = acceptable_issuers.len(),
+            ?sigschemes,
+            selected = field::Empty,
+        );
+        let _enter = span.enter();
+
+        let key = self.resolve_(sigschemes);
+        span.record("selected", key.is_some());
+        if self.trace_decisions {
+            trace!(
+                acceptable_issuers = ?acceptable_issuers.iter().map(hex::encode).collect::<Vec<_>>(),
+                ?sigschemes,
+                selected = key.is_some(),
+                "Resolved client certificate",
+            );
+        }
+        key
     }
 
+    /// Indicates whether a certificate is available for client
+    /// authentication, i.e. the chain is non-empty and the leaf has not
+    /// expired.
     fn has_certs(&self) -> bool {
-        true
+        !self.key.cert.is_empty() && self.not_after.map_or(true, |t| self.now() < t)
     }
 }
 
@@ -240,27 +2045,111 @@ impl rustls::server::ResolvesServerCert for CertResolver {
         &self,
         hello: rustls::server::ClientHello<'_>,
     ) -> Option<Arc<rustls::sign::CertifiedKey>> {
-        let server_name = match hello.server_name() {
-            Some(name) => {
-                let name = webpki::DnsNameRef::try_from_ascii_str(name)
-                    .expect("server name must be a valid server name");
-                webpki::SubjectNameRef::DnsName(name)
-            }
-            None => {
-                debug!("no SNI -> no certificate");
-                return None;
+        let span = server_span(&hello);
+        let _enter = span.enter();
+
+        if let Some(ref on_client_hello) = self.on_client_hello {
+            on_client_hello(&ClientHelloRecord {
+                server_name: hello.server_name().map(String::from),
+                signature_schemes: hello.signature_schemes().to_vec(),
+                alpn_protocols: hello
+                    .alpn()
+                    .map(|protocols| protocols.map(<[u8]>::to_vec).collect()),
+                cipher_suites: hello.cipher_suites().to_vec(),
+            });
+        }
+
+        let key = self.resolve_server(hello.server_name(), hello.signature_schemes());
+        span.record("selected", key.is_some());
+        if self.trace_decisions {
+            trace!(
+                sni = ?hello.server_name(),
+                sigschemes = ?hello.signature_schemes(),
+                selected = key.is_some(),
+                "Resolved server certificate",
+            );
+        }
+        key
+    }
+}
+
+/// Returns a span for a server certificate resolution, so that its decision
+/// (and why no certificate is selected, if none is) may be correlated with the
+/// handshake, e.g. in distributed traces.
+fn server_span(hello: &rustls::server::ClientHello<'_>) -> tracing::Span {
+    debug_span!(
+        "resolve_server_cert",
+        sni = ?hello.server_name(),
+        sigschemes = ?hello.signature_schemes(),
+        selected = field::Empty,
+        unresolved = field::Empty,
+    )
+}
+
+// === impl SniResolver ===
+
+impl rustls::server::ResolvesServerCert for SniResolver {
+    fn resolve(
+        &self,
+        hello: rustls::server::ClientHello<'_>,
+    ) -> Option<Arc<rustls::sign::CertifiedKey>> {
+        let identity = hello.server_name().and_then(|sni| {
+            self.identities
+                .iter()
+                .find(|i| i.name.as_str().eq_ignore_ascii_case(sni))
+        });
+        match identity {
+            Some(identity) => {
+                let key = identity.resolver.resolve(hello)?;
+                *identity.last_resolved.lock() = Instant::now();
+                Some(key)
             }
-        };
+            None => self.primary.resolve(hello),
+        }
+    }
+}
 
-        // Verify that our certificate is valid for the given SNI name.
-        let c = self.0.cert.first()?;
-        if let Err(error) = webpki::EndEntityCert::try_from(c.as_ref())
-            .and_then(|c| c.verify_is_valid_for_subject_name(server_name))
-        {
-            debug!(%error, "Local certificate is not valid for SNI");
-            return None;
-        };
+// === impl AwaitingCert ===
+
+impl AwaitingCert {
+    pub(super) fn new(
+        bootstrap: Option<Arc<rustls::server::ResolvesServerCertUsingSni>>,
+        metrics: Metrics,
+    ) -> Self {
+        Self { bootstrap, metrics }
+    }
+}
 
-        self.resolve_(hello.signature_schemes())
+impl rustls::server::ResolvesServerCert for AwaitingCert {
+    fn resolve(
+        &self,
+        hello: rustls::server::ClientHello<'_>,
+    ) -> Option<Arc<rustls::sign::CertifiedKey>> {
+        let span = server_span(&hello);
+        let _enter = span.enter();
+
+        let key = self.bootstrap.as_ref().and_then(|b| b.resolve(hello));
+        span.record("selected", key.is_some());
+        if key.is_none() {
+            unresolved(&self.metrics, Unresolved::NoCertYet);
+            debug!("No certificate installed yet -> no certificate");
+        }
+        key
+    }
+}
+
+// === impl NoClientCert ===
+
+impl rustls::client::ResolvesClientCert for NoClientCert {
+    fn resolve(
+        &self,
+        _acceptable_issuers: &[&[u8]],
+        _sigschemes: &[rustls::SignatureScheme],
+    ) -> Option<Arc<rustls::sign::CertifiedKey>> {
+        None
+    }
+
+    fn has_certs(&self) -> bool {
+        false
     }
 }
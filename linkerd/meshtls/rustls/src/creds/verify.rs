@@ -0,0 +1,779 @@
+//! Verifiers that apply additional checks to peer certificates once they have
+//! passed standard validation.
+
+use super::{
+    spki_sha256, x509, DnsNameNotPermitted, IntermediateNotPinned, PeerLifetimeExceeded,
+    SerialNotAllowed, SignatureAlgorithm, SignatureAlgorithmNotAllowed, TooManyIntermediates,
+    TrustDomainMigration,
+};
+use crate::IdentityPattern;
+use std::{convert::TryFrom, sync::Arc, time::Duration};
+use tokio_rustls::rustls::{
+    self,
+    client::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    server::{ClientCertVerified, ClientCertVerifier},
+    Certificate, DigitallySignedStruct, DistinguishedName, SignatureScheme,
+};
+use tracing::{debug, warn};
+
+/// Only accepts peers whose leaf certificates have allow-listed serial numbers.
+pub(super) struct PinnedSerials<V: ?Sized> {
+    inner: Arc<V>,
+    serials: Arc<[Vec<u8>]>,
+}
+
+/// Accepts servers that present identities in deprecated trust domains while a
+/// trust domain migration is in progress.
+pub(super) struct TrustDomains<V: ?Sized> {
+    inner: Arc<V>,
+    migration: Arc<TrustDomainMigration>,
+}
+
+/// Only accepts peers whose leaf certificates are valid for no longer than a
+/// maximum lifetime.
+pub(super) struct MaxLifetime<V: ?Sized> {
+    inner: Arc<V>,
+    max: Duration,
+}
+
+/// Only accepts peers whose leaf certificates are issued through an
+/// intermediate with a pinned SubjectPublicKeyInfo.
+///
+/// Once a chain has passed standard validation, the leaf is verified again
+/// with the pinned intermediates that the peer presents as the only trust
+/// anchors, so intermediates that are presented but aren't on a path from the
+/// leaf don't satisfy the pin.
+pub(super) struct PinnedIntermediates<V: ?Sized> {
+    inner: Arc<V>,
+    pins: Arc<[[u8; 32]]>,
+}
+
+/// Only accepts peers that present no more than a maximum number of
+/// intermediates.
+///
+/// Chains are counted before they are validated, so that long chains are
+/// rejected without building paths through them.
+pub(super) struct MaxIntermediates<V: ?Sized> {
+    inner: Arc<V>,
+    max: usize,
+}
+
+/// Only accepts peers whose leaf certificates and the intermediates they
+/// present are signed with allow-listed algorithms.
+///
+/// Certificates are checked before they are validated, so that peers are
+/// rejected for using a disallowed algorithm even if their chains are also
+/// invalid for other reasons.
+pub(super) struct SignatureAlgorithms<V: ?Sized> {
+    inner: Arc<V>,
+    allowed: Arc<[SignatureAlgorithm]>,
+}
+
+/// Only accepts servers whose leaf certificates' DNS SANs are all within
+/// permitted domains.
+pub(super) struct PermittedDomains<V: ?Sized> {
+    inner: Arc<V>,
+    permitted: Arc<[IdentityPattern]>,
+}
+
+/// Rejects servers whose stapled OCSP responses report that their leaf
+/// certificates have been revoked.
+///
+/// Responses are not authenticated: a server can only use one to fail its own
+/// handshakes, so servers that staple no response, or a response that isn't
+/// understood, are verified as if OCSP weren't in use.
+pub(super) struct StapledOcsp<V: ?Sized> {
+    inner: Arc<V>,
+}
+
+/// Advertises a curated list of CA subjects to clients when requesting their
+/// certificates, rather than the subject of every trust root.
+///
+/// Clients are still verified by the inner verifier, against all of its
+/// roots.
+pub(super) struct ClientCaHints<V: ?Sized> {
+    inner: Arc<V>,
+    subjects: Arc<[DistinguishedName]>,
+}
+
+/// The algorithms that certificates may be signed with when leaves are
+/// verified against pinned intermediates, as rustls accepts by default.
+static SIG_ALGS: &[&webpki::SignatureAlgorithm] = &[
+    &webpki::ECDSA_P256_SHA256,
+    &webpki::ECDSA_P256_SHA384,
+    &webpki::ECDSA_P384_SHA256,
+    &webpki::ECDSA_P384_SHA384,
+    &webpki::ED25519,
+    &webpki::RSA_PSS_2048_8192_SHA256_LEGACY_KEY,
+    &webpki::RSA_PSS_2048_8192_SHA384_LEGACY_KEY,
+    &webpki::RSA_PSS_2048_8192_SHA512_LEGACY_KEY,
+    &webpki::RSA_PKCS1_2048_8192_SHA256,
+    &webpki::RSA_PKCS1_2048_8192_SHA384,
+    &webpki::RSA_PKCS1_2048_8192_SHA512,
+    &webpki::RSA_PKCS1_3072_8192_SHA384,
+];
+
+/// Implements the `ServerCertVerifier` methods that a wrapper doesn't
+/// override by forwarding them to its `inner` verifier, so that wrappers only
+/// implement `verify_server_cert`.
+macro_rules! forward_server_cert_verifier {
+    () => {
+        fn verify_tls12_signature(
+            &self,
+            message: &[u8],
+            cert: &Certificate,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, rustls::Error> {
+            self.inner.verify_tls12_signature(message, cert, dss)
+        }
+
+        fn verify_tls13_signature(
+            &self,
+            message: &[u8],
+            cert: &Certificate,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, rustls::Error> {
+            self.inner.verify_tls13_signature(message, cert, dss)
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+            self.inner.supported_verify_schemes()
+        }
+
+        fn request_scts(&self) -> bool {
+            self.inner.request_scts()
+        }
+    };
+}
+
+/// Implements the `ClientCertVerifier` methods that a wrapper doesn't
+/// override by forwarding them to its `inner` verifier, so that wrappers only
+/// implement `verify_client_cert` (and, when excepted,
+/// `client_auth_root_subjects`).
+///
+/// Every method with a default is forwarded, so that wrapping a verifier never
+/// changes whether client authentication is offered or required.
+macro_rules! forward_client_cert_verifier {
+    () => {
+        fn client_auth_root_subjects(&self) -> &[DistinguishedName] {
+            self.inner.client_auth_root_subjects()
+        }
+
+        forward_client_cert_verifier!(except client_auth_root_subjects);
+    };
+
+    (except client_auth_root_subjects) => {
+        fn offer_client_auth(&self) -> bool {
+            self.inner.offer_client_auth()
+        }
+
+        fn client_auth_mandatory(&self) -> bool {
+            self.inner.client_auth_mandatory()
+        }
+
+        fn verify_tls12_signature(
+            &self,
+            message: &[u8],
+            cert: &Certificate,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, rustls::Error> {
+            self.inner.verify_tls12_signature(message, cert, dss)
+        }
+
+        fn verify_tls13_signature(
+            &self,
+            message: &[u8],
+            cert: &Certificate,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, rustls::Error> {
+            self.inner.verify_tls13_signature(message, cert, dss)
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+            self.inner.supported_verify_schemes()
+        }
+    };
+}
+
+// === impl PinnedSerials ===
+
+impl<V: ?Sized> PinnedSerials<V> {
+    pub(super) fn new(inner: Arc<V>, serials: Arc<[Vec<u8>]>) -> Self {
+        Self { inner, serials }
+    }
+
+    fn check(&self, end_entity: &Certificate) -> Result<(), rustls::Error> {
+        let cert = x509::Cert::parse(end_entity.as_ref()).map_err(|_| {
+            rustls::Error::InvalidCertificate(rustls::CertificateError::BadEncoding)
+        })?;
+        let serial = cert.serial();
+        let allowed = self.serials.iter().any(|s| {
+            let n = s.iter().take_while(|&&b| b == 0).count();
+            &s[n..] == serial
+        });
+        if allowed {
+            return Ok(());
+        }
+
+        Err(rustls::Error::InvalidCertificate(
+            rustls::CertificateError::Other(Arc::new(SerialNotAllowed(hex::encode(serial)))),
+        ))
+    }
+}
+
+impl<V: ServerCertVerifier + ?Sized> ServerCertVerifier for PinnedSerials<V> {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &rustls::ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: std::time::SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            scts,
+            ocsp_response,
+            now,
+        )?;
+        self.check(end_entity)?;
+        Ok(verified)
+    }
+
+    forward_server_cert_verifier!();
+}
+
+impl<V: ClientCertVerifier + ?Sized> ClientCertVerifier for PinnedSerials<V> {
+    fn verify_client_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        now: std::time::SystemTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        let verified = self
+            .inner
+            .verify_client_cert(end_entity, intermediates, now)?;
+        self.check(end_entity)?;
+        Ok(verified)
+    }
+
+    forward_client_cert_verifier!();
+}
+
+// === impl TrustDomains ===
+
+impl<V: ?Sized> TrustDomains<V> {
+    pub(super) fn new(inner: Arc<V>, migration: Arc<TrustDomainMigration>) -> Self {
+        Self { inner, migration }
+    }
+
+    /// Returns the name of the expected identity in each deprecated trust
+    /// domain, if the name is in the current trust domain.
+    fn deprecated_names(&self, name: &str) -> Vec<(&str, String)> {
+        let trust_domain = self.migration.trust_domain.trim_end_matches('.');
+        let suffix_len = trust_domain.len() + 1;
+        if name.len() <= suffix_len || !name.is_char_boundary(name.len() - suffix_len) {
+            return Vec::new();
+        }
+        let (prefix, suffix) = name.split_at(name.len() - suffix_len);
+        if !suffix.starts_with('.') || !suffix[1..].eq_ignore_ascii_case(trust_domain) {
+            return Vec::new();
+        }
+
+        self.migration
+            .deprecated
+            .iter()
+            .map(|td| {
+                let td = td.trim_end_matches('.');
+                (td, format!("{}.{}", prefix, td))
+            })
+            .collect()
+    }
+}
+
+impl<V: ServerCertVerifier + ?Sized> ServerCertVerifier for TrustDomains<V> {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &rustls::ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: std::time::SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let error = match self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            scts,
+            ocsp_response,
+            now,
+        ) {
+            Err(
+                error
+                @ rustls::Error::InvalidCertificate(rustls::CertificateError::NotValidForName),
+            ) => error,
+            result => return result,
+        };
+
+        let name = match server_name {
+            rustls::ServerName::DnsName(name) if now < self.migration.until => name.as_ref(),
+            _ => return Err(error),
+        };
+        for (trust_domain, deprecated) in self.deprecated_names(name) {
+            let deprecated = match rustls::ServerName::try_from(deprecated.as_str()) {
+                Ok(deprecated) => deprecated,
+                Err(_) => continue,
+            };
+            if let Ok(verified) = self.inner.verify_server_cert(
+                end_entity,
+                intermediates,
+                &deprecated,
+                &mut std::iter::empty(),
+                ocsp_response,
+                now,
+            ) {
+                warn!(server.id = %name, trust_domain, "Accepted server identity in a deprecated trust domain");
+                return Ok(verified);
+            }
+        }
+        Err(error)
+    }
+
+    forward_server_cert_verifier!();
+}
+
+// === impl MaxLifetime ===
+
+impl<V: ?Sized> MaxLifetime<V> {
+    pub(super) fn new(inner: Arc<V>, max: Duration) -> Self {
+        Self { inner, max }
+    }
+
+    fn check(&self, end_entity: &Certificate) -> Result<(), rustls::Error> {
+        let (not_before, not_after) = x509::Cert::parse(end_entity.as_ref())
+            .and_then(|cert| cert.validity())
+            .map_err(|_| {
+                rustls::Error::InvalidCertificate(rustls::CertificateError::BadEncoding)
+            })?;
+        let lifetime = not_after
+            .duration_since(not_before)
+            .unwrap_or(Duration::ZERO);
+        if lifetime <= self.max {
+            return Ok(());
+        }
+
+        Err(rustls::Error::InvalidCertificate(
+            rustls::CertificateError::Other(Arc::new(PeerLifetimeExceeded {
+                lifetime,
+                max: self.max,
+            })),
+        ))
+    }
+}
+
+impl<V: ServerCertVerifier + ?Sized> ServerCertVerifier for MaxLifetime<V> {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &rustls::ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: std::time::SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            scts,
+            ocsp_response,
+            now,
+        )?;
+        self.check(end_entity)?;
+        Ok(verified)
+    }
+
+    forward_server_cert_verifier!();
+}
+
+impl<V: ClientCertVerifier + ?Sized> ClientCertVerifier for MaxLifetime<V> {
+    fn verify_client_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        now: std::time::SystemTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        let verified = self
+            .inner
+            .verify_client_cert(end_entity, intermediates, now)?;
+        self.check(end_entity)?;
+        Ok(verified)
+    }
+
+    forward_client_cert_verifier!();
+}
+
+// === impl PinnedIntermediates ===
+
+impl<V: ?Sized> PinnedIntermediates<V> {
+    pub(super) fn new(inner: Arc<V>, pins: Arc<[[u8; 32]]>) -> Self {
+        Self { inner, pins }
+    }
+
+    fn check(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        now: std::time::SystemTime,
+        usage: webpki::KeyUsage,
+    ) -> Result<(), rustls::Error> {
+        let (pinned, others): (Vec<_>, Vec<_>) = intermediates
+            .iter()
+            .partition(|c| spki_sha256(&c.0).map_or(false, |digest| self.pins.contains(&digest)));
+        let anchors = pinned
+            .iter()
+            .filter_map(|c| webpki::TrustAnchor::try_from_cert_der(&c.0).ok())
+            .collect::<Vec<_>>();
+        let others = others.iter().map(|c| c.0.as_slice()).collect::<Vec<_>>();
+
+        let not_pinned = || {
+            rustls::Error::InvalidCertificate(rustls::CertificateError::Other(Arc::new(
+                IntermediateNotPinned(()),
+            )))
+        };
+        if anchors.is_empty() {
+            debug!("Peer presented no pinned intermediates");
+            return Err(not_pinned());
+        }
+        let time = webpki::Time::try_from(now).map_err(|_| not_pinned())?;
+        webpki::EndEntityCert::try_from(end_entity.as_ref())
+            .and_then(|cert| cert.verify_for_usage(SIG_ALGS, &anchors, &others, time, usage, &[]))
+            .map_err(|error| {
+                debug!(%error, "Peer certificate is not issued by a pinned intermediate");
+                not_pinned()
+            })
+    }
+}
+
+impl<V: ServerCertVerifier + ?Sized> ServerCertVerifier for PinnedIntermediates<V> {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &rustls::ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: std::time::SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            scts,
+            ocsp_response,
+            now,
+        )?;
+        self.check(
+            end_entity,
+            intermediates,
+            now,
+            webpki::KeyUsage::server_auth(),
+        )?;
+        Ok(verified)
+    }
+
+    forward_server_cert_verifier!();
+}
+
+impl<V: ClientCertVerifier + ?Sized> ClientCertVerifier for PinnedIntermediates<V> {
+    fn verify_client_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        now: std::time::SystemTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        let verified = self
+            .inner
+            .verify_client_cert(end_entity, intermediates, now)?;
+        self.check(
+            end_entity,
+            intermediates,
+            now,
+            webpki::KeyUsage::client_auth(),
+        )?;
+        Ok(verified)
+    }
+
+    forward_client_cert_verifier!();
+}
+
+// === impl MaxIntermediates ===
+
+impl<V: ?Sized> MaxIntermediates<V> {
+    pub(super) fn new(inner: Arc<V>, max: usize) -> Self {
+        Self { inner, max }
+    }
+
+    fn check(&self, intermediates: &[Certificate]) -> Result<(), rustls::Error> {
+        if intermediates.len() <= self.max {
+            return Ok(());
+        }
+
+        Err(rustls::Error::InvalidCertificate(
+            rustls::CertificateError::Other(Arc::new(TooManyIntermediates {
+                count: intermediates.len(),
+                max: self.max,
+            })),
+        ))
+    }
+}
+
+impl<V: ServerCertVerifier + ?Sized> ServerCertVerifier for MaxIntermediates<V> {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &rustls::ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: std::time::SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        self.check(intermediates)?;
+        self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            scts,
+            ocsp_response,
+            now,
+        )
+    }
+
+    forward_server_cert_verifier!();
+}
+
+impl<V: ClientCertVerifier + ?Sized> ClientCertVerifier for MaxIntermediates<V> {
+    fn verify_client_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        now: std::time::SystemTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        self.check(intermediates)?;
+        self.inner
+            .verify_client_cert(end_entity, intermediates, now)
+    }
+
+    forward_client_cert_verifier!();
+}
+
+// === impl SignatureAlgorithms ===
+
+impl<V: ?Sized> SignatureAlgorithms<V> {
+    pub(super) fn new(inner: Arc<V>, allowed: Arc<[SignatureAlgorithm]>) -> Self {
+        Self { inner, allowed }
+    }
+
+    fn check(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+    ) -> Result<(), rustls::Error> {
+        let chain = std::iter::once(end_entity).chain(intermediates);
+        for (index, cert) in chain.enumerate() {
+            let algorithm = x509::Cert::parse(cert.as_ref())
+                .and_then(|cert| cert.signature_algorithm())
+                .map_err(|_| {
+                    rustls::Error::InvalidCertificate(rustls::CertificateError::BadEncoding)
+                })?;
+            if matches!(algorithm, Some(ref a) if self.allowed.contains(a)) {
+                continue;
+            }
+
+            let algorithm = match algorithm {
+                Some(algorithm) => algorithm.to_string(),
+                None => "an unrecognized algorithm".to_string(),
+            };
+            return Err(rustls::Error::InvalidCertificate(
+                rustls::CertificateError::Other(Arc::new(SignatureAlgorithmNotAllowed {
+                    index,
+                    algorithm,
+                })),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl<V: ServerCertVerifier + ?Sized> ServerCertVerifier for SignatureAlgorithms<V> {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &rustls::ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: std::time::SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        self.check(end_entity, intermediates)?;
+        self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            scts,
+            ocsp_response,
+            now,
+        )
+    }
+
+    forward_server_cert_verifier!();
+}
+
+impl<V: ClientCertVerifier + ?Sized> ClientCertVerifier for SignatureAlgorithms<V> {
+    fn verify_client_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        now: std::time::SystemTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        self.check(end_entity, intermediates)?;
+        self.inner
+            .verify_client_cert(end_entity, intermediates, now)
+    }
+
+    forward_client_cert_verifier!();
+}
+
+// === impl PermittedDomains ===
+
+impl<V: ?Sized> PermittedDomains<V> {
+    pub(super) fn new(inner: Arc<V>, permitted: Arc<[IdentityPattern]>) -> Self {
+        Self { inner, permitted }
+    }
+
+    fn check(&self, end_entity: &Certificate) -> Result<(), rustls::Error> {
+        let names = x509::Cert::parse(end_entity.as_ref())
+            .and_then(|cert| cert.dns_names())
+            .map_err(|_| {
+                rustls::Error::InvalidCertificate(rustls::CertificateError::BadEncoding)
+            })?;
+        for name in names {
+            if !self.permitted.iter().any(|p| p.matches_dns_name(name)) {
+                return Err(rustls::Error::InvalidCertificate(
+                    rustls::CertificateError::Other(Arc::new(DnsNameNotPermitted(
+                        name.to_string(),
+                    ))),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<V: ServerCertVerifier + ?Sized> ServerCertVerifier for PermittedDomains<V> {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &rustls::ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: std::time::SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            scts,
+            ocsp_response,
+            now,
+        )?;
+        self.check(end_entity)?;
+        Ok(verified)
+    }
+
+    forward_server_cert_verifier!();
+}
+
+// === impl StapledOcsp ===
+
+impl<V: ?Sized> StapledOcsp<V> {
+    pub(super) fn new(inner: Arc<V>) -> Self {
+        Self { inner }
+    }
+
+    fn check(&self, end_entity: &Certificate, ocsp_response: &[u8]) -> Result<(), rustls::Error> {
+        if ocsp_response.is_empty() {
+            return Ok(());
+        }
+
+        let cert = x509::Cert::parse(end_entity.as_ref()).map_err(|_| {
+            rustls::Error::InvalidCertificate(rustls::CertificateError::BadEncoding)
+        })?;
+        match x509::ocsp_status(ocsp_response, cert.serial()) {
+            Ok(Some(x509::OcspStatus::Revoked)) => Err(rustls::Error::InvalidCertificate(
+                rustls::CertificateError::Revoked,
+            )),
+            Ok(_) => Ok(()),
+            Err(_) => {
+                debug!("Ignoring malformed stapled OCSP response");
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<V: ServerCertVerifier + ?Sized> ServerCertVerifier for StapledOcsp<V> {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &rustls::ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: std::time::SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            scts,
+            ocsp_response,
+            now,
+        )?;
+        self.check(end_entity, ocsp_response)?;
+        Ok(verified)
+    }
+
+    forward_server_cert_verifier!();
+}
+
+// === impl ClientCaHints ===
+
+impl<V: ?Sized> ClientCaHints<V> {
+    pub(super) fn new(inner: Arc<V>, subjects: Arc<[DistinguishedName]>) -> Self {
+        Self { inner, subjects }
+    }
+}
+
+impl<V: ClientCertVerifier + ?Sized> ClientCertVerifier for ClientCaHints<V> {
+    fn client_auth_root_subjects(&self) -> &[DistinguishedName] {
+        &self.subjects
+    }
+
+    fn verify_client_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        now: std::time::SystemTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        self.inner
+            .verify_client_cert(end_entity, intermediates, now)
+    }
+
+    forward_client_cert_verifier!(except client_auth_root_subjects);
+}
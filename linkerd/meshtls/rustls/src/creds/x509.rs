@@ -0,0 +1,985 @@
+//! Minimal DER parsing for the X.509 fields that webpki doesn't expose.
+//!
+//! This is not a general-purpose X.509 parser: it only understands the subset
+//! of the encoding needed to inspect certificates that have already been (or
+//! are about to be) verified by webpki.
+
+use super::SignatureAlgorithm;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[error("malformed certificate")]
+pub struct BadDer(());
+
+/// A parsed view of a DER-encoded X.509 certificate.
+#[derive(Debug)]
+pub(crate) struct Cert<'a> {
+    serial: &'a [u8],
+    issuer: &'a [u8],
+    validity: &'a [u8],
+    subject: &'a [u8],
+    spki: &'a [u8],
+    extensions: Option<&'a [u8]>,
+    signature_algorithm: &'a [u8],
+}
+
+/// The status that an OCSP response reports for a certificate (RFC 6960,
+/// section 4.2.1).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum OcspStatus {
+    Good,
+    Revoked,
+    Unknown,
+}
+
+/// A parsed view of a DER-encoded PKCS #10 certificate signing request.
+#[derive(Debug)]
+pub(crate) struct Csr<'a> {
+    extensions: Option<&'a [u8]>,
+}
+
+pub(crate) mod oid {
+    /// id-at-commonName (2.5.4.3).
+    pub(crate) const COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+
+    pub(crate) const SUBJECT_KEY_IDENTIFIER: &[u8] = &[0x55, 0x1d, 0x0e];
+    pub(crate) const AUTHORITY_KEY_IDENTIFIER: &[u8] = &[0x55, 0x1d, 0x23];
+    pub(crate) const SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1d, 0x11];
+    pub(crate) const BASIC_CONSTRAINTS: &[u8] = &[0x55, 0x1d, 0x13];
+
+    /// authorityInfoAccess (1.3.6.1.5.5.7.1.1).
+    pub(super) const AUTHORITY_INFO_ACCESS: &[u8] =
+        &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x01, 0x01];
+
+    /// id-ad-ocsp (1.3.6.1.5.5.7.48.1).
+    pub(super) const OCSP: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x30, 0x01];
+
+    /// id-pkix-ocsp-basic (1.3.6.1.5.5.7.48.1.1).
+    pub(super) const OCSP_BASIC: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x30, 0x01, 0x01];
+
+    /// PKCS #9 extensionRequest (1.2.840.113549.1.9.14).
+    pub(super) const EXTENSION_REQUEST: &[u8] =
+        &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x0e];
+
+    /// id-ecPublicKey (1.2.840.10045.2.1) and its named curves: prime256v1
+    /// (1.2.840.10045.3.1.7), secp384r1 (1.3.132.0.34), and secp521r1
+    /// (1.3.132.0.35).
+    pub(crate) const EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+    pub(crate) const PRIME256V1: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+    pub(crate) const SECP384R1: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x22];
+    pub(crate) const SECP521R1: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x23];
+
+    /// rsaEncryption (1.2.840.113549.1.1.1) and id-RSASSA-PSS
+    /// (1.2.840.113549.1.1.10).
+    pub(crate) const RSA_ENCRYPTION: &[u8] =
+        &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+    pub(crate) const RSASSA_PSS: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0a];
+
+    /// id-X25519, id-X448, id-Ed25519, and id-Ed448 (1.3.101.110-113).
+    pub(crate) const X25519: &[u8] = &[0x2b, 0x65, 0x6e];
+    pub(crate) const X448: &[u8] = &[0x2b, 0x65, 0x6f];
+    pub(crate) const ED25519: &[u8] = &[0x2b, 0x65, 0x70];
+    pub(crate) const ED448: &[u8] = &[0x2b, 0x65, 0x71];
+
+    /// id-dsa (1.2.840.10040.4.1).
+    pub(crate) const DSA: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x38, 0x04, 0x01];
+
+    /// ecdsa-with-SHA1 (1.2.840.10045.4.1) and ecdsa-with-SHA256, -SHA384, and
+    /// -SHA512 (1.2.840.10045.4.3.2-4).
+    pub(crate) const ECDSA_WITH_SHA1: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x01];
+    pub(crate) const ECDSA_WITH_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+    pub(crate) const ECDSA_WITH_SHA384: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x03];
+    pub(crate) const ECDSA_WITH_SHA512: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x04];
+
+    /// sha1WithRSAEncryption (1.2.840.113549.1.1.5) and sha256-, sha384-, and
+    /// sha512WithRSAEncryption (1.2.840.113549.1.1.11-13).
+    pub(crate) const SHA1_WITH_RSA_ENCRYPTION: &[u8] =
+        &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x05];
+    pub(crate) const SHA256_WITH_RSA_ENCRYPTION: &[u8] =
+        &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b];
+    pub(crate) const SHA384_WITH_RSA_ENCRYPTION: &[u8] =
+        &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0c];
+    pub(crate) const SHA512_WITH_RSA_ENCRYPTION: &[u8] =
+        &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0d];
+
+    /// id-sha1 (1.3.14.3.2.26) and id-sha256, -sha384, and -sha512
+    /// (2.16.840.1.101.3.4.2.1-3), as used in RSASSA-PSS parameters.
+    pub(super) const SHA1: &[u8] = &[0x2b, 0x0e, 0x03, 0x02, 0x1a];
+    pub(super) const SHA256: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+    pub(super) const SHA384: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02];
+    pub(super) const SHA512: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03];
+}
+
+/// Attribute types that RFC 4514 names in distinguished names.
+const NAME_ATTRIBUTES: &[(&[u8], &str)] = &[
+    (oid::COMMON_NAME, "CN"),
+    (&[0x55, 0x04, 0x06], "C"),
+    (&[0x55, 0x04, 0x07], "L"),
+    (&[0x55, 0x04, 0x08], "ST"),
+    (&[0x55, 0x04, 0x09], "STREET"),
+    (&[0x55, 0x04, 0x0a], "O"),
+    (&[0x55, 0x04, 0x0b], "OU"),
+    (
+        &[0x09, 0x92, 0x26, 0x89, 0x93, 0xf2, 0x2c, 0x64, 0x01, 0x01],
+        "UID",
+    ),
+    (
+        &[0x09, 0x92, 0x26, 0x89, 0x93, 0xf2, 0x2c, 0x64, 0x01, 0x19],
+        "DC",
+    ),
+];
+
+pub(super) const BOOLEAN: u8 = 0x01;
+pub(super) const INTEGER: u8 = 0x02;
+pub(super) const BIT_STRING: u8 = 0x03;
+pub(super) const OCTET_STRING: u8 = 0x04;
+pub(super) const NULL: u8 = 0x05;
+pub(super) const OID: u8 = 0x06;
+pub(super) const ENUMERATED: u8 = 0x0a;
+pub(super) const UTF8_STRING: u8 = 0x0c;
+pub(super) const PRINTABLE_STRING: u8 = 0x13;
+pub(super) const IA5_STRING: u8 = 0x16;
+pub(super) const UTC_TIME: u8 = 0x17;
+pub(super) const GENERALIZED_TIME: u8 = 0x18;
+pub(super) const SEQUENCE: u8 = 0x30;
+pub(super) const SET: u8 = 0x31;
+pub(super) const EXPLICIT_0: u8 = 0xa0;
+pub(super) const EXPLICIT_1: u8 = 0xa1;
+pub(super) const EXPLICIT_3: u8 = 0xa3;
+pub(super) const IMPLICIT_0: u8 = 0x80;
+pub(super) const IMPLICIT_1: u8 = 0x81;
+pub(super) const IMPLICIT_2: u8 = 0x82;
+pub(super) const CSR_ATTRIBUTES: u8 = 0xa0;
+pub(super) const DNS_NAME: u8 = 0x82;
+pub(super) const URI: u8 = 0x86;
+
+/// Reads DER-encoded TLVs from a byte slice.
+#[derive(Clone, Debug)]
+struct Reader<'a>(&'a [u8]);
+
+// === impl Cert ===
+
+impl<'a> Cert<'a> {
+    pub(crate) fn parse(der: &'a [u8]) -> Result<Self, BadDer> {
+        let mut cert = Reader(Reader(der).read_only(SEQUENCE)?);
+        let mut tbs = Reader(cert.read(SEQUENCE)?);
+
+        tbs.read_optional(EXPLICIT_0)?; // version
+        let serial = tbs.read(INTEGER)?;
+        tbs.read(SEQUENCE)?; // signature
+        let issuer = tbs.read_tlv(SEQUENCE)?;
+        let validity = tbs.read(SEQUENCE)?;
+        let subject = tbs.read_tlv(SEQUENCE)?;
+        let spki = tbs.read_tlv(SEQUENCE)?;
+        tbs.read_optional(IMPLICIT_1)?; // issuerUniqueID
+        tbs.read_optional(IMPLICIT_2)?; // subjectUniqueID
+        let extensions = tbs
+            .read_optional(EXPLICIT_3)?
+            .map(|exts| Reader(exts).read_only(SEQUENCE))
+            .transpose()?;
+        if !tbs.is_empty() {
+            return Err(BadDer(()));
+        }
+        let signature_algorithm = cert.read(SEQUENCE)?;
+
+        Ok(Self {
+            serial,
+            issuer,
+            validity,
+            subject,
+            spki,
+            extensions,
+            signature_algorithm,
+        })
+    }
+
+    /// Returns the big-endian serial number, without leading zero octets.
+    pub(crate) fn serial(&self) -> &'a [u8] {
+        let n = self.serial.iter().take_while(|&&b| b == 0).count();
+        &self.serial[n..]
+    }
+
+    /// Returns the DER encoding of the issuer's distinguished name.
+    pub(crate) fn issuer(&self) -> &'a [u8] {
+        self.issuer
+    }
+
+    /// Returns the DER encoding of the subject's distinguished name.
+    pub(crate) fn subject(&self) -> &'a [u8] {
+        self.subject
+    }
+
+    /// Returns the DER encoding of the certificate's SubjectPublicKeyInfo.
+    pub(crate) fn spki(&self) -> &'a [u8] {
+        self.spki
+    }
+
+    /// Returns the subject's public key, as encoded in the SubjectPublicKeyInfo's
+    /// BIT STRING (e.g. an uncompressed EC point, or a DER-encoded
+    /// `RSAPublicKey`).
+    pub(crate) fn public_key(&self) -> Result<&'a [u8], BadDer> {
+        spki_parts(self.spki).map(|(_, _, key)| key)
+    }
+
+    /// Returns the period during which the certificate is valid, as its
+    /// `(notBefore, notAfter)` times.
+    pub(crate) fn validity(&self) -> Result<(SystemTime, SystemTime), BadDer> {
+        let mut validity = Reader(self.validity);
+        let not_before = validity.read_time()?;
+        let not_after = validity.read_time()?;
+        if !validity.is_empty() {
+            return Err(BadDer(()));
+        }
+        Ok((not_before, not_after))
+    }
+
+    /// Returns the time after which the certificate is no longer valid.
+    pub(crate) fn not_after(&self) -> Result<SystemTime, BadDer> {
+        self.validity().map(|(_, not_after)| not_after)
+    }
+
+    /// Returns the value of the extension identified by `oid`, if the
+    /// certificate has one.
+    pub(crate) fn extension(&self, oid: &[u8]) -> Result<Option<&'a [u8]>, BadDer> {
+        match self.extensions {
+            Some(exts) => find_extension(exts, oid),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the URL of the OCSP responder named in the Authority
+    /// Information Access extension, if there is one.
+    pub(crate) fn ocsp_responder(&self) -> Result<Option<&'a str>, BadDer> {
+        let aia = match self.extension(oid::AUTHORITY_INFO_ACCESS)? {
+            Some(aia) => aia,
+            None => return Ok(None),
+        };
+        let mut descriptions = Reader(Reader(aia).read_only(SEQUENCE)?);
+        while !descriptions.is_empty() {
+            let mut description = Reader(descriptions.read(SEQUENCE)?);
+            let method = description.read(OID)?;
+            if let (URI, location) = description.read_any()? {
+                if method == oid::OCSP {
+                    return std::str::from_utf8(location)
+                        .map(Some)
+                        .map_err(|_| BadDer(()));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the maximum number of intermediate CAs that may follow this
+    /// certificate in a path, if its Basic Constraints extension limits them.
+    pub(crate) fn path_len_constraint(&self) -> Result<Option<u64>, BadDer> {
+        let bc = match self.extension(oid::BASIC_CONSTRAINTS)? {
+            Some(bc) => bc,
+            None => return Ok(None),
+        };
+        let mut bc = Reader(Reader(bc).read_only(SEQUENCE)?);
+        bc.read_optional(BOOLEAN)?; // cA
+        let path_len = match bc.read_optional(INTEGER)? {
+            Some(n) if !n.is_empty() && n.len() <= 8 && n[0] & 0x80 == 0 => {
+                n.iter().fold(0, |n, &b| (n << 8) | u64::from(b))
+            }
+            Some(_) => return Err(BadDer(())),
+            None => return Ok(None),
+        };
+        if !bc.is_empty() {
+            return Err(BadDer(()));
+        }
+        Ok(Some(path_len))
+    }
+
+    /// Returns the DNS names in the Subject Alternative Name extension.
+    pub(crate) fn dns_names(&self) -> Result<Vec<&'a str>, BadDer> {
+        match self.extension(oid::SUBJECT_ALT_NAME)? {
+            Some(san) => general_names(san, DNS_NAME),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Returns the URIs in the Subject Alternative Name extension.
+    pub(crate) fn uris(&self) -> Result<Vec<&'a str>, BadDer> {
+        match self.extension(oid::SUBJECT_ALT_NAME)? {
+            Some(san) => general_names(san, URI),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Returns the algorithm that the certificate is signed with, or `None` if
+    /// it isn't recognized.
+    pub(crate) fn signature_algorithm(&self) -> Result<Option<SignatureAlgorithm>, BadDer> {
+        let mut algorithm = Reader(self.signature_algorithm);
+        let algorithm = match algorithm.read(OID)? {
+            oid::ECDSA_WITH_SHA1 => SignatureAlgorithm::EcdsaSha1,
+            oid::ECDSA_WITH_SHA256 => SignatureAlgorithm::EcdsaSha256,
+            oid::ECDSA_WITH_SHA384 => SignatureAlgorithm::EcdsaSha384,
+            oid::ECDSA_WITH_SHA512 => SignatureAlgorithm::EcdsaSha512,
+            oid::ED25519 => SignatureAlgorithm::Ed25519,
+            oid::SHA1_WITH_RSA_ENCRYPTION => SignatureAlgorithm::RsaPkcs1Sha1,
+            oid::SHA256_WITH_RSA_ENCRYPTION => SignatureAlgorithm::RsaPkcs1Sha256,
+            oid::SHA384_WITH_RSA_ENCRYPTION => SignatureAlgorithm::RsaPkcs1Sha384,
+            oid::SHA512_WITH_RSA_ENCRYPTION => SignatureAlgorithm::RsaPkcs1Sha512,
+            oid::RSASSA_PSS => {
+                // RSASSA-PSS-params ::= SEQUENCE {
+                //   hashAlgorithm [0] HashAlgorithm DEFAULT sha1,
+                //   ...
+                // }
+                let mut params = Reader(algorithm.read(SEQUENCE)?);
+                let hash = match params.read_optional(EXPLICIT_0)? {
+                    Some(hash) => Reader(Reader(hash).read_only(SEQUENCE)?).read(OID)?,
+                    None => oid::SHA1,
+                };
+                match hash {
+                    oid::SHA256 => SignatureAlgorithm::RsaPssSha256,
+                    oid::SHA384 => SignatureAlgorithm::RsaPssSha384,
+                    oid::SHA512 => SignatureAlgorithm::RsaPssSha512,
+                    _ => return Ok(None),
+                }
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(algorithm))
+    }
+
+    pub(crate) fn has_extension(&self, oid: &[u8]) -> Result<bool, BadDer> {
+        self.extension(oid).map(|ext| ext.is_some())
+    }
+}
+
+// === impl Csr ===
+
+impl<'a> Csr<'a> {
+    pub(crate) fn parse(der: &'a [u8]) -> Result<Self, BadDer> {
+        let mut csr = Reader(Reader(der).read_only(SEQUENCE)?);
+        let mut info = Reader(csr.read(SEQUENCE)?);
+
+        info.read(INTEGER)?; // version
+        info.read(SEQUENCE)?; // subject
+        info.read(SEQUENCE)?; // subjectPKInfo
+        let mut attrs = Reader(info.read(CSR_ATTRIBUTES)?);
+        if !info.is_empty() {
+            return Err(BadDer(()));
+        }
+
+        while !attrs.is_empty() {
+            let mut attr = Reader(attrs.read(SEQUENCE)?);
+            if attr.read(OID)? == oid::EXTENSION_REQUEST {
+                let values = Reader(attr.read(SET)?);
+                let extensions = Reader(values.read_only(SEQUENCE)?);
+                return Ok(Self {
+                    extensions: Some(extensions.0),
+                });
+            }
+        }
+        Ok(Self { extensions: None })
+    }
+
+    /// Returns the DNS names requested in the Subject Alternative Name
+    /// extension.
+    pub(crate) fn dns_names(&self) -> Result<Vec<&'a str>, BadDer> {
+        let san = match self.extensions {
+            Some(exts) => find_extension(exts, oid::SUBJECT_ALT_NAME)?,
+            None => None,
+        };
+        match san {
+            Some(san) => general_names(san, DNS_NAME),
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+/// Formats a DER-encoded distinguished name as an RFC 4514 string, e.g.
+/// `CN=root.linkerd.cluster.local,O=Linkerd`.
+///
+/// Attribute types without a string representation are written as dotted
+/// OIDs, and values that aren't ASCII or UTF-8 strings are written as hex.
+pub(crate) fn format_name(name: &[u8]) -> Result<String, BadDer> {
+    let mut rdns = Reader(Reader(name).read_only(SEQUENCE)?);
+    let mut formatted = Vec::new();
+    while !rdns.is_empty() {
+        let mut atvs = Reader(rdns.read(SET)?);
+        let mut rdn = Vec::new();
+        while !atvs.is_empty() {
+            let mut atv = Reader(atvs.read(SEQUENCE)?);
+            let attr = atv.read(OID)?;
+            let attr = match NAME_ATTRIBUTES.iter().find(|(oid, _)| *oid == attr) {
+                Some((_, name)) => name.to_string(),
+                None => dotted_oid(attr)?,
+            };
+            let encoded = atv.0;
+            let value = match atv.read_any()? {
+                (UTF8_STRING | PRINTABLE_STRING | IA5_STRING, value) => {
+                    escape_name_value(std::str::from_utf8(value).map_err(|_| BadDer(()))?)
+                }
+                _ => format!("#{}", hex::encode(encoded)),
+            };
+            if !atv.is_empty() {
+                return Err(BadDer(()));
+            }
+            rdn.push(format!("{}={}", attr, value));
+        }
+        formatted.push(rdn.join("+"));
+    }
+    // RFC 4514 lists the most specific RDN first.
+    formatted.reverse();
+    Ok(formatted.join(","))
+}
+
+/// Escapes the characters that RFC 4514 reserves in attribute values.
+fn escape_name_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for (i, c) in value.char_indices() {
+        match c {
+            '"' | '+' | ',' | ';' | '<' | '>' | '\\' => escaped.push('\\'),
+            '#' if i == 0 => escaped.push('\\'),
+            ' ' if i == 0 || i + 1 == value.len() => escaped.push('\\'),
+            '\0' => {
+                escaped.push_str("\\00");
+                continue;
+            }
+            _ => {}
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Formats a DER-encoded object identifier in dotted-decimal notation.
+fn dotted_oid(oid: &[u8]) -> Result<String, BadDer> {
+    if oid.last().map_or(true, |b| b & 0x80 != 0) {
+        return Err(BadDer(()));
+    }
+    let mut arcs = Vec::new();
+    let mut arc = 0u64;
+    for &b in oid {
+        if arc > u64::MAX >> 7 {
+            return Err(BadDer(()));
+        }
+        arc = (arc << 7) | u64::from(b & 0x7f);
+        if b & 0x80 == 0 {
+            arcs.push(arc);
+            arc = 0;
+        }
+    }
+    // The first subidentifier encodes the first two arcs.
+    let (first, second) = match arcs[0] {
+        a if a < 40 => (0, a),
+        a if a < 80 => (1, a - 40),
+        a => (2, a - 80),
+    };
+    let arcs = [first, second]
+        .iter()
+        .chain(&arcs[1..])
+        .map(u64::to_string)
+        .collect::<Vec<_>>();
+    Ok(arcs.join("."))
+}
+
+/// Returns the DER-encoded SubjectPublicKeyInfo for an uncompressed P-256
+/// public key, as returned by ring's `EcdsaKeyPair::public_key`.
+pub(crate) fn p256_spki(public_key: &[u8]) -> Vec<u8> {
+    // SEQUENCE {
+    //   SEQUENCE { OID ecPublicKey, OID prime256v1 },
+    //   BIT STRING (no unused bits)
+    // }
+    const PREFIX: &[u8] = &[
+        0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08,
+        0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00,
+    ];
+    debug_assert_eq!(
+        public_key.len(),
+        65,
+        "P-256 public keys must be uncompressed"
+    );
+
+    let mut spki = Vec::with_capacity(PREFIX.len() + public_key.len());
+    spki.extend_from_slice(PREFIX);
+    spki.extend_from_slice(public_key);
+    spki
+}
+
+/// Encodes a DER TLV with the given tag and contents.
+pub(crate) fn tlv(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(contents.len() + 6);
+    out.push(tag);
+    if contents.len() < 0x80 {
+        out.push(contents.len() as u8);
+    } else {
+        let len = (contents.len() as u32).to_be_bytes();
+        let n = len.iter().take_while(|&&b| b == 0).count();
+        out.push(0x80 | (len.len() - n) as u8);
+        out.extend_from_slice(&len[n..]);
+    }
+    out.extend_from_slice(contents);
+    out
+}
+
+/// Returns the algorithm OID of a DER-encoded PKCS #8 private key, along with
+/// the OID in its parameters, if any (e.g. an EC key's named curve).
+pub(crate) fn pkcs8_algorithm(der: &[u8]) -> Result<(&[u8], Option<&[u8]>), BadDer> {
+    let mut info = Reader(Reader(der).read_only(SEQUENCE)?);
+    info.read(INTEGER)?; // version
+    let mut algorithm = Reader(info.read(SEQUENCE)?);
+    let oid = algorithm.read(OID)?;
+    let parameters = algorithm.read_optional(OID)?;
+    Ok((oid, parameters))
+}
+
+/// Returns the algorithm OID of a DER-encoded SubjectPublicKeyInfo, along with
+/// the OID in its parameters, if any, and the public key in its BIT STRING.
+pub(crate) fn spki_parts(der: &[u8]) -> Result<(&[u8], Option<&[u8]>, &[u8]), BadDer> {
+    let mut spki = Reader(Reader(der).read_only(SEQUENCE)?);
+    let mut algorithm = Reader(spki.read(SEQUENCE)?);
+    let oid = algorithm.read(OID)?;
+    let parameters = algorithm.read_optional(OID)?;
+    let key = spki.read(BIT_STRING)?;
+    match key.split_first() {
+        // Keys are always a whole number of octets.
+        Some((0, key)) if spki.is_empty() => Ok((oid, parameters, key)),
+        _ => Err(BadDer(())),
+    }
+}
+
+/// Returns whether a DER-encoded PKCS #8 key is an `EncryptedPrivateKeyInfo`,
+/// which begins with an `AlgorithmIdentifier` rather than a version.
+pub(crate) fn pkcs8_is_encrypted(der: &[u8]) -> bool {
+    Reader(der)
+        .read_only(SEQUENCE)
+        .map_or(false, |info| Reader(info).peek(SEQUENCE))
+}
+
+/// Converts a DER-encoded SEC 1 `ECPrivateKey` (as in an `EC PRIVATE KEY` PEM
+/// section) to PKCS #8.
+///
+/// The key must name its curve, which moves to the PKCS #8
+/// `AlgorithmIdentifier`; ring rejects keys that name it in both places.
+pub(crate) fn sec1_to_pkcs8(der: &[u8]) -> Result<Vec<u8>, BadDer> {
+    let mut key = Reader(Reader(der).read_only(SEQUENCE)?);
+    if key.read(INTEGER)? != [1] {
+        return Err(BadDer(()));
+    }
+    let private_key = key.read(OCTET_STRING)?;
+    let curve = Reader(key.read(EXPLICIT_0)?).read_only(OID)?;
+    let public_key = key.read_optional(EXPLICIT_1)?;
+    if !key.is_empty() {
+        return Err(BadDer(()));
+    }
+
+    let mut ec_private_key = [tlv(INTEGER, &[1]), tlv(OCTET_STRING, private_key)].concat();
+    if let Some(public_key) = public_key {
+        ec_private_key.extend(tlv(EXPLICIT_1, public_key));
+    }
+    Ok(private_key_info(
+        &[tlv(OID, oid::EC_PUBLIC_KEY), tlv(OID, curve)].concat(),
+        &tlv(SEQUENCE, &ec_private_key),
+    ))
+}
+
+/// Converts a DER-encoded PKCS #1 `RSAPrivateKey` (as in an `RSA PRIVATE KEY`
+/// PEM section) to PKCS #8.
+pub(crate) fn pkcs1_to_pkcs8(der: &[u8]) -> Vec<u8> {
+    private_key_info(
+        &[tlv(OID, oid::RSA_ENCRYPTION), tlv(NULL, &[])].concat(),
+        der,
+    )
+}
+
+/// Returns a DER-encoded PKCS #8 `PrivateKeyInfo` for a key of the given
+/// algorithm.
+fn private_key_info(algorithm: &[u8], private_key: &[u8]) -> Vec<u8> {
+    tlv(
+        SEQUENCE,
+        &[
+            tlv(INTEGER, &[0]), // v1
+            tlv(SEQUENCE, algorithm),
+            tlv(OCTET_STRING, private_key),
+        ]
+        .concat(),
+    )
+}
+
+/// Splits a DER-encoded PKCS #10 certificate signing request into its signed
+/// `CertificationRequestInfo` (including its tag and length), the
+/// SubjectPublicKeyInfo it contains, its signature algorithm, and its
+/// signature.
+pub(crate) fn csr_parts(der: &[u8]) -> Result<(&[u8], &[u8], &[u8], &[u8]), BadDer> {
+    let mut csr = Reader(Reader(der).read_only(SEQUENCE)?);
+    let info = csr.read_tlv(SEQUENCE)?;
+    let algorithm = csr.read_tlv(SEQUENCE)?;
+    let signature = match csr.read_only(BIT_STRING)? {
+        [0, signature @ ..] => signature,
+        _ => return Err(BadDer(())),
+    };
+
+    let mut fields = Reader(Reader(info).read_only(SEQUENCE)?);
+    fields.read(INTEGER)?; // version
+    fields.read(SEQUENCE)?; // subject
+    let spki = fields.read_tlv(SEQUENCE)?;
+    Ok((info, spki, algorithm, signature))
+}
+
+/// Returns the status that a DER-encoded OCSP response reports for the
+/// certificate with the given serial number (as returned by `Cert::serial`),
+/// or `None` if the response wasn't successful or doesn't cover the
+/// certificate.
+///
+/// Only basic responses are understood, and their signatures are not checked.
+pub(crate) fn ocsp_status(response: &[u8], serial: &[u8]) -> Result<Option<OcspStatus>, BadDer> {
+    let mut response = Reader(Reader(response).read_only(SEQUENCE)?);
+    // Unsuccessful responses (e.g. `tryLater`) have no response bytes.
+    if response.read(ENUMERATED)? != [0] {
+        return Ok(None);
+    }
+    let mut bytes = Reader(Reader(response.read_only(EXPLICIT_0)?).read_only(SEQUENCE)?);
+    if bytes.read(OID)? != oid::OCSP_BASIC {
+        return Ok(None);
+    }
+    let basic = bytes.read_only(OCTET_STRING)?;
+
+    let mut basic = Reader(Reader(basic).read_only(SEQUENCE)?);
+    let mut data = Reader(basic.read(SEQUENCE)?);
+    data.read_optional(EXPLICIT_0)?; // version
+    data.read_any()?; // responderID
+    data.read(GENERALIZED_TIME)?; // producedAt
+    let mut responses = Reader(data.read(SEQUENCE)?);
+    while !responses.is_empty() {
+        let mut single = Reader(responses.read(SEQUENCE)?);
+        let mut cert_id = Reader(single.read(SEQUENCE)?);
+        cert_id.read(SEQUENCE)?; // hashAlgorithm
+        cert_id.read(OCTET_STRING)?; // issuerNameHash
+        cert_id.read(OCTET_STRING)?; // issuerKeyHash
+        let s = cert_id.read(INTEGER)?;
+        // `good` and `unknown` are NULLs, and `revoked` is a RevokedInfo
+        // SEQUENCE.
+        let status = match single.read_any()? {
+            (IMPLICIT_0, _) => OcspStatus::Good,
+            (EXPLICIT_1, _) => OcspStatus::Revoked,
+            (IMPLICIT_2, _) => OcspStatus::Unknown,
+            _ => return Err(BadDer(())),
+        };
+        let n = s.iter().take_while(|&&b| b == 0).count();
+        if &s[n..] == serial {
+            return Ok(Some(status));
+        }
+    }
+    Ok(None)
+}
+
+/// Finds the value of the extension identified by `oid` in a DER-encoded
+/// `Extensions` sequence.
+fn find_extension<'a>(exts: &'a [u8], oid: &[u8]) -> Result<Option<&'a [u8]>, BadDer> {
+    let mut exts = Reader(exts);
+    while !exts.is_empty() {
+        let mut ext = Reader(exts.read(SEQUENCE)?);
+        let id = ext.read(OID)?;
+        ext.read_optional(BOOLEAN)?; // critical
+        let value = ext.read(OCTET_STRING)?;
+        if id == oid {
+            return Ok(Some(value));
+        }
+    }
+    Ok(None)
+}
+
+/// Returns the names of the given type (e.g. `DNS_NAME` or `URI`) in a
+/// DER-encoded `GeneralNames` sequence.
+fn general_names(general_names: &[u8], tag: u8) -> Result<Vec<&str>, BadDer> {
+    let mut names = Reader(Reader(general_names).read_only(SEQUENCE)?);
+    let mut matching = Vec::new();
+    while !names.is_empty() {
+        let (t, name) = names.read_any()?;
+        if t == tag {
+            matching.push(std::str::from_utf8(name).map_err(|_| BadDer(()))?);
+        }
+    }
+    Ok(matching)
+}
+
+// === impl Reader ===
+
+impl<'a> Reader<'a> {
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn peek(&self, tag: u8) -> bool {
+        self.0.first() == Some(&tag)
+    }
+
+    /// Reads a single TLV, returning its tag and contents.
+    fn read_any(&mut self) -> Result<(u8, &'a [u8]), BadDer> {
+        let (&tag, rest) = self.0.split_first().ok_or(BadDer(()))?;
+        // High tag numbers are never used by the structures we parse.
+        if tag & 0x1f == 0x1f {
+            return Err(BadDer(()));
+        }
+
+        let (&first, mut rest) = rest.split_first().ok_or(BadDer(()))?;
+        let len = if first < 0x80 {
+            first as usize
+        } else {
+            let n = (first & 0x7f) as usize;
+            if n == 0 || n > 4 || rest.len() < n {
+                return Err(BadDer(()));
+            }
+            let (octets, r) = rest.split_at(n);
+            rest = r;
+            octets
+                .iter()
+                .fold(0usize, |len, &octet| (len << 8) | octet as usize)
+        };
+        if rest.len() < len {
+            return Err(BadDer(()));
+        }
+
+        let (contents, rest) = rest.split_at(len);
+        self.0 = rest;
+        Ok((tag, contents))
+    }
+
+    /// Reads a TLV with the given tag, returning its contents.
+    fn read(&mut self, tag: u8) -> Result<&'a [u8], BadDer> {
+        match self.read_any()? {
+            (t, contents) if t == tag => Ok(contents),
+            _ => Err(BadDer(())),
+        }
+    }
+
+    /// Reads a UTCTime or GeneralizedTime, as used in certificate validity
+    /// periods (RFC 5280, section 4.1.2.5).
+    fn read_time(&mut self) -> Result<SystemTime, BadDer> {
+        let (year, rest) = match self.read_any()? {
+            (UTC_TIME, t) if t.len() == 13 => {
+                // Two-digit years in 1950..=2049.
+                let yy = digits(&t[..2])?;
+                (if yy < 50 { 2000 + yy } else { 1900 + yy }, &t[2..])
+            }
+            (GENERALIZED_TIME, t) if t.len() == 15 => (digits(&t[..4])?, &t[4..]),
+            _ => return Err(BadDer(())),
+        };
+        if rest[10] != b'Z' {
+            return Err(BadDer(()));
+        }
+
+        let month = digits(&rest[0..2])?;
+        let day = digits(&rest[2..4])?;
+        let hour = digits(&rest[4..6])?;
+        let minute = digits(&rest[6..8])?;
+        let second = digits(&rest[8..10])?;
+        if !(1..=12).contains(&month)
+            || !(1..=31).contains(&day)
+            || hour > 23
+            || minute > 59
+            || second > 59
+            || year < 1970
+        {
+            return Err(BadDer(()));
+        }
+
+        let days = days_from_civil(year, month, day);
+        let secs = days * 86_400 + hour * 3_600 + minute * 60 + second;
+        Ok(UNIX_EPOCH + Duration::from_secs(secs))
+    }
+
+    /// Reads a TLV with the given tag, returning its complete encoding.
+    fn read_tlv(&mut self, tag: u8) -> Result<&'a [u8], BadDer> {
+        let start = self.0;
+        self.read(tag)?;
+        Ok(&start[..start.len() - self.0.len()])
+    }
+
+    /// Reads a TLV with the given tag if it is the next element.
+    fn read_optional(&mut self, tag: u8) -> Result<Option<&'a [u8]>, BadDer> {
+        if self.peek(tag) {
+            self.read(tag).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Reads a TLV with the given tag, requiring that it is the only remaining
+    /// element.
+    fn read_only(mut self, tag: u8) -> Result<&'a [u8], BadDer> {
+        let contents = self.read(tag)?;
+        if !self.is_empty() {
+            return Err(BadDer(()));
+        }
+        Ok(contents)
+    }
+}
+
+/// Parses a fixed-width decimal number.
+fn digits(ascii: &[u8]) -> Result<u64, BadDer> {
+    ascii.iter().try_fold(0, |n, &c| {
+        if c.is_ascii_digit() {
+            Ok(n * 10 + u64::from(c - b'0'))
+        } else {
+            Err(BadDer(()))
+        }
+    })
+}
+
+/// Formats a time as an RFC 3339 UTC timestamp, e.g. `2031-10-06T19:24:00Z`.
+///
+/// Times before the UNIX epoch are formatted as the epoch.
+pub(crate) fn format_time(time: &SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (year, month, day) = civil_from_days(secs / 86_400);
+    let secs = secs % 86_400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3_600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Returns the date that is the given number of days after the UNIX epoch.
+///
+/// See http://howardhinnant.github.io/date_algorithms.html#civil_from_days.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (year_of_era * 365 + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (day_of_year * 5 + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = (month_from_march + 2) % 12 + 1;
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Returns the number of days between the UNIX epoch and the given date, which
+/// must not precede it.
+///
+/// See http://howardhinnant.github.io/date_algorithms.html#days_from_civil.
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let month_from_march = (month + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use linkerd_tls_test_util::FOO_NS1;
+    use ring::signature::{EcdsaKeyPair, KeyPair, RsaKeyPair, ECDSA_P256_SHA256_ASN1_SIGNING};
+
+    #[test]
+    fn p256_spki_matches_issued_cert() {
+        let key = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, FOO_NS1.key).unwrap();
+        let cert = Cert::parse(FOO_NS1.crt).unwrap();
+        assert_eq!(p256_spki(key.public_key().as_ref()), cert.spki());
+    }
+
+    #[test]
+    fn ocsp_statuses_are_read() {
+        let serial = Cert::parse(FOO_NS1.crt).unwrap().serial();
+        for (der, status) in [
+            (
+                &include_bytes!("../testdata/foo-ns1-ocsp-good.der")[..],
+                OcspStatus::Good,
+            ),
+            (
+                &include_bytes!("../testdata/foo-ns1-ocsp-revoked.der")[..],
+                OcspStatus::Revoked,
+            ),
+        ] {
+            assert_eq!(ocsp_status(der, serial).unwrap(), Some(status));
+            // Responses don't report the status of other certificates.
+            assert_eq!(ocsp_status(der, &[0x01]).unwrap(), None);
+        }
+
+        // An unsuccessful (`unauthorized`) response.
+        assert_eq!(
+            ocsp_status(&[0x30, 0x03, 0x0a, 0x01, 0x06], serial).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn signature_algorithms_are_recognized() {
+        for (der, algorithm) in [
+            (FOO_NS1.crt, SignatureAlgorithm::EcdsaSha256),
+            (
+                &include_bytes!("../testdata/foo-ns1-sha384.der")[..],
+                SignatureAlgorithm::EcdsaSha384,
+            ),
+            (
+                &include_bytes!("../testdata/rsa-sha1.der")[..],
+                SignatureAlgorithm::RsaPkcs1Sha1,
+            ),
+            (
+                &include_bytes!("../testdata/rsa-pss-sha384.der")[..],
+                SignatureAlgorithm::RsaPssSha384,
+            ),
+        ] {
+            let cert = Cert::parse(der).unwrap();
+            assert_eq!(cert.signature_algorithm().unwrap(), Some(algorithm));
+        }
+    }
+
+    #[test]
+    fn public_keys_match_their_private_keys() {
+        let key = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, FOO_NS1.key).unwrap();
+        let cert = Cert::parse(FOO_NS1.crt).unwrap();
+        assert_eq!(cert.public_key().unwrap(), key.public_key().as_ref());
+
+        let key = RsaKeyPair::from_pkcs8(include_bytes!("../testdata/foo-ns1-rsa-key.p8")).unwrap();
+        let cert = Cert::parse(include_bytes!("../testdata/foo-ns1-rsa.der")).unwrap();
+        assert_eq!(cert.public_key().unwrap(), key.public_key().as_ref());
+    }
+
+    #[test]
+    fn times_are_formatted_per_rfc3339() {
+        let (_, not_after) = Cert::parse(FOO_NS1.crt).unwrap().validity().unwrap();
+        assert_eq!(format_time(&not_after), "2031-10-06T19:24:00Z");
+        assert_eq!(format_time(&UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        let leap_day = UNIX_EPOCH + Duration::from_secs(951_868_799);
+        assert_eq!(format_time(&leap_day), "2000-02-29T23:59:59Z");
+    }
+
+    #[test]
+    fn names_are_formatted_per_rfc4514() {
+        let atv = |oid: &[u8], tag: u8, value: &[u8]| {
+            tlv(SEQUENCE, &[tlv(OID, oid), tlv(tag, value)].concat())
+        };
+        let cn = &[0x55, 0x04, 0x03];
+        let name = tlv(
+            SEQUENCE,
+            &[
+                tlv(SET, &atv(&[0x55, 0x04, 0x06], PRINTABLE_STRING, b"US")),
+                tlv(SET, &atv(&[0x55, 0x04, 0x0a], UTF8_STRING, b"Acme, Inc")),
+                tlv(
+                    SET,
+                    &[
+                        atv(cn, UTF8_STRING, b"#a+b "),
+                        atv(&[0x55, 0x04, 0x0c], UTF8_STRING, b"Dr"),
+                    ]
+                    .concat(),
+                ),
+                // A BMPString, which is written as hex.
+                tlv(SET, &atv(cn, 0x1e, &[0x00, 0x78])),
+            ]
+            .concat(),
+        );
+        assert_eq!(
+            format_name(&name).unwrap(),
+            "CN=#1e020078,CN=\\#a\\+b\\ +2.5.4.12=Dr,O=Acme\\, Inc,C=US"
+        );
+        assert_eq!(format_name(&tlv(SEQUENCE, &[])).unwrap(), "");
+        assert!(format_name(&tlv(SET, &[])).is_err());
+    }
+}
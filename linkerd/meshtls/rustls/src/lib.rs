@@ -1,13 +1,19 @@
 #![deny(rust_2018_idioms, clippy::disallowed_methods, clippy::disallowed_types)]
 #![forbid(unsafe_code)]
 
+mod authz;
 mod client;
 pub mod creds;
+mod peer;
 mod server;
+mod summary;
 #[cfg(test)]
 mod tests;
 
 pub use self::{
+    authz::{identity_authorized, IdentityPattern},
     client::{ClientIo, Connect, ConnectFuture, NewClient},
-    server::{Server, ServerIo, TerminateFuture},
+    peer::ResolvedIdentity,
+    server::{client_identity, Server, ServerIo, TerminateFuture},
+    summary::TlsSummary,
 };
@@ -0,0 +1,45 @@
+use linkerd_identity::Name;
+use std::{convert::TryFrom, fmt};
+use tokio_rustls::rustls::Certificate;
+
+/// The identity of a peer, determined once its certificate has been verified
+/// during a handshake.
+///
+/// This is cheap to clone, so that it may be attached to logs for the
+/// lifetime of a connection without reparsing the certificate.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ResolvedIdentity(Name);
+
+// === impl ResolvedIdentity ===
+
+impl ResolvedIdentity {
+    /// Reads the identity from the first DNS SAN of a verified peer's leaf
+    /// certificate.
+    pub(crate) fn from_peer_certs(certs: Option<&[Certificate]>) -> Option<Self> {
+        let c = certs?.first().map(Certificate::as_ref)?;
+        let end_cert = webpki::EndEntityCert::try_from(c).ok()?;
+        let name: &str = end_cert.dns_names().ok()?.next().map(Into::into)?;
+        if name == "*" {
+            // Wildcards can perhaps be handled in a future path...
+            return None;
+        }
+
+        name.parse().ok().map(Self)
+    }
+
+    pub fn name(&self) -> &Name {
+        &self.0
+    }
+}
+
+impl From<ResolvedIdentity> for Name {
+    fn from(ResolvedIdentity(name): ResolvedIdentity) -> Self {
+        name
+    }
+}
+
+impl fmt::Display for ResolvedIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
@@ -1,19 +1,20 @@
+use crate::{creds::ServerState, ResolvedIdentity, TlsSummary};
 use futures::prelude::*;
 use linkerd_identity::{LocalId, Name};
 use linkerd_io as io;
 use linkerd_stack::{Param, Service};
 use linkerd_tls::{ClientId, NegotiatedProtocol, NegotiatedProtocolRef, ServerTls};
-use std::{convert::TryFrom, pin::Pin, sync::Arc, task::Context};
+use std::{pin::Pin, sync::Arc, task::Context};
 use thiserror::Error;
 use tokio::sync::watch;
-use tokio_rustls::rustls::{Certificate, ServerConfig};
+use tokio_rustls::rustls::{ServerConfig, ServerConnection};
 use tracing::debug;
 
 /// A Service that terminates TLS connections using a dynamically updated server configuration.
 #[derive(Clone)]
 pub struct Server {
     name: Name,
-    rx: watch::Receiver<Arc<ServerConfig>>,
+    rx: watch::Receiver<ServerState>,
 }
 
 pub type TerminateFuture<I> = futures::future::MapOk<
@@ -22,20 +23,20 @@ pub type TerminateFuture<I> = futures::future::MapOk<
 >;
 
 #[derive(Debug)]
-pub struct ServerIo<I>(tokio_rustls::server::TlsStream<I>);
+pub struct ServerIo<I>(tokio_rustls::server::TlsStream<I>, Option<ResolvedIdentity>);
 
 #[derive(Debug, Error)]
 #[error("credential store lost")]
 pub struct LostStore(());
 
 impl Server {
-    pub(crate) fn new(name: Name, rx: watch::Receiver<Arc<ServerConfig>>) -> Self {
+    pub(crate) fn new(name: Name, rx: watch::Receiver<ServerState>) -> Self {
         Self { name, rx }
     }
 
-    #[cfg(test)]
+    #[cfg(any(test, feature = "test-util"))]
     pub(crate) fn config(&self) -> Arc<ServerConfig> {
-        (*self.rx.borrow()).clone()
+        self.rx.borrow().config.clone()
     }
 
     /// Spawns a background task that watches for TLS configuration updates and creates an augmented
@@ -48,9 +49,15 @@ impl Server {
 
         let mut orig_rx = self.rx;
 
-        let mut c = (**orig_rx.borrow_and_update()).clone();
-        c.alpn_protocols = alpn_protocols.clone();
-        let (tx, rx) = watch::channel(c.into());
+        let with_alpn = move |state: &ServerState| {
+            let mut c = (*state.config).clone();
+            c.alpn_protocols = alpn_protocols.clone();
+            ServerState {
+                config: c.into(),
+                leaf: state.leaf,
+            }
+        };
+        let (tx, rx) = watch::channel(with_alpn(&orig_rx.borrow_and_update()));
 
         // Spawn a background task that watches the optional server configuration and publishes it
         // as a reliable channel, including any ALPN overrides.
@@ -72,9 +79,8 @@ impl Server {
                     }
                 }
 
-                let mut c = (*orig_rx.borrow().clone()).clone();
-                c.alpn_protocols = alpn_protocols.clone();
-                let _ = tx.send(c.into());
+                let state = with_alpn(&orig_rx.borrow());
+                let _ = tx.send(state);
             }
         });
 
@@ -103,11 +109,12 @@ where
 
     #[inline]
     fn call(&mut self, io: I) -> Self::Future {
-        tokio_rustls::TlsAcceptor::from((*self.rx.borrow()).clone())
+        tokio_rustls::TlsAcceptor::from(self.rx.borrow().config.clone())
             .accept(io)
             .map_ok(|io| {
                 // Determine the peer's identity, if it exist.
-                let client_id = client_identity(&io);
+                let resolved = ResolvedIdentity::from_peer_certs(io.get_ref().1.peer_certificates());
+                let client_id = resolved.clone().map(|id| ClientId(id.into()));
 
                 let negotiated_protocol = io
                     .get_ref()
@@ -120,23 +127,19 @@ where
                     client_id,
                     negotiated_protocol,
                 };
-                (tls, ServerIo(io))
+                (tls, ServerIo(io, resolved))
             })
     }
 }
 
-fn client_identity<I>(tls: &tokio_rustls::server::TlsStream<I>) -> Option<ClientId> {
-    let (_io, session) = tls.get_ref();
-    let certs = session.peer_certificates()?;
-    let c = certs.first().map(Certificate::as_ref)?;
-    let end_cert = webpki::EndEntityCert::try_from(c).ok()?;
-    let name: &str = end_cert.dns_names().ok()?.next().map(Into::into)?;
-    if name == "*" {
-        // Wildcards can perhaps be handled in a future path...
-        return None;
-    }
-
-    name.parse().ok().map(ClientId)
+/// Returns the identity of the client on a server connection, as verified
+/// during the handshake, or `None` if the client is anonymous (or the
+/// handshake hasn't completed).
+///
+/// This is intended for connections accepted without `Server`; `ServerIo`
+/// exposes the same identity as `ServerIo::client_identity`.
+pub fn client_identity(conn: &ServerConnection) -> Option<Name> {
+    ResolvedIdentity::from_peer_certs(conn.peer_certificates()).map(Into::into)
 }
 
 // === impl ServerIo ===
@@ -184,6 +187,12 @@ impl<I: io::AsyncRead + io::AsyncWrite + Unpin> io::AsyncWrite for ServerIo<I> {
 }
 
 impl<I> ServerIo<I> {
+    /// Returns the client's identity, as verified during the handshake.
+    #[inline]
+    pub fn client_identity(&self) -> Option<&ResolvedIdentity> {
+        self.1.as_ref()
+    }
+
     #[inline]
     pub fn negotiated_protocol(&self) -> Option<NegotiatedProtocolRef<'_>> {
         self.0
@@ -192,6 +201,11 @@ impl<I> ServerIo<I> {
             .alpn_protocol()
             .map(NegotiatedProtocolRef)
     }
+
+    /// Summarizes what the handshake negotiated.
+    pub fn tls_summary(&self) -> TlsSummary {
+        TlsSummary::new(self.0.get_ref().1)
+    }
 }
 
 impl<I: io::PeerAddr> io::PeerAddr for ServerIo<I> {
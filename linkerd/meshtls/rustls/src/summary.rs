@@ -0,0 +1,46 @@
+use crate::ResolvedIdentity;
+use tokio_rustls::rustls::{self, CommonState};
+
+/// Summarizes what a TLS handshake negotiated, e.g. for logging once a
+/// connection is established.
+///
+/// Fields that haven't been negotiated (because the handshake is incomplete,
+/// or the peer offered no ALPN protocols) are `None`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TlsSummary {
+    /// The negotiated protocol version.
+    pub protocol_version: Option<rustls::ProtocolVersion>,
+
+    /// The negotiated cipher suite.
+    pub cipher_suite: Option<rustls::CipherSuite>,
+
+    /// The negotiated ALPN protocol, if the peers agreed on one.
+    pub alpn_protocol: Option<Vec<u8>>,
+
+    /// The peer's identity, if it presented a certificate that names one.
+    pub peer_identity: Option<ResolvedIdentity>,
+
+    /// Whether the peer presented a certificate, which was verified during the
+    /// handshake.
+    ///
+    /// Servers always do, so this is only unset for anonymous clients. A peer
+    /// may be authenticated without an identity, e.g. if its certificate only
+    /// names a wildcard.
+    pub peer_authenticated: bool,
+}
+
+// === impl TlsSummary ===
+
+impl TlsSummary {
+    /// Summarizes a client or server connection's handshake.
+    pub fn new(conn: &CommonState) -> Self {
+        let peer_certs = conn.peer_certificates().filter(|certs| !certs.is_empty());
+        Self {
+            protocol_version: conn.protocol_version(),
+            cipher_suite: conn.negotiated_cipher_suite().map(|s| s.suite()),
+            alpn_protocol: conn.alpn_protocol().map(<[u8]>::to_vec),
+            peer_identity: ResolvedIdentity::from_peer_certs(peer_certs),
+            peer_authenticated: peer_certs.is_some(),
+        }
+    }
+}
@@ -0,0 +1,202 @@
+#!/bin/sh
+#
+# Generates certificates used by this crate's tests. Leaf certificates are
+# issued for the keys in `linkerd-tls-test-util` so that they may be paired
+# with that crate's fixtures.
+#
+# Requires OpenSSL 3.4+.
+#
+set -euox pipefail
+
+util=../../../../tls/test-util/src/testdata
+
+ca() {
+  name=$1
+  ext=$2
+  validity=${3:--days 3650}
+
+  openssl ecparam -name prime256v1 -genkey -noout -out "${name}-key.pem"
+  printf "[req]\ndistinguished_name=dn\n[dn]\n[ext]\n${ext}" > ca.cnf
+  openssl req -x509 -new -key "${name}-key.pem" -subj "/OU=${name}" \
+    -config ca.cnf -extensions ext ${validity} -out "${name}.pem"
+  rm ca.cnf
+}
+
+intermediate() {
+  parent=$1
+  name=$2
+  ext=${3:-$ca_ext}
+
+  openssl ecparam -name prime256v1 -genkey -noout -out "${name}-key.pem"
+  printf "[req]\ndistinguished_name=dn\n[dn]\n" > req.cnf
+  openssl req -new -key "${name}-key.pem" -subj "/OU=${name}" -config req.cnf \
+    -out "${name}.csr"
+  printf "$ext" > ext.cnf
+  openssl x509 -req -in "${name}.csr" -CA "${parent}.pem" -CAkey "${parent}-key.pem" \
+    -days 3650 -extfile ext.cnf -out "${name}.pem"
+  rm req.cnf ext.cnf "${name}.csr"
+}
+
+leaf() {
+  ca=$1
+  csr=$2
+  out=$3
+  ext=$4
+  digest=${5:--sha256}
+
+  printf "$ext" > ext.cnf
+  openssl x509 -req -in "${csr}" -CA "${ca}.pem" -CAkey "${ca}-key.pem" \
+    -days 3650 ${digest} -extfile ext.cnf -outform der -out "${out}"
+  rm ext.cnf
+}
+
+foo_csr="${util}/foo-ns1-ca1/csr.pem"
+ca_ext='basicConstraints=critical,CA:TRUE\nkeyUsage=critical,keyCertSign,cRLSign\nsubjectKeyIdentifier=hash\n'
+leaf_ext='keyUsage=critical,digitalSignature,keyEncipherment\nextendedKeyUsage=serverAuth,clientAuth\nbasicConstraints=critical,CA:FALSE\n'
+
+san() {
+  printf 'subjectAltName=critical,DNS:%s.serviceaccount.identity.linkerd.cluster.local\n' "$1"
+}
+
+# A leaf without Subject or Authority Key Identifiers.
+leaf "${util}/ca1" "${foo_csr}" foo-ns1-ca1-no-key-ids.der \
+  "$(san foo.ns1)\n${leaf_ext}subjectKeyIdentifier=none\nauthorityKeyIdentifier=none\n"
+
+# A CA that may only issue certificates for names in the `ns1` namespace.
+ca ns1-ca "${ca_ext}nameConstraints=critical,permitted;DNS:ns1.serviceaccount.identity.linkerd.cluster.local\n"
+leaf ns1-ca "${foo_csr}" foo-ns1-ns1-ca.der "$(san foo.ns1)\n${leaf_ext}"
+leaf ns1-ca "${foo_csr}" foo-ns2-ns1-ca.der "$(san foo.ns2)\n${leaf_ext}"
+
+# A root that expires before those in `linkerd-tls-test-util`.
+ca early-root "${ca_ext}" "-not_after 20300101000000Z"
+rm early-root-key.pem
+
+# Intermediates issued by ca1, only one of which issues a leaf.
+intermediate "${util}/ca1" int-a
+intermediate "${util}/ca1" int-b
+leaf int-b "${foo_csr}" foo-ns1-int-b.der "$(san foo.ns1)\n${leaf_ext}"
+rm int-a-key.pem int-b-key.pem
+
+# The CSR for foo.ns1 (as DER and PEM), and a leaf issued for it that names an
+# additional SAN.
+openssl req -in "${foo_csr}" -outform der -out foo-ns1-csr.der
+cp "${foo_csr}" foo-ns1-csr.pem
+leaf "${util}/ca1" "${foo_csr}" foo-ns1-extra-san.der \
+  "$(san foo.ns1),DNS:bar.ns1.serviceaccount.identity.linkerd.cluster.local\n${leaf_ext}"
+
+# Leaves for other identities in ns1 that are issued for foo.ns1's key, so that
+# they may be served alongside foo.ns1's.
+for name in bar baz qux; do
+  leaf "${util}/ca1" "${foo_csr}" "foo-key-${name}-ns1.der" "$(san ${name}.ns1)\n${leaf_ext}"
+done
+
+# A leaf that names an OCSP responder.
+leaf "${util}/ca1" "${foo_csr}" foo-ns1-ocsp.der \
+  "$(san foo.ns1)\n${leaf_ext}authorityInfoAccess=OCSP;URI:http://ocsp.example.com\n"
+
+# An intermediate that may not issue other CAs, a CA that it issued anyway, and
+# leaves issued by each.
+intermediate "${util}/ca1" int-pathlen0 \
+  "basicConstraints=critical,CA:TRUE,pathlen:0\nkeyUsage=critical,keyCertSign,cRLSign\nsubjectKeyIdentifier=hash\n"
+intermediate int-pathlen0 int-pathlen0-sub
+leaf int-pathlen0 "${foo_csr}" foo-ns1-int-pathlen0.der "$(san foo.ns1)\n${leaf_ext}"
+leaf int-pathlen0-sub "${foo_csr}" foo-ns1-int-pathlen0-sub.der "$(san foo.ns1)\n${leaf_ext}"
+rm int-pathlen0-key.pem int-pathlen0-sub-key.pem
+
+# Leaves for foo.ns1 in other trust domains.
+leaf "${util}/ca1" "${foo_csr}" foo-ns1-old-domain.der \
+  "subjectAltName=critical,DNS:foo.ns1.serviceaccount.identity.linkerd.old.example\n${leaf_ext}"
+leaf "${util}/ca1" "${foo_csr}" foo-ns1-unknown-domain.der \
+  "subjectAltName=critical,DNS:foo.ns1.serviceaccount.identity.linkerd.unknown.example\n${leaf_ext}"
+
+# Leaves for foo.ns1 that may only be used for server and client
+# authentication, respectively.
+auth_ext='keyUsage=critical,digitalSignature,keyEncipherment\nbasicConstraints=critical,CA:FALSE\n'
+leaf "${util}/ca1" "${foo_csr}" foo-ns1-server-auth.der \
+  "$(san foo.ns1)\n${auth_ext}extendedKeyUsage=serverAuth\n"
+leaf "${util}/ca1" "${foo_csr}" foo-ns1-client-auth.der \
+  "$(san foo.ns1)\n${auth_ext}extendedKeyUsage=clientAuth\n"
+
+# Keys of other algorithms for foo.ns1, and CSRs and leaves for them.
+other_key() {
+  name=$1
+  shift
+
+  openssl genpkey "$@" -out "foo-ns1-${name}-key.pem"
+  openssl pkcs8 -topk8 -nocrypt -in "foo-ns1-${name}-key.pem" -outform der \
+    -out "foo-ns1-${name}-key.p8"
+  printf "[req]\ndistinguished_name=dn\n[dn]\n" > req.cnf
+  openssl req -new -key "foo-ns1-${name}-key.pem" \
+    -subj "/CN=foo.ns1.serviceaccount.identity.linkerd.cluster.local" \
+    -config req.cnf -out "foo-ns1-${name}.csr"
+  leaf "${util}/ca1" "foo-ns1-${name}.csr" "foo-ns1-${name}.der" "$(san foo.ns1)\n${leaf_ext}"
+  openssl req -in "foo-ns1-${name}.csr" -outform der -out "foo-ns1-${name}-csr.der"
+  rm req.cnf "foo-ns1-${name}.csr" "foo-ns1-${name}-key.pem"
+}
+other_key rsa -algorithm RSA -pkeyopt rsa_keygen_bits:2048
+other_key p384 -algorithm EC -pkeyopt ec_paramgen_curve:P-384
+
+# A key of an algorithm that isn't supported.
+openssl genpkey -algorithm EC -pkeyopt ec_paramgen_curve:P-521 | \
+  openssl pkcs8 -topk8 -nocrypt -outform der -out p521-key.p8
+
+# A leaf signed with ecdsa-with-SHA384, and self-signed certificates signed
+# with algorithms that webpki doesn't accept.
+leaf "${util}/ca1" "${foo_csr}" foo-ns1-sha384.der "$(san foo.ns1)\n${leaf_ext}" -sha384
+self_signed() {
+  out=$1
+  shift
+
+  printf "[req]\ndistinguished_name=dn\n[dn]\n" > req.cnf
+  openssl req -x509 -newkey rsa:2048 -nodes -keyout /dev/null -subj "/CN=${out}" \
+    -config req.cnf -days 3650 "$@" -outform der -out "${out}.der"
+  rm req.cnf
+}
+self_signed rsa-sha1 -sha1
+self_signed rsa-pss-sha384 -sha384 -sigopt rsa_padding_mode:pss
+
+# A leaf that names a wildcard covering foo.ns1 rather than foo.ns1 itself.
+leaf "${util}/ca1" "${foo_csr}" foo-ns1-wildcard.der \
+  "subjectAltName=critical,DNS:*.ns1.serviceaccount.identity.linkerd.cluster.local\n${leaf_ext}"
+
+# foo.ns1's key, encrypted with PBES2 (PBKDF2-HMAC-SHA256 and AES-256-CBC).
+openssl pkcs8 -topk8 -inform der -in "${util}/foo-ns1-ca1/key.p8" -v2 aes-256-cbc \
+  -v2prf hmacWithSHA256 -passout pass:correct-horse -outform der \
+  -out foo-ns1-key-encrypted.p8
+
+# foo.ns1's key as PKCS #8 and SEC 1 PEM, and the RSA key as PKCS #1 PEM.
+openssl pkcs8 -topk8 -nocrypt -inform der -in "${util}/foo-ns1-ca1/key.p8" \
+  -out foo-ns1-key.pem
+openssl ec -inform der -in "${util}/foo-ns1-ca1/key.p8" -out foo-ns1-key-sec1.pem
+openssl rsa -inform der -in foo-ns1-rsa-key.p8 -traditional -out foo-ns1-rsa-key-pkcs1.pem
+
+# OCSP responses, signed by ca1, that report foo.ns1's certificate (and the leaf
+# that names an OCSP responder) as good and as revoked.
+ocsp_response() {
+  status=$1
+  cert=$2
+  out=$3
+
+  openssl x509 -inform der -in "${cert}" -out foo.pem
+  serial=$(openssl x509 -in foo.pem -noout -serial | cut -d= -f2)
+  case "${status}" in
+    V) printf 'V\t491231235959Z\t\t%s\tunknown\t/\n' "${serial}" > index.txt ;;
+    R) printf 'R\t491231235959Z\t240101000000Z\t%s\tunknown\t/\n' "${serial}" > index.txt ;;
+  esac
+  openssl ocsp -issuer "${util}/ca1.pem" -cert foo.pem -no_nonce -reqout req.der
+  openssl ocsp -index index.txt -rsigner "${util}/ca1.pem" -rkey "${util}/ca1-key.pem" \
+    -CA "${util}/ca1.pem" -reqin req.der -ndays 3650 -respout "${out}"
+  rm foo.pem index.txt req.der
+}
+ocsp_response V "${util}/foo-ns1-ca1/crt.der" foo-ns1-ocsp-good.der
+ocsp_response R "${util}/foo-ns1-ca1/crt.der" foo-ns1-ocsp-revoked.der
+ocsp_response V foo-ns1-ocsp.der foo-ns1-ocsp-leaf-good.der
+ocsp_response R foo-ns1-ocsp.der foo-ns1-ocsp-leaf-revoked.der
+
+# A leaf for foo.ns1 that isn't valid yet. (The test utilities provide one
+# that has expired.)
+printf "$(san foo.ns1)\n${leaf_ext}" > ext.cnf
+openssl x509 -req -in "${foo_csr}" -CA "${util}/ca1.pem" -CAkey "${util}/ca1-key.pem" \
+  -not_before 20400101000000Z -not_after 20450101000000Z -extfile ext.cnf \
+  -outform der -out foo-ns1-not-yet-valid.der
+rm ext.cnf
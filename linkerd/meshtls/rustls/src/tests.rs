@@ -1,17 +1,311 @@
+use crate::creds::{
+    CipherSuite, ClientAuth, ClientHelloRecord, CredentialsBuilder, CsrSanMismatch, CsrSanPolicy,
+    CsrTemplate, IdentityLimit, IdentityMismatch, Install, InstallRecord, KeyIdentifierPolicy,
+    Metrics, Options, Readiness, Receiver, ResolutionFailures, RotationInfo, SignatureAlgorithm,
+    Store, TrustRootsInfo, WatchError,
+};
+use crate::TlsSummary;
 use linkerd_identity::{Credentials, DerX509};
+use linkerd_metrics::FmtMetrics;
 use linkerd_tls_test_util::*;
-use std::time::Duration;
+use std::{
+    convert::TryFrom,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio_rustls::rustls;
 
-fn load(ent: &Entity) -> crate::creds::Store {
+static FOO_NS1_NO_KEY_IDS: &[u8] = include_bytes!("testdata/foo-ns1-ca1-no-key-ids.der");
+
+/// A CA whose name constraints only permit names in the `ns1` namespace.
+static NS1_CA: &[u8] = include_bytes!("testdata/ns1-ca.pem");
+static FOO_NS1_NS1_CA: &[u8] = include_bytes!("testdata/foo-ns1-ns1-ca.der");
+static FOO_NS2_NS1_CA: &[u8] = include_bytes!("testdata/foo-ns2-ns1-ca.der");
+
+/// A root that expires at 2030-01-01T00:00:00Z, before those in `linkerd-tls-test-util`.
+static EARLY_ROOT: &[u8] = include_bytes!("testdata/early-root.pem");
+
+/// Intermediates issued by ca1 and a leaf issued by `INT_B`.
+static INT_A: &[u8] = include_bytes!("testdata/int-a.pem");
+static INT_B: &[u8] = include_bytes!("testdata/int-b.pem");
+static FOO_NS1_INT_B: &[u8] = include_bytes!("testdata/foo-ns1-int-b.der");
+
+/// An intermediate issued by ca1 with a path length of 0, an intermediate CA
+/// that it issued anyway, and leaves issued by each.
+static INT_PATHLEN0: &[u8] = include_bytes!("testdata/int-pathlen0.pem");
+static INT_PATHLEN0_SUB: &[u8] = include_bytes!("testdata/int-pathlen0-sub.pem");
+static FOO_NS1_INT_PATHLEN0: &[u8] = include_bytes!("testdata/foo-ns1-int-pathlen0.der");
+static FOO_NS1_INT_PATHLEN0_SUB: &[u8] = include_bytes!("testdata/foo-ns1-int-pathlen0-sub.der");
+
+/// Certificates for `FOO_NS1`'s identity in the `old.example` and
+/// `unknown.example` trust domains.
+static FOO_NS1_OLD_DOMAIN: &[u8] = include_bytes!("testdata/foo-ns1-old-domain.der");
+static FOO_NS1_UNKNOWN_DOMAIN: &[u8] = include_bytes!("testdata/foo-ns1-unknown-domain.der");
+
+/// The CSR for `FOO_NS1`, and a certificate issued for it that names an
+/// additional SAN.
+static FOO_NS1_CSR: &[u8] = include_bytes!("testdata/foo-ns1-csr.der");
+static FOO_NS1_CSR_PEM: &[u8] = include_bytes!("testdata/foo-ns1-csr.pem");
+static FOO_NS1_EXTRA_SAN: &[u8] = include_bytes!("testdata/foo-ns1-extra-san.der");
+
+/// Certificates for other identities in ns1 that are issued for `FOO_NS1`'s
+/// key, so that they may be served alongside its own.
+static FOO_KEY_BAR_NS1: &[u8] = include_bytes!("testdata/foo-key-bar-ns1.der");
+static FOO_KEY_BAZ_NS1: &[u8] = include_bytes!("testdata/foo-key-baz-ns1.der");
+static FOO_KEY_QUX_NS1: &[u8] = include_bytes!("testdata/foo-key-qux-ns1.der");
+
+/// Certificates for `FOO_NS1` that may only be used for server and client
+/// authentication, respectively.
+static FOO_NS1_SERVER_AUTH: &[u8] = include_bytes!("testdata/foo-ns1-server-auth.der");
+static FOO_NS1_CLIENT_AUTH: &[u8] = include_bytes!("testdata/foo-ns1-client-auth.der");
+
+/// A certificate for `FOO_NS1` that names an OCSP responder.
+static FOO_NS1_OCSP: &[u8] = include_bytes!("testdata/foo-ns1-ocsp.der");
+
+/// OCSP responses, signed by ca1, that report `FOO_NS1`'s certificate as good
+/// and as revoked.
+static FOO_NS1_OCSP_GOOD: &[u8] = include_bytes!("testdata/foo-ns1-ocsp-good.der");
+static FOO_NS1_OCSP_REVOKED: &[u8] = include_bytes!("testdata/foo-ns1-ocsp-revoked.der");
+
+/// OCSP responses, signed by ca1, that report `FOO_NS1_OCSP` as good and as
+/// revoked.
+static FOO_NS1_OCSP_LEAF_GOOD: &[u8] = include_bytes!("testdata/foo-ns1-ocsp-leaf-good.der");
+static FOO_NS1_OCSP_LEAF_REVOKED: &[u8] = include_bytes!("testdata/foo-ns1-ocsp-leaf-revoked.der");
+
+/// An RSA key for `FOO_NS1`'s identity, a CSR for it, and a certificate issued
+/// for it by ca1.
+static FOO_NS1_RSA_KEY: &[u8] = include_bytes!("testdata/foo-ns1-rsa-key.p8");
+static FOO_NS1_RSA_CSR: &[u8] = include_bytes!("testdata/foo-ns1-rsa-csr.der");
+static FOO_NS1_RSA: &[u8] = include_bytes!("testdata/foo-ns1-rsa.der");
+
+/// A P-384 key for `FOO_NS1`'s identity, a CSR for it, and a certificate issued
+/// for it by ca1.
+static FOO_NS1_P384_KEY: &[u8] = include_bytes!("testdata/foo-ns1-p384-key.p8");
+static FOO_NS1_P384_CSR: &[u8] = include_bytes!("testdata/foo-ns1-p384-csr.der");
+static FOO_NS1_P384: &[u8] = include_bytes!("testdata/foo-ns1-p384.der");
+
+/// A certificate for `FOO_NS1`'s key that names a wildcard covering its
+/// identity rather than the identity itself.
+static FOO_NS1_WILDCARD: &[u8] = include_bytes!("testdata/foo-ns1-wildcard.der");
+
+/// A certificate for `FOO_NS1` that isn't valid until 2040.
+static FOO_NS1_NOT_YET_VALID: &[u8] = include_bytes!("testdata/foo-ns1-not-yet-valid.der");
+
+/// A certificate for `FOO_NS1` that ca1 signed with ecdsa-with-SHA384.
+static FOO_NS1_SHA384: &[u8] = include_bytes!("testdata/foo-ns1-sha384.der");
+
+/// `FOO_NS1`'s key, encrypted with the password `correct-horse`.
+static FOO_NS1_KEY_ENCRYPTED: &[u8] = include_bytes!("testdata/foo-ns1-key-encrypted.p8");
+
+/// `FOO_NS1`'s key as PKCS #8 and SEC 1 PEM, and `FOO_NS1_RSA_KEY` as PKCS #1
+/// PEM.
+static FOO_NS1_KEY_PEM: &[u8] = include_bytes!("testdata/foo-ns1-key.pem");
+static FOO_NS1_KEY_SEC1_PEM: &[u8] = include_bytes!("testdata/foo-ns1-key-sec1.pem");
+static FOO_NS1_RSA_KEY_PKCS1_PEM: &[u8] = include_bytes!("testdata/foo-ns1-rsa-key-pkcs1.pem");
+
+/// A P-521 key, which ring doesn't support.
+static P521_KEY: &[u8] = include_bytes!("testdata/p521-key.p8");
+
+fn load(ent: &Entity) -> Store {
+    load_with_options(ent, Options::default())
+}
+
+fn load_with_options(ent: &Entity, options: Options) -> Store {
+    watch(ent, options).0
+}
+
+fn watch(ent: &Entity, options: Options) -> (Store, Receiver) {
     let roots_pem = std::str::from_utf8(ent.trust_anchors).expect("valid PEM");
-    let (store, _) = crate::creds::watch(
+    crate::creds::watch_with_options(
         ent.name.parse().unwrap(),
         roots_pem,
         ent.key,
-        b"fake CSR data",
+        ent.csr,
+        options,
     )
-    .expect("credentials must be readable");
-    store
+    .expect("credentials must be readable")
+}
+
+fn watch_raw(name: &str, roots_pem: &[u8], key: &[u8], options: Options) -> (Store, Receiver) {
+    let roots_pem = std::str::from_utf8(roots_pem).expect("valid PEM");
+    crate::creds::watch_with_options(name.parse().unwrap(), roots_pem, key, csr_for(key), options)
+        .expect("credentials must be readable")
+}
+
+/// Returns a CSR for `key`, which must be accepted by `watch`: other keys are
+/// assumed to be `FOO_NS1`'s, in some other encoding.
+fn csr_for(key: &[u8]) -> &'static [u8] {
+    let ents = [&BAR_NS1, &DEFAULT_DEFAULT, &FOO_NS1_CA2, &FOO_NS1_ED25519];
+    if let Some(ent) = ents.iter().find(|ent| ent.key == key) {
+        return ent.csr;
+    }
+    if key == FOO_NS1_RSA_KEY || key == FOO_NS1_RSA_KEY_PKCS1_PEM {
+        return FOO_NS1_RSA_CSR;
+    }
+    if key == FOO_NS1_P384_KEY {
+        return FOO_NS1_P384_CSR;
+    }
+    FOO_NS1_CSR
+}
+
+fn pem_to_der(pem: &[u8]) -> Vec<u8> {
+    let mut certs = rustls_pemfile::certs(&mut std::io::Cursor::new(pem)).expect("valid PEM");
+    certs.remove(0)
+}
+
+fn expiry() -> std::time::SystemTime {
+    std::time::SystemTime::now() + Duration::from_secs(600)
+}
+
+/// Runs a TLS handshake between in-memory client and server connections,
+/// including any post-handshake messages (like session tickets).
+fn handshake(
+    client: Arc<rustls::ClientConfig>,
+    server_name: &str,
+    server: Arc<rustls::ServerConfig>,
+) -> Result<(rustls::Connection, rustls::Connection), rustls::Error> {
+    let server_name = rustls::ServerName::try_from(server_name).expect("name must be valid");
+    let mut client = rustls::Connection::from(rustls::ClientConnection::new(client, server_name)?);
+    let mut server = rustls::Connection::from(rustls::ServerConnection::new(server)?);
+
+    for _ in 0..10 {
+        transfer(&mut client, &mut server)?;
+        transfer(&mut server, &mut client)?;
+        if !client.is_handshaking() && !server.is_handshaking() && !client.wants_write() {
+            // Deliver anything the server sent after the handshake completed.
+            transfer(&mut server, &mut client)?;
+            return Ok((client, server));
+        }
+    }
+    panic!("handshake did not complete");
+}
+
+fn transfer(
+    from: &mut rustls::Connection,
+    to: &mut rustls::Connection,
+) -> Result<(), rustls::Error> {
+    let mut buf = Vec::new();
+    while from.wants_write() {
+        from.write_tls(&mut buf)
+            .expect("writes to a Vec must succeed");
+    }
+
+    let mut rd = &buf[..];
+    while !rd.is_empty() {
+        to.read_tls(&mut rd)
+            .expect("reads from a slice must succeed");
+        to.process_new_packets()?;
+    }
+    Ok(())
+}
+
+/// Returns the messages of the events at `level` that are recorded while `f`
+/// runs.
+/// Returns the name and fields of each span that is created while `f` runs,
+/// including fields that are recorded after the span is created.
+fn spans(f: impl FnOnce()) -> Vec<(&'static str, Vec<(&'static str, String)>)> {
+    type Spans = Arc<parking_lot::Mutex<Vec<(&'static str, Vec<(&'static str, String)>)>>>;
+
+    struct Collect(Spans);
+
+    struct Fields<'a>(&'a mut Vec<(&'static str, String)>);
+
+    impl tracing::Subscriber for Collect {
+        fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, attrs: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            let mut spans = self.0.lock();
+            let mut fields = Vec::new();
+            attrs.record(&mut Fields(&mut fields));
+            spans.push((attrs.metadata().name(), fields));
+            tracing::span::Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, id: &tracing::span::Id, values: &tracing::span::Record<'_>) {
+            let mut spans = self.0.lock();
+            let (_, ref mut fields) = spans[id.into_u64() as usize - 1];
+            values.record(&mut Fields(fields));
+        }
+
+        fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+        fn event(&self, _: &tracing::Event<'_>) {}
+
+        fn enter(&self, _: &tracing::span::Id) {}
+
+        fn exit(&self, _: &tracing::span::Id) {}
+    }
+
+    impl tracing::field::Visit for Fields<'_> {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            self.0.push((field.name(), format!("{:?}", value)));
+        }
+    }
+
+    let spans = Spans::default();
+    tracing::subscriber::with_default(Collect(spans.clone()), f);
+    let spans = spans.lock().clone();
+    spans
+}
+
+fn messages(level: tracing::Level, f: impl FnOnce()) -> Vec<String> {
+    struct Collect {
+        level: tracing::Level,
+        messages: Arc<parking_lot::Mutex<Vec<String>>>,
+    }
+
+    struct Message(String);
+
+    impl tracing::Subscriber for Collect {
+        fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, _: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            tracing::span::Id::from_u64(1)
+        }
+
+        fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+        fn event(&self, event: &tracing::Event<'_>) {
+            if *event.metadata().level() != self.level {
+                return;
+            }
+            let mut message = Message(String::new());
+            event.record(&mut message);
+            self.messages.lock().push(message.0);
+        }
+
+        fn enter(&self, _: &tracing::span::Id) {}
+
+        fn exit(&self, _: &tracing::span::Id) {}
+    }
+
+    impl tracing::field::Visit for Message {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            if field.name() == "message" {
+                self.0 = format!("{:?}", value);
+            }
+        }
+    }
+
+    let messages = Arc::new(parking_lot::Mutex::new(Vec::new()));
+    tracing::subscriber::with_default(
+        Collect {
+            level,
+            messages: messages.clone(),
+        },
+        f,
+    );
+    let messages = messages.lock().clone();
+    messages
 }
 
 #[test]
@@ -38,11 +332,4238 @@ fn recognize_ca_did_not_issue_cert() {
 
 #[test]
 fn recognize_cert_is_not_valid_for_identity() {
-    assert!(load(&BAR_NS1)
+    let err = load(&BAR_NS1)
         .set_certificate(
             DerX509(FOO_NS1.crt.to_vec()),
             vec![],
-            std::time::SystemTime::now() + Duration::from_secs(600)
+            std::time::SystemTime::now() + Duration::from_secs(600),
+        )
+        .expect_err("certificate must be rejected");
+    assert!(err.is::<IdentityMismatch>(), "{}", err);
+}
+
+#[test]
+fn recognize_wildcard_cert_is_not_issued_for_identity() {
+    // The wildcard covers our name, so the certificate would validate for it,
+    // but it wasn't issued for our identity specifically.
+    let err = load(&FOO_NS1)
+        .set_certificate(DerX509(FOO_NS1_WILDCARD.to_vec()), vec![], expiry())
+        .expect_err("certificate must be rejected");
+    assert!(err.is::<IdentityMismatch>(), "{}", err);
+    assert!(
+        err.to_string()
+            .contains("*.ns1.serviceaccount.identity.linkerd.cluster.local"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn key_identifiers_are_only_required_when_configured() {
+    let expiry = expiry();
+    assert!(load(&FOO_NS1)
+        .set_certificate(DerX509(FOO_NS1_NO_KEY_IDS.to_vec()), vec![], expiry)
+        .is_ok());
+
+    let warn = Options {
+        key_identifiers: KeyIdentifierPolicy::Warn,
+        ..Options::default()
+    };
+    assert!(load_with_options(&FOO_NS1, warn)
+        .set_certificate(DerX509(FOO_NS1_NO_KEY_IDS.to_vec()), vec![], expiry)
+        .is_ok());
+
+    let require = Options {
+        key_identifiers: KeyIdentifierPolicy::Require,
+        ..Options::default()
+    };
+    assert!(load_with_options(&FOO_NS1, require.clone())
+        .set_certificate(DerX509(FOO_NS1_NO_KEY_IDS.to_vec()), vec![], expiry)
+        .is_err());
+    assert!(load_with_options(&FOO_NS1, require)
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry)
+        .is_ok());
+}
+
+#[test]
+fn client_config_without_client_auth_presents_no_cert() {
+    let (mut store, rx) = watch(&FOO_NS1, Options::default());
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+
+    let config = rx.client_config_without_client_auth();
+    let resolver = &config.client_auth_cert_resolver;
+    assert!(!resolver.has_certs());
+    assert!(resolver
+        .resolve(&[], &[rustls::SignatureScheme::ECDSA_NISTP256_SHA256])
+        .is_none());
+}
+
+#[test]
+fn handshakes_are_summarized() {
+    let protocols = vec![b"h2".to_vec()];
+    let (mut foo, foo_rx) = watch(
+        &FOO_NS1,
+        Options {
+            alpn_protocols: protocols.clone(),
+            ..Options::default()
+        },
+    );
+    foo.set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    let (mut bar, bar_rx) = watch(&BAR_NS1, Options::default());
+    bar.set_certificate(DerX509(BAR_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+
+    let (client, server) = handshake(foo_rx.client_config(), FOO_NS1.name, foo_rx.server_config())
+        .expect("handshake must succeed");
+    let summary = TlsSummary::new(&client);
+    assert_eq!(
+        summary.protocol_version,
+        Some(rustls::ProtocolVersion::TLSv1_3)
+    );
+    assert_eq!(
+        summary.cipher_suite,
+        Some(rustls::CipherSuite::TLS13_CHACHA20_POLY1305_SHA256)
+    );
+    assert_eq!(summary.alpn_protocol, Some(b"h2".to_vec()));
+    assert_eq!(
+        summary.peer_identity.as_ref().map(|id| id.to_string()),
+        Some(FOO_NS1.name.to_string())
+    );
+    assert!(summary.peer_authenticated);
+    assert_eq!(TlsSummary::new(&server), summary);
+
+    // Anonymous clients and connections without ALPN are represented.
+    let (client, server) = handshake(
+        bar_rx.client_config_without_client_auth(),
+        FOO_NS1.name,
+        foo_rx.server().config(),
+    )
+    .expect("handshake must succeed");
+    let summary = TlsSummary::new(&server);
+    assert_eq!(summary.alpn_protocol, None);
+    assert_eq!(summary.peer_identity, None);
+    assert!(!summary.peer_authenticated);
+    let summary = TlsSummary::new(&client);
+    assert_eq!(summary.alpn_protocol, None);
+    assert_eq!(
+        summary.peer_identity.as_ref().map(|id| id.to_string()),
+        Some(FOO_NS1.name.to_string())
+    );
+    assert!(summary.peer_authenticated);
+}
+
+#[test]
+fn servers_may_require_client_certificates() {
+    let (mut client, client_rx) = watch(&BAR_NS1, Options::default());
+    client
+        .set_certificate(DerX509(BAR_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+
+    for (client_auth, anonymous_ok) in [(ClientAuth::Optional, true), (ClientAuth::Required, false)]
+    {
+        let options = Options {
+            client_auth,
+            ..Options::default()
+        };
+        let (mut server, server_rx) = watch(&FOO_NS1, options);
+        server
+            .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+            .expect("certificate must be valid");
+
+        let res = handshake(
+            client_rx.client_config_without_client_auth(),
+            FOO_NS1.name,
+            server_rx.server().config(),
+        );
+        assert_eq!(res.is_ok(), anonymous_ok, "{:?}", res.err());
+
+        // Authenticated clients are always accepted.
+        let (_, server) = handshake(
+            client_rx.new_client().config(),
+            FOO_NS1.name,
+            server_rx.server().config(),
+        )
+        .expect("handshake must succeed");
+        assert!(server.peer_certificates().is_some());
+    }
+}
+
+#[test]
+fn unusable_trust_roots_are_distinguished() {
+    let watch = |roots_pem: &str| {
+        crate::creds::watch(
+            FOO_NS1.name.parse().unwrap(),
+            roots_pem,
+            FOO_NS1.key,
+            FOO_NS1_CSR,
+        )
+        .err()
+        .expect("roots must be rejected")
+    };
+
+    let err = watch("");
+    assert!(
+        matches!(
+            err.downcast_ref::<WatchError>(),
+            Some(WatchError::NoTrustRoots)
+        ),
+        "{}",
+        err
+    );
+
+    let err = watch("-----BEGIN CERTIFICATE-----\n!!!!\n-----END CERTIFICATE-----\n");
+    assert!(
+        matches!(
+            err.downcast_ref::<WatchError>(),
+            Some(WatchError::PemParse(_))
+        ),
+        "{}",
+        err
+    );
+
+    let err = watch(
+        "-----BEGIN CERTIFICATE-----\nAAAA\n-----END CERTIFICATE-----\n\
+         -----BEGIN CERTIFICATE-----\nAAAA\n-----END CERTIFICATE-----\n",
+    );
+    assert!(
+        matches!(
+            err.downcast_ref::<WatchError>(),
+            Some(WatchError::AllAnchorsSkipped { skipped: 2 })
+        ),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn roots_earliest_expiry_is_reported() {
+    // 2031-10-06T19:24:00Z
+    let ca1_expiry = std::time::UNIX_EPOCH + Duration::from_secs(1_949_081_040);
+    let (store, _) = watch(&FOO_NS1, Options::default());
+    assert_eq!(store.roots_earliest_expiry(), Some(ca1_expiry));
+
+    let metrics = Metrics::default();
+    let roots = [FOO_NS1.trust_anchors, EARLY_ROOT].concat();
+    let (store, _) = watch_raw(
+        FOO_NS1.name,
+        &roots,
+        FOO_NS1.key,
+        Options {
+            metrics: metrics.clone(),
+            ..Options::default()
+        },
+    );
+    assert_eq!(
+        store.roots_earliest_expiry(),
+        Some(std::time::UNIX_EPOCH + Duration::from_secs(1_893_456_000))
+    );
+    let text = metrics.as_display().to_string();
+    assert!(
+        text.contains("identity_roots_expiration_timestamp_seconds 1893456000\n"),
+        "{}",
+        text
+    );
+}
+
+#[test]
+fn trusted_roots_describe_the_loaded_roots() {
+    let sha256 = |pem: &[u8]| {
+        let digest = ring::digest::digest(&ring::digest::SHA256, &pem_to_der(pem));
+        <[u8; 32]>::try_from(digest.as_ref()).unwrap()
+    };
+
+    let mut store = load(&FOO_NS1);
+    let roots = store.trusted_roots();
+    assert_eq!(roots.len(), 1);
+    assert_eq!(roots[0].subject, "OU=None");
+    assert_eq!(roots[0].sha256, sha256(FOO_NS1.trust_anchors));
+    assert_eq!(
+        roots[0].to_string(),
+        format!("OU=None, fp={}", hex::encode(roots[0].sha256))
+    );
+
+    let early = std::str::from_utf8(EARLY_ROOT).unwrap();
+    let ca1 = std::str::from_utf8(FOO_NS1.trust_anchors).unwrap();
+    store
+        .set_trust_anchors(&[early, ca1].concat())
+        .expect("roots must be valid");
+    let roots = store.trusted_roots();
+    assert_eq!(
+        roots
+            .iter()
+            .map(|r| (r.subject.as_str(), r.sha256))
+            .collect::<Vec<_>>(),
+        vec![
+            ("OU=early-root", sha256(EARLY_ROOT)),
+            ("OU=None", sha256(FOO_NS1.trust_anchors)),
+        ]
+    );
+}
+
+#[test]
+fn trust_roots_are_counted() {
+    let invalid = "-----BEGIN CERTIFICATE-----\nAAAA\n-----END CERTIFICATE-----\n";
+    let ca1 = std::str::from_utf8(FOO_NS1.trust_anchors).unwrap();
+    let early = std::str::from_utf8(EARLY_ROOT).unwrap();
+
+    let store = load(&FOO_NS1);
+    assert_eq!(
+        store.trust_roots_info(),
+        TrustRootsInfo {
+            added: 1,
+            skipped: 0,
+            duplicates: 0,
+            expired: 0,
+        }
+    );
+
+    let (mut store, _rx) = watch_raw(
+        FOO_NS1.name,
+        [invalid, ca1, invalid, early].concat().as_bytes(),
+        FOO_NS1.key,
+        Options::default(),
+    );
+    assert_eq!(
+        store.trust_roots_info(),
+        TrustRootsInfo {
+            added: 2,
+            skipped: 2,
+            duplicates: 0,
+            expired: 0,
+        }
+    );
+
+    // Counts are replaced when the roots are reloaded.
+    store
+        .set_trust_anchors(&[early, invalid].concat())
+        .expect("roots must be valid");
+    assert_eq!(
+        store.trust_roots_info(),
+        TrustRootsInfo {
+            added: 1,
+            skipped: 1,
+            duplicates: 0,
+            expired: 0,
+        }
+    );
+}
+
+#[test]
+fn duplicate_and_expired_trust_roots_are_counted() {
+    let ca1 = std::str::from_utf8(FOO_NS1.trust_anchors).unwrap();
+    let early = std::str::from_utf8(EARLY_ROOT).unwrap();
+
+    // 2031-01-01T00:00:00Z, after the early root expired but before ca1 did.
+    let now = std::time::UNIX_EPOCH + Duration::from_secs(1_924_992_000);
+    let (mut store, _rx) = crate::creds::watch_with_bundles(
+        FOO_NS1.name.parse().unwrap(),
+        &[&[ca1, early, ca1].concat(), ca1],
+        FOO_NS1.key,
+        FOO_NS1_CSR,
+        Options {
+            clock: Some(Arc::new(move || now)),
+            ..Options::default()
+        },
+    )
+    .expect("credentials must be readable");
+    assert_eq!(
+        store.trust_roots_info(),
+        TrustRootsInfo {
+            added: 2,
+            skipped: 0,
+            duplicates: 2,
+            expired: 1,
+        }
+    );
+    assert_eq!(store.trusted_roots().len(), 2);
+
+    // Expired roots are still trusted.
+    store
+        .set_trust_anchors(early)
+        .expect("expired roots must be added");
+    assert_eq!(
+        store.trust_roots_info(),
+        TrustRootsInfo {
+            added: 1,
+            skipped: 0,
+            duplicates: 0,
+            expired: 1,
+        }
+    );
+}
+
+#[test]
+fn separate_certificates_may_be_presented_as_client_and_server() {
+    let (mut store, rx) = watch(&FOO_NS1, Options::default());
+
+    // A certificate that may only authenticate clients can't be served.
+    assert!(store
+        .set_certificate(DerX509(FOO_NS1_CLIENT_AUTH.to_vec()), vec![], expiry())
+        .is_err());
+    assert!(store
+        .set_certificates(
+            DerX509(FOO_NS1_CLIENT_AUTH.to_vec()),
+            vec![],
+            DerX509(FOO_NS1_SERVER_AUTH.to_vec()),
+            vec![],
+            expiry(),
+        )
+        .is_err());
+
+    store
+        .set_certificates(
+            DerX509(FOO_NS1_SERVER_AUTH.to_vec()),
+            vec![],
+            DerX509(FOO_NS1_CLIENT_AUTH.to_vec()),
+            vec![],
+            expiry(),
+        )
+        .expect("certificates must be valid");
+    let (client, server) = handshake(rx.client_config(), FOO_NS1.name, rx.server_config())
+        .expect("handshake must succeed");
+    assert_eq!(
+        client.peer_certificates().unwrap()[0].0,
+        FOO_NS1_SERVER_AUTH
+    );
+    assert_eq!(
+        server.peer_certificates().unwrap()[0].0,
+        FOO_NS1_CLIENT_AUTH
+    );
+
+    // By default, one certificate is presented in both roles.
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    let (client, server) = handshake(rx.client_config(), FOO_NS1.name, rx.server_config())
+        .expect("handshake must succeed");
+    assert_eq!(client.peer_certificates().unwrap()[0].0, FOO_NS1.crt);
+    assert_eq!(server.peer_certificates().unwrap()[0].0, FOO_NS1.crt);
+}
+
+#[test]
+fn trust_anchors_may_be_reloaded() {
+    let (mut store, rx) = watch(&FOO_NS1, Options::default());
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+
+    // A server whose certificate is issued by ca2.
+    let (mut ca2_store, ca2_rx) = watch(&FOO_NS1_CA2, Options::default());
+    ca2_store
+        .set_certificate(DerX509(FOO_NS1_CA2.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    let connect = |rx: &Receiver| {
+        handshake(
+            rx.client_config_without_client_auth(),
+            FOO_NS1.name,
+            ca2_rx.server().config(),
+        )
+    };
+    assert!(connect(&rx).is_err(), "ca2 must not be trusted");
+
+    // Bundles that can't be used are rejected, as are roots that don't
+    // validate the installed certificate.
+    let ca2 = std::str::from_utf8(FOO_NS1_CA2.trust_anchors).unwrap();
+    for pem in ["", "not PEM", ca2] {
+        assert!(store.set_trust_anchors(pem).is_err(), "{:?}", pem);
+    }
+    assert!(connect(&rx).is_err(), "ca2 must not be trusted");
+
+    let ca1 = std::str::from_utf8(FOO_NS1.trust_anchors).unwrap();
+    store
+        .set_trust_anchors(&[ca1, ca2].concat())
+        .expect("roots must be valid");
+    connect(&rx).expect("ca2 must be trusted");
+    handshake(rx.new_client().config(), FOO_NS1.name, rx.server().config())
+        .expect("the installed certificate must remain valid");
+}
+
+#[test]
+fn trust_anchors_may_be_rotated_with_the_certificate() {
+    let (mut store, rx) = watch(&FOO_NS1, Options::default());
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    let ca1_roots = store.trusted_roots();
+
+    // ns1-ca issues a certificate for foo's key, which doesn't chain to ca1,
+    // so neither the roots nor the certificate may be replaced on their own.
+    let ns1_ca = std::str::from_utf8(NS1_CA).unwrap();
+    let rotated = || DerX509(FOO_NS1_NS1_CA.to_vec());
+    assert!(store.set_trust_anchors(ns1_ca).is_err());
+    assert!(store.set_certificate(rotated(), vec![], expiry()).is_err());
+
+    // If the bundle can't be read, or the certificate doesn't chain to the new
+    // roots, nothing changes.
+    let server = rx.server_config();
+    for (pem, leaf) in [
+        ("not PEM", rotated()),
+        (ns1_ca, DerX509(FOO_NS1.crt.to_vec())),
+    ] {
+        let installed = store.rotate(pem, leaf, vec![], expiry());
+        assert!(
+            matches!(installed, Install::ValidationFailed(_)),
+            "{:?}",
+            installed
+        );
+        assert_eq!(store.trusted_roots(), ca1_roots);
+        assert!(Arc::ptr_eq(&rx.server_config(), &server));
+    }
+    handshake(rx.new_client().config(), FOO_NS1.name, rx.server().config())
+        .expect("the installed certificate must remain valid");
+
+    let installed = store.rotate(ns1_ca, rotated(), vec![], expiry());
+    assert!(matches!(installed, Install::Published), "{:?}", installed);
+    assert_ne!(store.trusted_roots(), ca1_roots);
+    handshake(rx.new_client().config(), FOO_NS1.name, rx.server().config())
+        .expect("the rotated certificate must chain to the rotated roots");
+
+    // Peers issued by ca1 are no longer trusted.
+    let (mut bar, bar_rx) = watch(&BAR_NS1, Options::default());
+    bar.set_certificate(DerX509(BAR_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    assert!(handshake(
+        rx.client_config_without_client_auth(),
+        BAR_NS1.name,
+        bar_rx.server().config(),
+    )
+    .is_err());
+}
+
+#[test]
+fn server_verifiers_may_be_injected() {
+    /// Verifies servers against ca1 and ca2, counting each verification.
+    struct Counting {
+        inner: rustls::client::WebPkiVerifier,
+        verified: AtomicUsize,
+    }
+
+    impl rustls::client::ServerCertVerifier for Counting {
+        fn verify_server_cert(
+            &self,
+            end_entity: &rustls::Certificate,
+            intermediates: &[rustls::Certificate],
+            server_name: &rustls::ServerName,
+            scts: &mut dyn Iterator<Item = &[u8]>,
+            ocsp_response: &[u8],
+            now: std::time::SystemTime,
+        ) -> Result<rustls::client::ServerCertVerified, rustls::Error> {
+            self.verified.fetch_add(1, Ordering::SeqCst);
+            self.inner.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                scts,
+                ocsp_response,
+                now,
+            )
+        }
+    }
+
+    let mut roots = rustls::RootCertStore::empty();
+    for pem in [FOO_NS1.trust_anchors, FOO_NS1_CA2.trust_anchors] {
+        roots
+            .add(&rustls::Certificate(pem_to_der(pem)))
+            .expect("root must be valid");
+    }
+    let verifier = Arc::new(Counting {
+        inner: rustls::client::WebPkiVerifier::new(roots, None),
+        verified: AtomicUsize::new(0),
+    });
+    let ca1 = std::str::from_utf8(FOO_NS1.trust_anchors).unwrap();
+    let (mut store, rx) = crate::creds::watch_with_verifier(
+        FOO_NS1.name.parse().unwrap(),
+        &[ca1],
+        FOO_NS1.key,
+        FOO_NS1_CSR,
+        verifier.clone(),
+        Options::default(),
+    )
+    .expect("credentials must be readable");
+
+    // The store validates its own certificates with the verifier.
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    assert_eq!(verifier.verified.load(Ordering::SeqCst), 1);
+
+    // Clients trust servers that the verifier accepts, even if their issuers
+    // aren't trust roots, and keep doing so when the roots are replaced.
+    let (mut ca2_store, ca2_rx) = watch(&FOO_NS1_CA2, Options::default());
+    ca2_store
+        .set_certificate(DerX509(FOO_NS1_CA2.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    handshake(
+        rx.client_config_without_client_auth(),
+        FOO_NS1.name,
+        ca2_rx.server().config(),
+    )
+    .expect("the verifier must accept ca2's server");
+    store.set_trust_anchors(ca1).expect("roots must be valid");
+    handshake(
+        rx.client_config_without_client_auth(),
+        FOO_NS1.name,
+        ca2_rx.server().config(),
+    )
+    .expect("the verifier must be kept");
+    assert_eq!(verifier.verified.load(Ordering::SeqCst), 4);
+}
+
+#[test]
+fn trust_anchor_bundles_are_merged() {
+    let ca1 = std::str::from_utf8(FOO_NS1.trust_anchors).unwrap();
+    let ca2 = std::str::from_utf8(FOO_NS1_CA2.trust_anchors).unwrap();
+    let watch_bundles = |bundles: &[&str]| {
+        crate::creds::watch_with_bundles(
+            FOO_NS1.name.parse().unwrap(),
+            bundles,
+            FOO_NS1.key,
+            FOO_NS1_CSR,
+            Options::default(),
         )
+    };
+
+    // Empty bundles are skipped with a warning.
+    let mut result = None;
+    let warnings = messages(tracing::Level::WARN, || {
+        result = Some(watch_bundles(&[ca1, "", ca2]));
+    });
+    assert!(
+        warnings
+            .iter()
+            .any(|m| m == "no valid certs in trust anchors file"),
+        "{:?}",
+        warnings
+    );
+    let (mut store, rx) = result.unwrap().expect("bundles must be loaded");
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificates issued by any bundle's roots must be valid");
+
+    // Servers whose certificates are issued by either CA are trusted.
+    let (mut ca2_store, ca2_rx) = watch(&FOO_NS1_CA2, Options::default());
+    ca2_store
+        .set_certificate(DerX509(FOO_NS1_CA2.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    for server in [rx.server().config(), ca2_rx.server().config()] {
+        handshake(rx.new_client().config(), FOO_NS1.name, server)
+            .expect("roots from all bundles must be trusted");
+    }
+
+    // Some bundle must contain roots, and every bundle must be PEM.
+    for bundles in [&[][..], &["", ""][..]] {
+        let err = watch_bundles(bundles).err().expect("roots are required");
+        assert!(
+            matches!(
+                err.downcast_ref::<WatchError>(),
+                Some(WatchError::NoTrustRoots)
+            ),
+            "{:?}: {}",
+            bundles,
+            err
+        );
+    }
+    let err = watch_bundles(&[
+        ca1,
+        "-----BEGIN CERTIFICATE-----\n!!!!\n-----END CERTIFICATE-----\n",
+    ])
+    .err()
+    .expect("invalid PEM must be rejected");
+    assert!(matches!(
+        err.downcast_ref::<WatchError>(),
+        Some(WatchError::PemParse(_))
+    ));
+}
+
+#[test]
+fn client_has_certs_only_once_provisioned() {
+    let (mut store, rx) = watch(&FOO_NS1, Options::default());
+    assert!(!rx
+        .new_client()
+        .config()
+        .client_auth_cert_resolver
+        .has_certs());
+
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    assert!(rx
+        .new_client()
+        .config()
+        .client_auth_cert_resolver
+        .has_certs());
+}
+
+#[test]
+fn clients_stop_presenting_certificates_that_expire_by_the_configured_clock() {
+    let now = Arc::new(parking_lot::Mutex::new(std::time::SystemTime::now()));
+    let (mut store, rx) = watch(&FOO_NS1, {
+        let now = now.clone();
+        Options {
+            clock: Some(Arc::new(move || *now.lock())),
+            ..Options::default()
+        }
+    });
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    let config = rx.new_client().config();
+    assert!(config.client_auth_cert_resolver.has_certs());
+
+    // The test leaf expires when ca1 does, in 2031.
+    *now.lock() = std::time::UNIX_EPOCH + Duration::from_secs(4_000_000_000);
+    assert!(!config.client_auth_cert_resolver.has_certs());
+}
+
+#[test]
+fn install_records_describe_the_installed_cert() {
+    let records = Arc::new(parking_lot::Mutex::new(Vec::new()));
+    let mut store = load_with_options(&FOO_NS1, {
+        let records = records.clone();
+        Options {
+            on_install: Some(Arc::new(move |r: &InstallRecord| {
+                records.lock().push(r.clone())
+            })),
+            ..Options::default()
+        }
+    });
+
+    // Rejected certificates are not recorded.
+    assert!(store
+        .set_certificate(DerX509(BAR_NS1.crt.to_vec()), vec![], expiry())
+        .is_err());
+    assert!(records.lock().is_empty());
+
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    let records = records.lock();
+    assert_eq!(records.len(), 1);
+    let record = &records[0];
+    assert_eq!(record.identity.as_str(), FOO_NS1.name);
+    assert_eq!(
+        &record.leaf_sha256[..],
+        ring::digest::digest(&ring::digest::SHA256, FOO_NS1.crt).as_ref()
+    );
+    // 2021-10-08T19:24:00Z to 2031-10-06T19:24:00Z
+    assert_eq!(
+        record.not_before,
+        std::time::UNIX_EPOCH + Duration::from_secs(1_633_721_040)
+    );
+    assert_eq!(
+        record.not_after,
+        std::time::UNIX_EPOCH + Duration::from_secs(1_949_081_040)
+    );
+    // OU=None
+    assert_eq!(
+        record.issuer,
+        b"\x30\x0f\x31\x0d\x30\x0b\x06\x03\x55\x04\x0b\x13\x04None"
+    );
+    assert_eq!(record.chain_len, 1);
+    let roots = pem_to_der(FOO_NS1.trust_anchors);
+    assert_eq!(
+        &record.roots_sha256[..],
+        ring::digest::digest(&ring::digest::SHA256, &roots).as_ref()
+    );
+}
+
+#[test]
+fn rotations_are_reported_once_published() {
+    let rotations = Arc::new(parking_lot::Mutex::new(Vec::new()));
+    let (mut store, rx) = watch(&FOO_NS1, {
+        let rotations = rotations.clone();
+        Options {
+            on_rotation: Some(Arc::new(move |expiry| rotations.lock().push(expiry))),
+            ..Options::default()
+        }
+    });
+
+    // Rejected certificates are not reported.
+    assert!(store
+        .set_certificate(DerX509(BAR_NS1.crt.to_vec()), vec![], expiry())
         .is_err());
+    assert!(rotations.lock().is_empty());
+
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    assert_eq!(
+        *rotations.lock(),
+        [std::time::UNIX_EPOCH + Duration::from_secs(1_949_081_040)]
+    );
+
+    // Certificates that aren't published to any receivers are not reported.
+    drop(rx);
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    assert_eq!(rotations.lock().len(), 1);
+}
+
+#[test]
+fn csr_sans_are_compared_when_configured() {
+    let watch_csr = |csr_sans| {
+        crate::creds::watch_with_options(
+            FOO_NS1.name.parse().unwrap(),
+            std::str::from_utf8(FOO_NS1.trust_anchors).unwrap(),
+            FOO_NS1.key,
+            FOO_NS1_CSR,
+            Options {
+                csr_sans,
+                ..Options::default()
+            },
+        )
+        .expect("credentials must be readable")
+        .0
+    };
+
+    for policy in [
+        CsrSanPolicy::Ignore,
+        CsrSanPolicy::Warn,
+        CsrSanPolicy::Require,
+    ] {
+        watch_csr(policy)
+            .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+            .expect("certificate must match the CSR");
+    }
+
+    for policy in [CsrSanPolicy::Ignore, CsrSanPolicy::Warn] {
+        watch_csr(policy)
+            .set_certificate(DerX509(FOO_NS1_EXTRA_SAN.to_vec()), vec![], expiry())
+            .expect("divergent SANs are only rejected when required");
+    }
+    let err = watch_csr(CsrSanPolicy::Require)
+        .set_certificate(DerX509(FOO_NS1_EXTRA_SAN.to_vec()), vec![], expiry())
+        .expect_err("divergent SANs must be rejected");
+    assert!(err.is::<CsrSanMismatch>(), "{}", err);
+}
+
+#[test]
+fn csrs_may_be_generated_for_the_key() {
+    let watch_generated = |template| {
+        crate::creds::watch_with_options(
+            FOO_NS1.name.parse().unwrap(),
+            std::str::from_utf8(FOO_NS1.trust_anchors).unwrap(),
+            FOO_NS1.key,
+            &[],
+            Options {
+                csr_sans: CsrSanPolicy::Require,
+                generate_csr: Some(template),
+                ..Options::default()
+            },
+        )
+        .expect("credentials must be readable")
+        .0
+    };
+
+    let mut store = watch_generated(CsrTemplate::default());
+    let DerX509(csr) = store.gen_certificate_signing_request();
+    assert_ne!(csr, FOO_NS1_CSR, "CSR must be generated");
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("generated CSR must request the identity");
+    let err = watch_generated(CsrTemplate::default())
+        .set_certificate(DerX509(FOO_NS1_EXTRA_SAN.to_vec()), vec![], expiry())
+        .expect_err("divergent SANs must be rejected");
+    assert!(err.is::<CsrSanMismatch>(), "{}", err);
+
+    watch_generated(CsrTemplate {
+        dns_names: vec![
+            FOO_NS1.name.to_string(),
+            "bar.ns1.serviceaccount.identity.linkerd.cluster.local".to_string(),
+        ],
+        ..CsrTemplate::default()
+    })
+    .set_certificate(DerX509(FOO_NS1_EXTRA_SAN.to_vec()), vec![], expiry())
+    .expect("generated CSR must request the configured SANs");
+}
+
+#[test]
+fn keys_may_be_held_externally() {
+    struct HsmKey {
+        key: ring::signature::EcdsaKeyPair,
+        spki: Vec<u8>,
+        signatures: AtomicUsize,
+    }
+
+    impl crate::creds::ExternalKey for HsmKey {
+        fn spki(&self) -> &[u8] {
+            &self.spki
+        }
+
+        fn sign(
+            &self,
+            scheme: rustls::SignatureScheme,
+            message: &[u8],
+        ) -> Result<Vec<u8>, rustls::Error> {
+            assert_eq!(scheme, rustls::SignatureScheme::ECDSA_NISTP256_SHA256);
+            self.signatures.fetch_add(1, Ordering::SeqCst);
+            let rng = ring::rand::SystemRandom::new();
+            let signature = self
+                .key
+                .sign(&rng, message)
+                .expect("message must be signed");
+            Ok(signature.as_ref().to_vec())
+        }
+    }
+
+    let key = ring::signature::EcdsaKeyPair::from_pkcs8(
+        &ring::signature::ECDSA_P256_SHA256_ASN1_SIGNING,
+        FOO_NS1.key,
+    )
+    .unwrap();
+    // A P-256 SubjectPublicKeyInfo, which ends with the public key.
+    let spki = [
+        &hex::decode("3059301306072a8648ce3d020106082a8648ce3d030107034200").unwrap()[..],
+        ring::signature::KeyPair::public_key(&key).as_ref(),
+    ]
+    .concat();
+    let hsm = Arc::new(HsmKey {
+        key,
+        spki,
+        signatures: AtomicUsize::new(0),
+    });
+
+    let (mut store, rx) = crate::creds::watch_with_external_key(
+        FOO_NS1.name.parse().unwrap(),
+        &[std::str::from_utf8(FOO_NS1.trust_anchors).unwrap()],
+        hsm.clone(),
+        &[],
+        Options {
+            generate_csr: Some(CsrTemplate::default()),
+            ..Options::default()
+        },
+    )
+    .expect("credentials must be readable");
+    assert_eq!(
+        hsm.signatures.load(Ordering::SeqCst),
+        1,
+        "the CSR must be signed by the external key"
+    );
+
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificates for the external key must be accepted");
+    handshake(rx.new_client().config(), FOO_NS1.name, rx.server().config())
+        .expect("handshake must succeed");
+    assert!(hsm.signatures.load(Ordering::SeqCst) > 1);
+
+    // Certificates for other keys are rejected, as for in-process keys.
+    let err = store
+        .set_certificate(DerX509(FOO_NS1_RSA.to_vec()), vec![], expiry())
+        .expect_err("certificates for other keys must be rejected");
+    assert!(
+        err.is::<crate::creds::CertKeyAlgorithmMismatch>(),
+        "{}",
+        err
+    );
+
+    let err = crate::creds::watch_with_external_key(
+        FOO_NS1.name.parse().unwrap(),
+        &[std::str::from_utf8(FOO_NS1.trust_anchors).unwrap()],
+        Arc::new(HsmKey {
+            key: ring::signature::EcdsaKeyPair::from_pkcs8(
+                &ring::signature::ECDSA_P256_SHA256_ASN1_SIGNING,
+                FOO_NS1.key,
+            )
+            .unwrap(),
+            spki: b"not a SubjectPublicKeyInfo".to_vec(),
+            signatures: AtomicUsize::new(0),
+        }),
+        FOO_NS1_CSR,
+        Options::default(),
+    )
+    .err()
+    .expect("malformed public keys must be rejected");
+    assert!(
+        matches!(
+            err.downcast_ref::<WatchError>(),
+            Some(WatchError::InvalidPublicKey)
+        ),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn csrs_must_be_for_the_private_key() {
+    let watch = |key: &[u8], csr: &[u8]| {
+        crate::creds::watch(
+            FOO_NS1.name.parse().unwrap(),
+            std::str::from_utf8(FOO_NS1.trust_anchors).unwrap(),
+            key,
+            csr,
+        )
+    };
+
+    let (store, _rx) = watch(FOO_NS1.key, FOO_NS1_CSR).expect("the CSR is for foo's key");
+    assert_eq!(store.gen_certificate_signing_request().0, FOO_NS1_CSR);
+    for key in [BAR_NS1.key, FOO_NS1_RSA_KEY] {
+        let err = watch(key, FOO_NS1_CSR)
+            .err()
+            .expect("the CSR is not for this key");
+        assert!(err.is::<crate::creds::CsrKeyMismatch>(), "{}", err);
+    }
+
+    // Requests that aren't PKCS #10 are rejected rather than sent to the
+    // identity service.
+    let err = watch(BAR_NS1.key, b"fake CSR data")
+        .err()
+        .expect("opaque CSRs must be rejected");
+    assert!(
+        matches!(
+            err.downcast_ref::<WatchError>(),
+            Some(WatchError::InvalidCsr)
+        ),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn csrs_may_be_pem() {
+    let watch = |csr: &[u8]| {
+        crate::creds::watch(
+            FOO_NS1.name.parse().unwrap(),
+            std::str::from_utf8(FOO_NS1.trust_anchors).unwrap(),
+            FOO_NS1.key,
+            csr,
+        )
+    };
+
+    // PEM requests are sent as DER, and are checked against the key.
+    let (store, _rx) = watch(FOO_NS1_CSR_PEM).expect("PEM CSRs must be read");
+    assert_eq!(store.gen_certificate_signing_request().0, FOO_NS1_CSR);
+    let err = crate::creds::watch(
+        BAR_NS1.name.parse().unwrap(),
+        std::str::from_utf8(BAR_NS1.trust_anchors).unwrap(),
+        BAR_NS1.key,
+        FOO_NS1_CSR_PEM,
+    )
+    .err()
+    .expect("the CSR is not for bar's key");
+    assert!(err.is::<crate::creds::CsrKeyMismatch>(), "{}", err);
+
+    // PEM files must contain exactly one well-formed request.
+    let terminated = std::str::from_utf8(FOO_NS1_CSR_PEM).unwrap();
+    let unterminated = &terminated[..terminated.find("-----END").unwrap()];
+    let garbled = terminated.replacen("MII", "!!!", 1);
+    for (pem, expected) in [
+        (INT_A.to_vec(), "no certificate signing request in PEM file"),
+        (
+            [FOO_NS1_CSR_PEM, FOO_NS1_CSR_PEM].concat(),
+            "2 certificate signing requests in PEM file, but only one may be used",
+        ),
+        (
+            unterminated.as_bytes().to_vec(),
+            "invalid certificate signing request PEM",
+        ),
+        (
+            garbled.into_bytes(),
+            "invalid certificate signing request PEM",
+        ),
+    ] {
+        let err = watch(&pem).err().expect("the PEM must be rejected");
+        assert!(
+            matches!(
+                err.downcast_ref::<WatchError>(),
+                Some(
+                    WatchError::NoCsr
+                        | WatchError::MultipleCsrs { .. }
+                        | WatchError::CsrPemParse(_)
+                )
+            ),
+            "{}",
+            err
+        );
+        assert!(err.to_string().starts_with(expected), "{}", err);
+    }
+
+    // The builder names the CSR as the invalid setting.
+    let err = CredentialsBuilder::new()
+        .identity(FOO_NS1.name.parse().unwrap())
+        .trust_roots_pem(std::str::from_utf8(FOO_NS1.trust_anchors).unwrap())
+        .key_pkcs8(FOO_NS1.key)
+        .csr(INT_A)
+        .build()
+        .err()
+        .expect("the PEM contains no CSR");
+    assert!(err.to_string().starts_with("invalid CSR"), "{}", err);
+}
+
+#[test]
+fn credentials_may_be_built() {
+    let builder = || {
+        CredentialsBuilder::new()
+            .identity(FOO_NS1.name.parse().unwrap())
+            .trust_roots_pem(std::str::from_utf8(FOO_NS1.trust_anchors).unwrap())
+            .key_pkcs8(FOO_NS1.key)
+            .csr(FOO_NS1_CSR)
+    };
+
+    // TLS 1.2 suites may only be configured once TLS 1.2 is enabled.
+    let suites = vec![
+        CipherSuite::Tls13Aes128GcmSha256,
+        CipherSuite::Tls12EcdheEcdsaWithAes128GcmSha256,
+    ];
+    let (mut store, rx) = builder()
+        .cipher_suites(suites.clone())
+        .protocol_versions(vec![
+            rustls::ProtocolVersion::TLSv1_3,
+            rustls::ProtocolVersion::TLSv1_2,
+        ])
+        .resumption(true)
+        .build()
+        .expect("credentials must be built");
+    assert_eq!(
+        rx.cipher_suites()
+            .iter()
+            .map(|s| s.suite())
+            .collect::<Vec<_>>(),
+        vec![
+            rustls::CipherSuite::TLS13_AES_128_GCM_SHA256,
+            rustls::CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256
+        ]
+    );
+    assert_eq!(store.gen_certificate_signing_request().0, FOO_NS1_CSR);
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be installed");
+    handshake(rx.new_client().config(), FOO_NS1.name, rx.server().config())
+        .expect("handshake must succeed");
+
+    // Missing and invalid settings are named.
+    let err = CredentialsBuilder::new()
+        .trust_roots_pem(std::str::from_utf8(FOO_NS1.trust_anchors).unwrap())
+        .key_pkcs8(FOO_NS1.key)
+        .csr(FOO_NS1_CSR)
+        .build()
+        .err()
+        .expect("the identity is required");
+    assert!(err.is::<crate::creds::MissingField>(), "{}", err);
+    assert!(err.to_string().contains("identity"), "{}", err);
+
+    let err = builder()
+        .cipher_suites(suites)
+        .build()
+        .err()
+        .expect("TLS 1.2 suites require TLS 1.2");
+    assert!(err.is::<crate::creds::InvalidField>(), "{}", err);
+    assert!(
+        err.to_string().starts_with("invalid cipher suites"),
+        "{}",
+        err
+    );
+
+    let err = builder()
+        .protocol_versions(vec![rustls::ProtocolVersion::TLSv1_2])
+        .build()
+        .err()
+        .expect("TLS 1.3 is required");
+    assert!(err.is::<crate::creds::InvalidField>(), "{}", err);
+    assert!(err.to_string().contains("protocol versions"), "{}", err);
+
+    let err = builder()
+        .key_pkcs8(&b"not a key"[..])
+        .build()
+        .err()
+        .expect("invalid keys are rejected");
+    assert!(err.is::<crate::creds::InvalidField>(), "{}", err);
+    assert!(
+        err.to_string().starts_with("invalid private key"),
+        "{}",
+        err
+    );
+    let source = std::error::Error::source(&*err).expect("errors must have a source");
+    assert!(source.is::<WatchError>(), "{}", source);
+}
+
+#[test]
+fn keys_may_be_replaced() {
+    let (mut store, rx) = watch(&FOO_NS1, Options::default());
+
+    // Before a certificate is installed, any key may be used.
+    store
+        .set_key(FOO_NS1_RSA_KEY)
+        .expect("keys may be replaced before a certificate is installed");
+    let err = store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect_err("certificates for the previous key must be rejected");
+    assert!(
+        err.is::<crate::creds::CertKeyAlgorithmMismatch>(),
+        "{}",
+        err
+    );
+    store
+        .set_certificate(DerX509(FOO_NS1_RSA.to_vec()), vec![], expiry())
+        .expect("certificates for the new key must be accepted");
+
+    // Once a certificate is installed, only the key it certifies may be used.
+    let server = rx.server_config();
+    let err = store
+        .set_key(FOO_NS1.key)
+        .expect_err("keys the certificate doesn't certify must be rejected");
+    assert!(err.is::<crate::creds::KeyMismatch>(), "{}", err);
+    assert!(Arc::ptr_eq(&server, &rx.server_config()));
+
+    store
+        .set_key(FOO_NS1_RSA_KEY_PKCS1_PEM)
+        .expect("the certified key may be reloaded");
+    assert!(!Arc::ptr_eq(&server, &rx.server_config()));
+    handshake(rx.new_client().config(), FOO_NS1.name, rx.server().config())
+        .expect("handshake must succeed with the reloaded key");
+}
+
+#[test]
+fn keys_must_match_separate_client_certificates() {
+    let (mut store, rx) = watch(&FOO_NS1, Options::default());
+    store
+        .set_certificates(
+            DerX509(FOO_NS1_SERVER_AUTH.to_vec()),
+            vec![],
+            DerX509(FOO_NS1_CLIENT_AUTH.to_vec()),
+            vec![],
+            expiry(),
+        )
+        .expect("certificates must be valid");
+
+    // Only the client certificate is for a different key than the new one.
+    store.set_resolver_unchecked(vec![rustls::Certificate(FOO_NS1_RSA.to_vec())]);
+    let client = rx.client_config();
+    let err = store
+        .set_key(FOO_NS1_RSA_KEY)
+        .expect_err("keys the client certificate doesn't certify must be rejected");
+    assert!(err.is::<crate::creds::KeyMismatch>(), "{}", err);
+    assert!(Arc::ptr_eq(&client, &rx.client_config()));
+}
+
+#[test]
+fn certs_for_other_keys_are_rejected() {
+    // FOO_NS1_CA2 names the same identity as FOO_NS1 but was issued for a
+    // different key.
+    let roots = [FOO_NS1.trust_anchors, FOO_NS1_CA2.trust_anchors].concat();
+    let (mut store, _) = watch_raw(FOO_NS1.name, &roots, FOO_NS1.key, Options::default());
+    let err = store
+        .set_certificate(DerX509(FOO_NS1_CA2.crt.to_vec()), vec![], expiry())
+        .expect_err("certificate must be rejected");
+    assert!(err.is::<crate::creds::KeyMismatch>(), "{}", err);
+
+    // Certificates for keys of other types are described as such.
+    for (crt, certificate) in [(FOO_NS1_RSA, "RSA"), (FOO_NS1_P384, "ECDSA P-384")] {
+        let err = store
+            .set_certificate(DerX509(crt.to_vec()), vec![], expiry())
+            .expect_err("certificate must be rejected");
+        assert!(
+            err.is::<crate::creds::CertKeyAlgorithmMismatch>(),
+            "{}",
+            err
+        );
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "issued certificate's public key is {}, but our private key is ECDSA P-256",
+                certificate
+            )
+        );
+    }
+}
+
+#[test]
+fn rsa_keys_may_sign_handshakes() {
+    let (mut store, rx) = watch_raw(
+        FOO_NS1.name,
+        FOO_NS1.trust_anchors,
+        FOO_NS1_RSA_KEY,
+        Options::default(),
+    );
+    store
+        .set_certificate(DerX509(FOO_NS1_RSA.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    store
+        .self_resolve_check()
+        .expect("installed certificate must resolve for our identity");
+
+    // The RSA identity authenticates both as a server and as a client.
+    let (client, server) = handshake(rx.new_client().config(), FOO_NS1.name, rx.server().config())
+        .expect("handshake must succeed");
+    for certs in [client.peer_certificates(), server.peer_certificates()] {
+        assert_eq!(
+            certs.and_then(|c| c.first()).map(|c| c.as_ref()),
+            Some(FOO_NS1_RSA)
+        );
+    }
+
+    // A certificate for the ECDSA key is rejected.
+    let err = store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect_err("certificate must be rejected");
+    assert!(
+        err.is::<crate::creds::CertKeyAlgorithmMismatch>(),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn keys_are_read_by_algorithm() {
+    let (mut store, rx) = watch_raw(
+        FOO_NS1.name,
+        FOO_NS1.trust_anchors,
+        FOO_NS1_P384_KEY,
+        Options::default(),
+    );
+    store
+        .set_certificate(DerX509(FOO_NS1_P384.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    handshake(rx.new_client().config(), FOO_NS1.name, rx.server().config())
+        .expect("handshake must succeed");
+
+    let watch = |key| {
+        crate::creds::watch(
+            FOO_NS1.name.parse().unwrap(),
+            std::str::from_utf8(FOO_NS1.trust_anchors).unwrap(),
+            key,
+            FOO_NS1_CSR,
+        )
+    };
+    let err = watch(P521_KEY).err().expect("P-521 keys must be rejected");
+    assert!(
+        matches!(
+            err.downcast_ref::<WatchError>(),
+            Some(WatchError::UnsupportedKeyAlgorithm(_))
+        ),
+        "{}",
+        err
+    );
+    assert_eq!(
+        err.to_string(),
+        "unsupported private key algorithm: ECDSA P-521"
+    );
+
+    let err = watch(b"not a key").err().expect("garbage must be rejected");
+    assert!(
+        matches!(
+            err.downcast_ref::<WatchError>(),
+            Some(WatchError::InvalidKey(_))
+        ),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn pem_keys_are_read() {
+    for (key, crt) in [
+        (FOO_NS1_KEY_PEM, FOO_NS1.crt),
+        (FOO_NS1_KEY_SEC1_PEM, FOO_NS1.crt),
+        (FOO_NS1_RSA_KEY_PKCS1_PEM, FOO_NS1_RSA),
+    ] {
+        let (mut store, rx) =
+            watch_raw(FOO_NS1.name, FOO_NS1.trust_anchors, key, Options::default());
+        store
+            .set_certificate(DerX509(crt.to_vec()), vec![], expiry())
+            .expect("the PEM key must match the certificate");
+        handshake(rx.new_client().config(), FOO_NS1.name, rx.server().config())
+            .expect("handshake must succeed");
+    }
+
+    let watch = |key: &[u8]| {
+        crate::creds::watch(
+            FOO_NS1.name.parse().unwrap(),
+            std::str::from_utf8(FOO_NS1.trust_anchors).unwrap(),
+            key,
+            FOO_NS1_CSR,
+        )
+        .err()
+        .expect("key must be rejected")
+    };
+    let err = watch(&[FOO_NS1_KEY_PEM, FOO_NS1_KEY_SEC1_PEM].concat());
+    assert!(
+        matches!(
+            err.downcast_ref::<WatchError>(),
+            Some(WatchError::MultiplePrivateKeys { count: 2 })
+        ),
+        "{}",
+        err
+    );
+    // A PEM file that holds only certificates.
+    let err = watch(FOO_NS1.trust_anchors);
+    assert!(
+        matches!(
+            err.downcast_ref::<WatchError>(),
+            Some(WatchError::NoPrivateKey)
+        ),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn encrypted_keys_are_decrypted_with_the_password() {
+    let key_password = |password: Option<&[u8]>| Options {
+        key_password: password.map(<[u8]>::to_vec),
+        ..Options::default()
+    };
+
+    let (mut store, rx) = watch_raw(
+        FOO_NS1.name,
+        FOO_NS1.trust_anchors,
+        FOO_NS1_KEY_ENCRYPTED,
+        key_password(Some(b"correct-horse")),
+    );
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("the decrypted key must match the certificate");
+    handshake(rx.new_client().config(), FOO_NS1.name, rx.server().config())
+        .expect("handshake must succeed");
+
+    let watch = |key, password| {
+        crate::creds::watch_with_options(
+            FOO_NS1.name.parse().unwrap(),
+            std::str::from_utf8(FOO_NS1.trust_anchors).unwrap(),
+            key,
+            FOO_NS1_CSR,
+            key_password(password),
+        )
+        .err()
+        .expect("key must be rejected")
+    };
+    let err = watch(FOO_NS1_KEY_ENCRYPTED, None);
+    assert!(
+        matches!(
+            err.downcast_ref::<WatchError>(),
+            Some(WatchError::KeyPasswordRequired)
+        ),
+        "{}",
+        err
+    );
+    let err = watch(FOO_NS1_KEY_ENCRYPTED, Some(b"incorrect-horse"));
+    assert!(
+        matches!(
+            err.downcast_ref::<WatchError>(),
+            Some(WatchError::KeyDecryption(_))
+        ),
+        "{}",
+        err
+    );
+    let err = watch(FOO_NS1.key, Some(b"correct-horse"));
+    assert!(
+        matches!(
+            err.downcast_ref::<WatchError>(),
+            Some(WatchError::KeyNotEncrypted)
+        ),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn empty_csrs_are_rejected() {
+    let err = crate::creds::watch(
+        FOO_NS1.name.parse().unwrap(),
+        std::str::from_utf8(FOO_NS1.trust_anchors).unwrap(),
+        FOO_NS1.key,
+        b"",
+    )
+    .err()
+    .expect("an empty CSR must be rejected");
+    assert!(err.is::<crate::creds::EmptyCsr>(), "{}", err);
+}
+
+#[test]
+fn install_durations_are_recorded() {
+    let metrics = Metrics::default();
+    let mut store = load_with_options(
+        &FOO_NS1,
+        Options {
+            metrics: metrics.clone(),
+            ..Options::default()
+        },
+    );
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+
+    let text = metrics.as_display().to_string();
+    for phase in ["validate", "build", "publish"] {
+        let count = format!(
+            "identity_cert_install_duration_us_count{{phase=\"{}\"}} 1\n",
+            phase
+        );
+        assert!(text.contains(&count), "missing {:?} in:\n{}", count, text);
+    }
+
+    // A rejected certificate is not recorded.
+    assert!(store
+        .set_certificate(DerX509(BAR_NS1.crt.to_vec()), vec![], expiry())
+        .is_err());
+    assert!(metrics
+        .as_display()
+        .to_string()
+        .contains("identity_cert_install_duration_us_count{phase=\"validate\"} 1\n"));
+}
+
+#[test]
+fn metrics_report_all_series() {
+    let metrics = Metrics::default();
+    let mut store = load_with_options(
+        &FOO_NS1,
+        Options {
+            metrics: metrics.clone(),
+            ..Options::default()
+        },
+    );
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+
+    let text = metrics.as_display().to_string();
+    let resolver_max_bytes = format!("identity_cert_resolver_max_bytes {}\n", FOO_NS1.crt.len());
+    for series in [
+        "identity_cert_installs_total 1\n",
+        // 2031-10-06T19:24:00Z
+        "identity_cert_expiration_timestamp_seconds 1949081040\n",
+        resolver_max_bytes.as_str(),
+        "# TYPE identity_cert_install_duration_us histogram\n",
+        "identity_roots_expiration_timestamp_seconds 1949081040\n",
+        "identity_sni_mismatches_total 0\n",
+        "identity_cert_resolution_failures_total{reason=\"no_cert_yet\"} 0\n",
+    ] {
+        assert!(text.contains(series), "missing {:?} in:\n{}", series, text);
+    }
+}
+
+#[test]
+fn resolver_size_reflects_installed_chains() {
+    let metrics = Metrics::default();
+    let mut store = load_with_options(
+        &FOO_NS1,
+        Options {
+            metrics: metrics.clone(),
+            ..Options::default()
+        },
+    );
+    assert_eq!(store.resolver_size(), 0);
+
+    let int_b = pem_to_der(INT_B);
+    let installed = store.install_certificate(
+        DerX509(FOO_NS1_INT_B.to_vec()),
+        vec![DerX509(int_b.clone())],
+        expiry(),
+    );
+    assert!(matches!(installed, Install::Published), "{:?}", installed);
+    let chain_len = FOO_NS1_INT_B.len() + int_b.len();
+    assert_eq!(store.resolver_size(), chain_len);
+
+    // The size shrinks with a shorter chain, but the metric reports the
+    // largest size observed.
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    assert_eq!(store.resolver_size(), FOO_NS1.crt.len());
+    let max = format!("identity_cert_resolver_max_bytes {}\n", chain_len);
+    let text = metrics.as_display().to_string();
+    assert!(text.contains(&max), "missing {:?} in:\n{}", max, text);
+}
+
+#[test]
+fn resolver_size_includes_every_identity() {
+    let metrics = Metrics::default();
+    let (mut store, _rx) = watch(
+        &FOO_NS1,
+        Options {
+            metrics: metrics.clone(),
+            ..Options::default()
+        },
+    );
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    let mut total = FOO_NS1.crt.len();
+    assert_eq!(store.resolver_size(), total);
+
+    for (name, crt) in other_identities() {
+        store
+            .set_certificate_for(name, DerX509(crt.to_vec()), vec![])
+            .expect("certificate must be valid");
+        total += crt.len();
+        assert_eq!(store.resolver_size(), total);
+    }
+    let max = format!("identity_cert_resolver_max_bytes {}\n", total);
+    let text = metrics.as_display().to_string();
+    assert!(text.contains(&max), "missing {:?} in:\n{}", max, text);
+
+    let [(bar, bar_crt), ..] = other_identities();
+    assert!(store.remove_certificate_for(&bar));
+    assert_eq!(store.resolver_size(), total - bar_crt.len());
+}
+
+#[test]
+fn alpn_may_be_configured_when_watching() {
+    let protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    let (mut store, rx) = watch(
+        &FOO_NS1,
+        Options {
+            alpn_protocols: protocols.clone(),
+            ..Options::default()
+        },
+    );
+    assert_eq!(rx.client_config().alpn_protocols, protocols);
+    assert_eq!(rx.server_config().alpn_protocols, protocols);
+
+    // The protocols are preserved as certificates are rotated.
+    for _ in 0..2 {
+        store
+            .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+            .expect("certificate must be valid");
+        assert_eq!(rx.client_config().alpn_protocols, protocols);
+        assert_eq!(rx.server_config().alpn_protocols, protocols);
+
+        let (client, server) = handshake(rx.client_config(), FOO_NS1.name, rx.server_config())
+            .expect("handshake must succeed");
+        assert_eq!(client.alpn_protocol(), Some(&b"h2"[..]));
+        assert_eq!(server.alpn_protocol(), Some(&b"h2"[..]));
+    }
+
+    // Without protocols, ALPN isn't negotiated.
+    let (mut store, rx) = watch(&FOO_NS1, Options::default());
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    let (client, server) = handshake(rx.client_config(), FOO_NS1.name, rx.server_config())
+        .expect("handshake must succeed");
+    assert_eq!(client.alpn_protocol(), None);
+    assert_eq!(server.alpn_protocol(), None);
+}
+
+#[test]
+fn alpn_may_be_updated_without_rotation() {
+    let (mut store, rx) = watch(&FOO_NS1, Options::default());
+    assert!(store
+        .set_alpn(vec![b"h2".to_vec()])
+        .unwrap_err()
+        .is::<crate::creds::NoCertificate>());
+
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    assert!(rx.new_client().config().alpn_protocols.is_empty());
+    assert!(rx.server().config().alpn_protocols.is_empty());
+
+    let protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    store
+        .set_alpn(protocols.clone())
+        .expect("a certificate is installed");
+    assert_eq!(rx.new_client().config().alpn_protocols, protocols);
+    assert_eq!(rx.server().config().alpn_protocols, protocols);
+
+    let (client, _) = handshake(rx.new_client().config(), FOO_NS1.name, rx.server().config())
+        .expect("handshake must succeed");
+    assert_eq!(client.alpn_protocol(), Some(&b"h2"[..]));
+}
+
+#[test]
+fn client_sessions_use_the_configured_store() {
+    #[derive(Default)]
+    struct Sessions {
+        gets: AtomicUsize,
+        puts: AtomicUsize,
+    }
+
+    impl rustls::client::ClientSessionStore for Sessions {
+        fn set_kx_hint(&self, _: &rustls::ServerName, _: rustls::NamedGroup) {
+            self.puts.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn kx_hint(&self, _: &rustls::ServerName) -> Option<rustls::NamedGroup> {
+            self.gets.fetch_add(1, Ordering::SeqCst);
+            None
+        }
+
+        fn set_tls12_session(
+            &self,
+            _: &rustls::ServerName,
+            _: rustls::client::Tls12ClientSessionValue,
+        ) {
+            self.puts.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn tls12_session(
+            &self,
+            _: &rustls::ServerName,
+        ) -> Option<rustls::client::Tls12ClientSessionValue> {
+            self.gets.fetch_add(1, Ordering::SeqCst);
+            None
+        }
+
+        fn remove_tls12_session(&self, _: &rustls::ServerName) {}
+
+        fn insert_tls13_ticket(
+            &self,
+            _: &rustls::ServerName,
+            _: rustls::client::Tls13ClientSessionValue,
+        ) {
+            self.puts.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn take_tls13_ticket(
+            &self,
+            _: &rustls::ServerName,
+        ) -> Option<rustls::client::Tls13ClientSessionValue> {
+            self.gets.fetch_add(1, Ordering::SeqCst);
+            None
+        }
+    }
+
+    let sessions = Arc::new(Sessions::default());
+    let (mut store, rx) = watch(
+        &FOO_NS1,
+        Options {
+            client_sessions: Some(sessions.clone()),
+            ..Options::default()
+        },
+    );
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+
+    handshake(rx.new_client().config(), FOO_NS1.name, rx.server().config())
+        .expect("handshake must succeed");
+    assert!(sessions.gets.load(Ordering::SeqCst) > 0);
+    assert!(sessions.puts.load(Ordering::SeqCst) > 0);
+}
+
+#[test]
+fn sessions_are_only_resumed_when_enabled() {
+    /// Returns the number of bytes the server sends during a handshake.
+    fn server_bytes(rx: &Receiver) -> usize {
+        let name = rustls::ServerName::try_from(FOO_NS1.name).unwrap();
+        let mut client = rustls::Connection::from(
+            rustls::ClientConnection::new(rx.new_client().config(), name).unwrap(),
+        );
+        let mut server =
+            rustls::Connection::from(rustls::ServerConnection::new(rx.server().config()).unwrap());
+
+        let mut sent = 0;
+        for _ in 0..10 {
+            if !client.is_handshaking() && !server.is_handshaking() && !server.wants_write() {
+                return sent;
+            }
+            transfer(&mut client, &mut server).expect("handshake must succeed");
+            while server.wants_write() {
+                let mut buf = Vec::new();
+                server.write_tls(&mut buf).unwrap();
+                sent += buf.len();
+                let mut rd = &buf[..];
+                while !rd.is_empty() {
+                    client.read_tls(&mut rd).unwrap();
+                    client
+                        .process_new_packets()
+                        .expect("handshake must succeed");
+                }
+            }
+        }
+        panic!("handshake did not complete");
+    }
+
+    for session_resumption in [false, true] {
+        let (mut store, rx) = watch(
+            &FOO_NS1,
+            Options {
+                session_resumption,
+                ..Options::default()
+            },
+        );
+        store
+            .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+            .expect("certificate must be valid");
+        assert_eq!(rx.server().config().ticketer.enabled(), session_resumption);
+
+        // A resumed handshake omits the server's certificate.
+        let full = server_bytes(&rx);
+        let next = server_bytes(&rx);
+        assert_eq!(
+            next + FOO_NS1.crt.len() < full,
+            session_resumption,
+            "full handshake sent {} bytes; the next sent {}",
+            full,
+            next
+        );
+    }
+}
+
+#[tokio::test]
+async fn receivers_snapshot_the_current_configs() {
+    let (mut store, rx) = watch(&FOO_NS1, Options::default());
+    let client = rx.client_config();
+    let server = rx.server_config();
+    assert!(Arc::ptr_eq(&client, &rx.new_client().config()));
+    assert!(Arc::ptr_eq(&server, &rx.server().config()));
+
+    // Snapshots don't mark the configurations as seen, so servers that watch
+    // for updates still observe them.
+    let alpn = rx
+        .server()
+        .spawn_with_alpn(vec![b"h2".to_vec()])
+        .expect("store must exist");
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    assert!(!Arc::ptr_eq(&client, &rx.client_config()));
+    assert!(!Arc::ptr_eq(&server, &rx.server_config()));
+    assert!(Arc::ptr_eq(&rx.server_config(), &rx.server().config()));
+
+    tokio::time::timeout(Duration::from_secs(1), async {
+        while handshake(rx.new_client().config(), FOO_NS1.name, alpn.config()).is_err() {
+            tokio::task::yield_now().await;
+        }
+    })
+    .await
+    .expect("the ALPN server must observe the update");
+}
+
+#[test]
+fn rotations_are_described() {
+    let (mut store, rx) = watch(&FOO_NS1, Options::default());
+    let mut rotations = rx.rotations();
+    assert_eq!(*rotations.borrow_and_update(), None);
+
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    assert!(rotations.has_changed().unwrap());
+    let rotation = rotations
+        .borrow_and_update()
+        .clone()
+        .expect("the rotation must be described");
+    assert_eq!(
+        rotation,
+        RotationInfo {
+            generation: 1,
+            serial: hex::decode("7b91e518db98cfac08363b77dfb183b902fb4aff").unwrap(),
+            leaf_sha256: rx.identity_digest().unwrap(),
+            not_before: std::time::UNIX_EPOCH + Duration::from_secs(1_633_721_040),
+            not_after: std::time::UNIX_EPOCH + Duration::from_secs(1_949_081_040),
+        }
+    );
+    assert_eq!(Some(rotation.not_after), rx.cert_expiry());
+
+    // Rejected certificates aren't described.
+    assert!(store
+        .set_certificate(DerX509(FOO_NS1_CA2.crt.to_vec()), vec![], expiry())
+        .is_err());
+    assert!(!rotations.has_changed().unwrap());
+
+    // Each rotation is a new generation.
+    store
+        .set_certificate(
+            DerX509(FOO_NS1_INT_B.to_vec()),
+            vec![DerX509(pem_to_der(INT_B))],
+            expiry(),
+        )
+        .expect("certificate must be valid");
+    let rotation = rotations
+        .borrow_and_update()
+        .clone()
+        .expect("the rotation must be described");
+    assert_eq!(rotation.generation, 2);
+    assert_eq!(Some(rotation.leaf_sha256), rx.identity_digest());
+}
+
+#[tokio::test]
+async fn receivers_observe_the_store_closing() {
+    use futures::FutureExt;
+
+    let (mut store, rx) = watch(&FOO_NS1, Options::default());
+    let mut leaves = rx.leaf_updates();
+    let closed = tokio::spawn({
+        let rx = rx.clone();
+        async move { rx.closed().await }
+    });
+
+    // Updates don't close the receiver, and are still observed by others.
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    assert!(rx.closed().now_or_never().is_none());
+    leaves.changed().await.expect("the store must exist");
+    assert!(leaves.borrow_and_update().is_some());
+    tokio::task::yield_now().await;
+    assert!(!closed.is_finished());
+
+    drop(store);
+    tokio::time::timeout(Duration::from_secs(1), closed)
+        .await
+        .expect("the receiver must observe the store closing")
+        .expect("task must not panic");
+    assert!(rx.closed().now_or_never().is_some());
+}
+
+#[test]
+fn servers_may_select_identities_by_sni() {
+    let (mut store, rx) = watch(&FOO_NS1, Options::default());
+    let bar: linkerd_identity::Name = BAR_NS1.name.parse().unwrap();
+    let presented = |sni: &str| -> Result<Vec<u8>, rustls::Error> {
+        let (client, _) = handshake(rx.new_client().config(), sni, rx.server().config())?;
+        let certs = client
+            .peer_certificates()
+            .expect("server must present a certificate");
+        Ok(certs[0].0.clone())
+    };
+
+    // Other identities aren't served until our own certificate is installed.
+    let err = store
+        .set_certificate_for(bar.clone(), DerX509(FOO_KEY_BAR_NS1.to_vec()), vec![])
+        .expect_err("our certificate must be installed first");
+    assert!(err.is::<crate::creds::NoCertificate>(), "{}", err);
+
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    store
+        .set_certificate_for(bar.clone(), DerX509(FOO_KEY_BAR_NS1.to_vec()), vec![])
+        .expect("certificate must be valid");
+    assert_eq!(store.identities(), vec![bar.clone()]);
+    assert_eq!(
+        presented(BAR_NS1.name).expect("bar must be served"),
+        FOO_KEY_BAR_NS1
+    );
+    assert_eq!(
+        presented(FOO_NS1.name).expect("foo must be served"),
+        FOO_NS1.crt
+    );
+
+    // Names that no identity is selected by fall through to our own
+    // certificate, which isn't valid for them.
+    let baz: linkerd_identity::Name = "baz.ns1.serviceaccount.identity.linkerd.cluster.local"
+        .parse()
+        .unwrap();
+    presented(baz.as_str()).expect_err("unknown names must not be served");
+    assert_eq!(store.sni_mismatches(), 1);
+
+    store
+        .set_certificate_for(baz.clone(), DerX509(FOO_KEY_BAZ_NS1.to_vec()), vec![])
+        .expect("certificate must be valid");
+    assert_eq!(store.identities(), vec![bar.clone(), baz.clone()]);
+    assert_eq!(
+        presented(baz.as_str()).expect("baz must be served"),
+        FOO_KEY_BAZ_NS1
+    );
+
+    // Each certificate is replaced independently.
+    store
+        .set_certificate(DerX509(FOO_NS1_SHA384.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    assert_eq!(
+        presented(FOO_NS1.name).expect("foo must be served"),
+        FOO_NS1_SHA384
+    );
+    assert_eq!(
+        presented(BAR_NS1.name).expect("bar must be served"),
+        FOO_KEY_BAR_NS1
+    );
+
+    // Once removed, an identity falls through to our own certificate.
+    assert!(store.remove_certificate_for(&bar));
+    assert!(!store.remove_certificate_for(&bar));
+    assert_eq!(store.identities(), vec![baz]);
+    presented(BAR_NS1.name).expect_err("bar must no longer be served");
+}
+
+#[test]
+fn certificates_for_other_identities_are_validated() {
+    let (mut store, _rx) = watch(&FOO_NS1, Options::default());
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+
+    let err = store
+        .set_certificate_for(
+            "baz.ns1.serviceaccount.identity.linkerd.cluster.local"
+                .parse()
+                .unwrap(),
+            DerX509(FOO_KEY_BAR_NS1.to_vec()),
+            vec![],
+        )
+        .expect_err("certificates must name their identity");
+    assert!(err.is::<crate::creds::IdentityMismatch>(), "{}", err);
+
+    let err = store
+        .set_certificate_for(
+            BAR_NS1.name.parse().unwrap(),
+            DerX509(BAR_NS1.crt.to_vec()),
+            vec![],
+        )
+        .expect_err("certificates must be for our key");
+    assert!(err.is::<crate::creds::KeyMismatch>(), "{}", err);
+
+    let err = store
+        .set_certificate_for(
+            FOO_NS1.name.parse().unwrap(),
+            DerX509(FOO_NS1.crt.to_vec()),
+            vec![],
+        )
+        .expect_err("our own identity must be set with set_certificate");
+    assert!(err.is::<crate::creds::OwnIdentity>(), "{}", err);
+    assert!(store.identities().is_empty());
+}
+
+#[test]
+fn servers_for_other_identities_use_current_settings() {
+    let (mut store, rx) = watch(&FOO_NS1, Options::default());
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    store
+        .set_certificate_for(
+            BAR_NS1.name.parse().unwrap(),
+            DerX509(FOO_KEY_BAR_NS1.to_vec()),
+            vec![],
+        )
+        .expect("certificate must be valid");
+
+    // Settings that are changed after an identity is installed apply to its
+    // handshakes, as to our own.
+    let protocols = vec![b"h2".to_vec()];
+    store
+        .set_alpn(protocols.clone())
+        .expect("a certificate is installed");
+    let server = rx.server().config();
+    assert_eq!(server.alpn_protocols, protocols);
+    let (client, _) =
+        handshake(rx.new_client().config(), BAR_NS1.name, server).expect("handshake must succeed");
+    assert_eq!(client.alpn_protocol(), Some(&b"h2"[..]));
+}
+
+/// Names and certificates of other identities that `FOO_NS1`'s key is
+/// certified for.
+fn other_identities() -> [(linkerd_identity::Name, &'static [u8]); 3] {
+    let name = |sa: &str| {
+        format!("{}.ns1.serviceaccount.identity.linkerd.cluster.local", sa)
+            .parse()
+            .unwrap()
+    };
+    [
+        (name("bar"), FOO_KEY_BAR_NS1),
+        (name("baz"), FOO_KEY_BAZ_NS1),
+        (name("qux"), FOO_KEY_QUX_NS1),
+    ]
+}
+
+#[test]
+fn identities_beyond_the_limit_are_rejected() {
+    let (mut store, rx) = watch(
+        &FOO_NS1,
+        Options {
+            max_identities: Some(2),
+            ..Options::default()
+        },
+    );
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    let [(bar, bar_crt), (baz, baz_crt), (qux, qux_crt)] = other_identities();
+    for (name, crt) in [(&bar, bar_crt), (&baz, baz_crt)] {
+        store
+            .set_certificate_for(name.clone(), DerX509(crt.to_vec()), vec![])
+            .expect("identities within the limit must be installed");
+    }
+
+    let err = store
+        .set_certificate_for(qux.clone(), DerX509(qux_crt.to_vec()), vec![])
+        .expect_err("identities beyond the limit must be rejected");
+    assert!(err.is::<crate::creds::TooManyIdentities>(), "{}", err);
+    assert_eq!(store.identities(), vec![bar.clone(), baz.clone()]);
+    handshake(rx.new_client().config(), qux.as_str(), rx.server().config())
+        .expect_err("qux must not be served");
+
+    // Replacing an installed identity's certificate doesn't count against the
+    // limit, and removing one makes room for another.
+    store
+        .set_certificate_for(bar.clone(), DerX509(bar_crt.to_vec()), vec![])
+        .expect("installed identities may be replaced");
+    assert!(store.remove_certificate_for(&bar));
+    store
+        .set_certificate_for(qux.clone(), DerX509(qux_crt.to_vec()), vec![])
+        .expect("there must be room for qux");
+    assert_eq!(store.identities(), vec![baz, qux.clone()]);
+    handshake(rx.new_client().config(), qux.as_str(), rx.server().config())
+        .expect("qux must be served");
+}
+
+#[test]
+fn least_recently_used_identities_may_be_evicted() {
+    let (mut store, rx) = watch(
+        &FOO_NS1,
+        Options {
+            max_identities: Some(2),
+            identity_limit: IdentityLimit::EvictLeastRecentlyUsed,
+            ..Options::default()
+        },
+    );
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    let [(bar, bar_crt), (baz, baz_crt), (qux, qux_crt)] = other_identities();
+    let serves = |name: &linkerd_identity::Name| {
+        handshake(
+            rx.new_client().config(),
+            name.as_str(),
+            rx.server().config(),
+        )
+        .is_ok()
+    };
+    for (name, crt) in [(&bar, bar_crt), (&baz, baz_crt)] {
+        store
+            .set_certificate_for(name.clone(), DerX509(crt.to_vec()), vec![])
+            .expect("identities within the limit must be installed");
+    }
+
+    // bar was installed first, but was resolved more recently than baz.
+    assert!(serves(&bar));
+    store
+        .set_certificate_for(qux.clone(), DerX509(qux_crt.to_vec()), vec![])
+        .expect("baz must be evicted");
+    assert_eq!(store.identities(), vec![bar.clone(), qux.clone()]);
+    assert!(!serves(&baz), "baz must no longer be served");
+    assert!(serves(&qux));
+
+    // Identities whose certificates are being presented aren't evicted, even
+    // when they are least recently used.
+    let held = store.hold_identity(&bar).expect("bar must be installed");
+    store
+        .set_certificate_for(baz.clone(), DerX509(baz_crt.to_vec()), vec![])
+        .expect("qux must be evicted");
+    assert_eq!(store.identities(), vec![bar.clone(), baz.clone()]);
+
+    // Once every identity is being presented, new identities are rejected.
+    let _also_held = store.hold_identity(&baz).expect("baz must be installed");
+    let err = store
+        .set_certificate_for(qux, DerX509(qux_crt.to_vec()), vec![])
+        .expect_err("no identity may be evicted");
+    assert!(err.is::<crate::creds::TooManyIdentities>(), "{}", err);
+    assert_eq!(store.identities(), vec![bar, baz]);
+    drop(held);
+}
+
+#[test]
+fn resolver_decisions_are_recorded_on_spans() {
+    let (mut store, rx) = watch(&FOO_NS1, Options::default());
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    let field = |fields: &[(&str, String)], name: &str| {
+        fields
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| v.clone())
+    };
+
+    let spans = spans(|| {
+        handshake(rx.new_client().config(), FOO_NS1.name, rx.server().config())
+            .expect("handshake must succeed");
+    });
+    let (_, server) = spans
+        .iter()
+        .find(|(name, _)| *name == "resolve_server_cert")
+        .expect("server resolution must be spanned");
+    assert_eq!(
+        field(server, "sni").as_deref(),
+        Some(&*format!("Some({:?})", FOO_NS1.name))
+    );
+    assert!(field(server, "sigschemes").is_some());
+    assert_eq!(field(server, "selected").as_deref(), Some("true"));
+    assert_eq!(field(server, "unresolved"), None);
+    let (_, client) = spans
+        .iter()
+        .find(|(name, _)| *name == "resolve_client_cert")
+        .expect("client resolution must be spanned");
+    assert_eq!(field(client, "selected").as_deref(), Some("true"));
+
+    // Clients connecting by IP address send no SNI.
+    let spans = spans(|| {
+        handshake(rx.new_client().config(), "192.0.2.1", rx.server().config())
+            .expect_err("no certificate is resolved without SNI");
+    });
+    let (_, server) = spans
+        .iter()
+        .find(|(name, _)| *name == "resolve_server_cert")
+        .expect("server resolution must be spanned");
+    assert_eq!(field(server, "sni").as_deref(), Some("None"));
+    assert_eq!(field(server, "selected").as_deref(), Some("false"));
+    assert_eq!(field(server, "unresolved").as_deref(), Some("\"no_sni\""));
+}
+
+#[test]
+fn receiver_reports_configured_algorithms() {
+    let (_, rx) = watch(&FOO_NS1, Options::default());
+    let suites = rx
+        .cipher_suites()
+        .iter()
+        .map(|s| s.suite())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        suites,
+        [rustls::CipherSuite::TLS13_CHACHA20_POLY1305_SHA256]
+    );
+    let groups = rx.kx_groups().iter().map(|g| g.name).collect::<Vec<_>>();
+    assert_eq!(
+        groups,
+        [
+            rustls::NamedGroup::X25519,
+            rustls::NamedGroup::secp256r1,
+            rustls::NamedGroup::secp384r1,
+        ]
+    );
+}
+
+#[test]
+fn configs_may_be_republished() {
+    let (mut store, rx) = watch(&FOO_NS1, Options::default());
+    let client = rx.client_config();
+    let server = rx.server_config();
+    let err = store.republish().expect_err("nothing is installed yet");
+    assert!(err.is::<crate::creds::NoCertificate>(), "{}", err);
+    assert!(Arc::ptr_eq(&client, &rx.client_config()));
+    assert!(Arc::ptr_eq(&server, &rx.server_config()));
+
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    let client = rx.client_config();
+    let server = rx.server_config();
+    let leaf = rx.identity_digest();
+    store.republish().expect("a certificate is installed");
+    assert!(!Arc::ptr_eq(&client, &rx.client_config()));
+    assert!(!Arc::ptr_eq(&server, &rx.server_config()));
+    assert_eq!(rx.identity_digest(), leaf);
+
+    let (_, server) = handshake(rx.new_client().config(), FOO_NS1.name, rx.server().config())
+        .expect("handshake must succeed");
+    assert_eq!(
+        server.peer_certificates().unwrap()[0].0,
+        FOO_NS1.crt.to_vec()
+    );
+}
+
+#[test]
+fn receiver_reports_readiness() {
+    // A clock that can be moved past the leaf's expiry.
+    let now = Arc::new(parking_lot::Mutex::new(std::time::SystemTime::now()));
+    let clock = now.clone();
+    let (mut store, rx) = watch(
+        &FOO_NS1,
+        Options {
+            clock: Some(Arc::new(move || *clock.lock())),
+            ..Default::default()
+        },
+    );
+    assert_eq!(rx.readiness(), Readiness::NotProvisioned);
+
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    assert_eq!(rx.readiness(), Readiness::Ready);
+
+    // The test leaf expires when ca1 does, in 2031.
+    *now.lock() = std::time::UNIX_EPOCH + Duration::from_secs(4_000_000_000);
+    assert_eq!(rx.readiness(), Readiness::Expired);
+}
+
+#[test]
+fn receivers_report_the_installed_identity_digest() {
+    let (mut store0, rx0) = watch(&FOO_NS1, Options::default());
+    let (mut store1, rx1) = watch(&FOO_NS1, Options::default());
+    assert_eq!(rx0.identity_digest(), None);
+
+    for store in [&mut store0, &mut store1] {
+        store
+            .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+            .expect("certificate must be valid");
+    }
+    let digest = rx0.identity_digest();
+    assert!(digest.is_some());
+    assert_eq!(digest, rx1.identity_digest());
+
+    store1
+        .set_certificate(DerX509(FOO_NS1_OCSP.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    assert_eq!(rx0.identity_digest(), digest);
+    assert_ne!(rx1.identity_digest(), digest);
+}
+
+#[test]
+fn receivers_report_the_presented_leaf_fingerprint() {
+    let (mut store, rx) = watch(&FOO_NS1, Options::default());
+    assert_eq!(rx.leaf_fingerprint(), None);
+
+    for crt in [FOO_NS1.crt, FOO_NS1_OCSP] {
+        store
+            .set_certificate(DerX509(crt.to_vec()), vec![], expiry())
+            .expect("certificate must be valid");
+        let (client, _) = handshake(rx.new_client().config(), FOO_NS1.name, rx.server().config())
+            .expect("handshake must succeed");
+        let presented = client
+            .peer_certificates()
+            .expect("server must present a chain")[0]
+            .clone();
+        assert_eq!(presented.as_ref(), crt);
+        let sha256 = ring::digest::digest(&ring::digest::SHA256, presented.as_ref());
+        assert_eq!(rx.leaf_fingerprint(), Some(hex::encode(sha256)));
+    }
+}
+
+#[test]
+fn receivers_report_the_installed_cert_expiry() {
+    let (mut store, rx) = watch(&FOO_NS1, Options::default());
+    let mut updates = rx.leaf_updates();
+    assert_eq!(rx.cert_expiry(), None);
+    assert!(updates.borrow_and_update().is_none());
+
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    let not_after = std::time::UNIX_EPOCH + Duration::from_secs(1949081040);
+    assert_eq!(rx.cert_expiry(), Some(not_after));
+    assert!(updates.has_changed().unwrap());
+    let leaf = (*updates.borrow_and_update()).expect("leaf must be installed");
+    assert_eq!(leaf.expiry(), not_after);
+    assert_eq!(Some(leaf.sha256()), rx.identity_digest());
+
+    // Each rotation is observed.
+    store
+        .set_certificate(DerX509(FOO_NS1_OCSP.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    assert!(updates.has_changed().unwrap());
+    assert_ne!(*updates.borrow_and_update(), Some(leaf));
+}
+
+#[test]
+fn cert_expiry_is_published_with_the_server_config() {
+    let (mut store, rx) = watch(&FOO_NS1, Options::default());
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+
+    // While certificates with different expiries are installed, every server
+    // configuration that is observed presents the leaf it was published with.
+    let expiries = [
+        (
+            FOO_NS1.crt,
+            std::time::UNIX_EPOCH + Duration::from_secs(1949081040),
+        ),
+        (
+            FOO_NS1_SHA384,
+            std::time::UNIX_EPOCH + Duration::from_secs(2107346606),
+        ),
+    ];
+    let done = Arc::new(AtomicBool::new(false));
+    let reader = {
+        let done = done.clone();
+        std::thread::spawn(move || loop {
+            let state = rx.server_state();
+            let leaf = state.leaf.expect("a certificate is installed");
+            let (client, _) = handshake(rx.new_client().config(), FOO_NS1.name, state.config)
+                .expect("handshake must succeed");
+            let presented = &client.peer_certificates().expect("server must present")[0];
+            let (_, not_after) = expiries
+                .iter()
+                .find(|(crt, _)| *crt == presented.as_ref())
+                .expect("an installed certificate must be presented");
+            assert_eq!(leaf.expiry(), *not_after);
+            let sha256 = ring::digest::digest(&ring::digest::SHA256, presented.as_ref());
+            assert_eq!(&leaf.sha256()[..], sha256.as_ref());
+            if done.load(Ordering::SeqCst) {
+                return;
+            }
+        })
+    };
+    for (crt, _) in expiries.iter().cycle().take(200) {
+        store
+            .set_certificate(DerX509(crt.to_vec()), vec![], expiry())
+            .expect("certificate must be valid");
+    }
+    done.store(true, Ordering::SeqCst);
+    reader.join().expect("reader must not panic");
+}
+
+#[test]
+fn expiry_warnings_are_issued_within_the_threshold() {
+    // 2031-10-06T19:24:00Z
+    let not_after_secs = 1949081040;
+    let not_after = std::time::UNIX_EPOCH + Duration::from_secs(not_after_secs);
+    let now = Arc::new(AtomicU64::new(not_after_secs - 48 * 60 * 60));
+    let warnings = Arc::new(AtomicUsize::new(0));
+    let options = |expiry_warning| {
+        let now = now.clone();
+        let warnings = warnings.clone();
+        Options {
+            clock: Some(Arc::new(move || {
+                std::time::UNIX_EPOCH + Duration::from_secs(now.load(Ordering::SeqCst))
+            })),
+            expiry_warning,
+            on_expiry_warning: Some(Arc::new(move |expiry| {
+                assert_eq!(expiry, not_after);
+                warnings.fetch_add(1, Ordering::SeqCst);
+            })),
+            ..Options::default()
+        }
+    };
+
+    // By default, warnings are issued within 24 hours of expiry.
+    let mut store = load_with_options(&FOO_NS1, options(None));
+    assert_eq!(store.check_expiry(), None, "nothing is installed");
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    assert_eq!(store.check_expiry(), None);
+    assert_eq!(warnings.load(Ordering::SeqCst), 0);
+
+    now.store(not_after_secs - 12 * 60 * 60, Ordering::SeqCst);
+    assert_eq!(store.check_expiry(), Some(not_after));
+    assert_eq!(warnings.load(Ordering::SeqCst), 1);
+
+    // Certificates that are installed within the threshold are warned about
+    // immediately.
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    assert_eq!(warnings.load(Ordering::SeqCst), 2);
+
+    let mut store = load_with_options(&FOO_NS1, options(Some(Duration::from_secs(60 * 60))));
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    assert_eq!(store.check_expiry(), None);
+    now.store(not_after_secs, Ordering::SeqCst);
+    assert_eq!(store.check_expiry(), Some(not_after));
+    assert_eq!(warnings.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn fips_mode_rejects_unapproved_cipher_suites() {
+    let roots_pem = std::str::from_utf8(FOO_NS1.trust_anchors).expect("valid PEM");
+    let err = crate::creds::watch_with_options(
+        FOO_NS1.name.parse().unwrap(),
+        roots_pem,
+        FOO_NS1.key,
+        FOO_NS1_CSR,
+        Options {
+            fips: true,
+            cipher_suites: Some(vec![
+                CipherSuite::Tls13Aes128GcmSha256,
+                CipherSuite::Tls13Chacha20Poly1305Sha256,
+            ]),
+            ..Options::default()
+        },
+    )
+    .err()
+    .expect("ChaCha20 must not be allowed in FIPS mode");
+    assert!(err.is::<crate::creds::NotFipsApproved>());
+}
+
+#[test]
+fn cipher_suites_may_be_configured() {
+    let suites = ["TLS13_AES_256_GCM_SHA384", "TLS13_AES_128_GCM_SHA256"]
+        .iter()
+        .map(|s| s.parse())
+        .collect::<Result<Vec<CipherSuite>, _>>()
+        .expect("suites must be known");
+    let (mut store, rx) = watch(
+        &FOO_NS1,
+        Options {
+            cipher_suites: Some(suites),
+            ..Options::default()
+        },
+    );
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    let (client, _) = handshake(rx.new_client().config(), FOO_NS1.name, rx.server().config())
+        .expect("handshake must succeed");
+    assert_eq!(
+        client.negotiated_cipher_suite().map(|s| s.suite()),
+        Some(rustls::CipherSuite::TLS13_AES_256_GCM_SHA384)
+    );
+
+    let err = "TLS_RSA_WITH_RC4_128_SHA"
+        .parse::<CipherSuite>()
+        .expect_err("suite must be unknown");
+    assert_eq!(
+        err.to_string(),
+        "unknown cipher suite \"TLS_RSA_WITH_RC4_128_SHA\""
+    );
+
+    let roots_pem = std::str::from_utf8(FOO_NS1.trust_anchors).expect("valid PEM");
+    let err = crate::creds::watch_with_options(
+        FOO_NS1.name.parse().unwrap(),
+        roots_pem,
+        FOO_NS1.key,
+        FOO_NS1_CSR,
+        Options {
+            cipher_suites: Some(vec![]),
+            ..Options::default()
+        },
+    )
+    .err()
+    .expect("an empty set of suites must be rejected");
+    assert!(err.is::<crate::creds::NoCipherSuites>(), "{}", err);
+}
+
+#[test]
+fn tls12_is_only_negotiated_when_enabled() {
+    // A client that only supports TLS 1.2.
+    let tls12_client = || {
+        let mut roots = rustls::RootCertStore::empty();
+        roots
+            .add(&rustls::Certificate(pem_to_der(FOO_NS1.trust_anchors)))
+            .expect("root must be valid");
+        Arc::new(
+            rustls::ClientConfig::builder()
+                .with_safe_default_cipher_suites()
+                .with_safe_default_kx_groups()
+                .with_protocol_versions(&[&rustls::version::TLS12])
+                .expect("client config must be valid")
+                .with_root_certificates(roots)
+                .with_no_client_auth(),
+        )
+    };
+
+    let (mut store, rx) = watch(&FOO_NS1, Options::default());
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    let err = handshake(tls12_client(), FOO_NS1.name, rx.server().config())
+        .err()
+        .expect("TLS 1.2 must not be negotiated by default");
+    assert!(
+        matches!(err, rustls::Error::PeerIncompatible(_)),
+        "{:?}",
+        err
+    );
+
+    let (mut store, rx) = watch(
+        &FOO_NS1,
+        Options {
+            tls12: true,
+            ..Options::default()
+        },
+    );
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    let (client, _) = handshake(tls12_client(), FOO_NS1.name, rx.server().config())
+        .expect("handshake must succeed");
+    assert_eq!(
+        client.protocol_version(),
+        Some(rustls::ProtocolVersion::TLSv1_2)
+    );
+    assert_eq!(
+        client.negotiated_cipher_suite().map(|s| s.suite()),
+        Some(rustls::CipherSuite::TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256)
+    );
+
+    // The store's own clients still prefer TLS 1.3.
+    let (client, _) = handshake(rx.new_client().config(), FOO_NS1.name, rx.server().config())
+        .expect("handshake must succeed");
+    assert_eq!(
+        client.protocol_version(),
+        Some(rustls::ProtocolVersion::TLSv1_3)
+    );
+
+    // TLS 1.2 suites may not be configured unless TLS 1.2 is enabled.
+    let roots_pem = std::str::from_utf8(FOO_NS1.trust_anchors).expect("valid PEM");
+    let err = crate::creds::watch_with_options(
+        FOO_NS1.name.parse().unwrap(),
+        roots_pem,
+        FOO_NS1.key,
+        FOO_NS1_CSR,
+        Options {
+            cipher_suites: Some(vec![CipherSuite::Tls12EcdheEcdsaWithAes128GcmSha256]),
+            ..Options::default()
+        },
+    )
+    .err()
+    .expect("TLS 1.2 suites must be rejected");
+    assert!(err.is::<crate::creds::Tls12NotEnabled>(), "{}", err);
+}
+
+#[test]
+fn fips_mode_uses_approved_algorithms() {
+    let (mut store, rx) = watch(
+        &FOO_NS1,
+        Options {
+            fips: true,
+            ..Options::default()
+        },
+    );
+    let suites = rx
+        .cipher_suites()
+        .iter()
+        .map(|s| s.suite())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        suites,
+        [
+            rustls::CipherSuite::TLS13_AES_128_GCM_SHA256,
+            rustls::CipherSuite::TLS13_AES_256_GCM_SHA384,
+        ]
+    );
+    let groups = rx.kx_groups().iter().map(|g| g.name).collect::<Vec<_>>();
+    assert_eq!(
+        groups,
+        [rustls::NamedGroup::secp256r1, rustls::NamedGroup::secp384r1]
+    );
+
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    let (client, _) = handshake(rx.new_client().config(), FOO_NS1.name, rx.server().config())
+        .expect("handshake must succeed");
+    assert_eq!(
+        client.negotiated_cipher_suite().map(|s| s.suite()),
+        Some(rustls::CipherSuite::TLS13_AES_128_GCM_SHA256)
+    );
+}
+
+#[test]
+fn self_resolve_check_selects_installed_cert() {
+    let mut store = load(&FOO_NS1);
+    assert!(store
+        .self_resolve_check()
+        .unwrap_err()
+        .is::<crate::creds::NoCertificate>());
+
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    store
+        .self_resolve_check()
+        .expect("installed certificate must resolve for our identity");
+
+    store.set_resolver_unchecked(vec![rustls::Certificate(BAR_NS1.crt.to_vec())]);
+    assert!(store
+        .self_resolve_check()
+        .unwrap_err()
+        .is::<crate::creds::UnresolvableIdentity>());
+}
+
+#[test]
+fn resolution_failures_are_counted_by_reason() {
+    /// Accepts any server certificate, but only offers RSA signature schemes.
+    struct RsaOnly;
+
+    impl rustls::client::ServerCertVerifier for RsaOnly {
+        fn verify_server_cert(
+            &self,
+            _: &rustls::Certificate,
+            _: &[rustls::Certificate],
+            _: &rustls::ServerName,
+            _: &mut dyn Iterator<Item = &[u8]>,
+            _: &[u8],
+            _: std::time::SystemTime,
+        ) -> Result<rustls::client::ServerCertVerified, rustls::Error> {
+            Ok(rustls::client::ServerCertVerified::assertion())
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+            vec![rustls::SignatureScheme::RSA_PSS_SHA256]
+        }
+    }
+
+    let metrics = Metrics::default();
+    let (mut store, rx) = watch(
+        &FOO_NS1,
+        Options {
+            metrics: metrics.clone(),
+            ..Options::default()
+        },
+    );
+    let connect = |name: &str| handshake(rx.new_client().config(), name, rx.server().config());
+
+    connect(FOO_NS1.name).expect_err("no certificate is installed yet");
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    connect(FOO_NS1.name).expect("handshake must succeed");
+    // Clients don't send IP addresses via SNI.
+    connect("192.0.2.1").expect_err("clients must request a name");
+    connect(BAR_NS1.name).expect_err("certificate is not valid for bar.ns1");
+    assert!(!store.resolves_sni("-foo.ns1.serviceaccount.identity.linkerd.cluster.local"));
+    let rsa_only = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(RsaOnly))
+        .with_no_client_auth();
+    handshake(Arc::new(rsa_only), FOO_NS1.name, rx.server().config())
+        .expect_err("our ECDSA key can't sign for RSA-only clients");
+
+    assert_eq!(
+        store.resolution_failures(),
+        ResolutionFailures {
+            no_sni: 1,
+            invalid_sni: 1,
+            sni_mismatch: 1,
+            unsupported_sigscheme: 1,
+            no_cert_yet: 1,
+        }
+    );
+    let text = metrics.as_display().to_string();
+    for reason in [
+        "no_sni",
+        "invalid_sni",
+        "sni_mismatch",
+        "unsupported_sigscheme",
+        "no_cert_yet",
+    ] {
+        let series = format!(
+            "identity_cert_resolution_failures_total{{reason=\"{}\"}} 1\n",
+            reason
+        );
+        assert!(text.contains(&series), "missing {:?} in:\n{}", series, text);
+    }
+}
+
+#[test]
+fn clients_without_sni_may_be_served() {
+    for serve_without_sni in [false, true] {
+        let (mut store, rx) = watch(
+            &FOO_NS1,
+            Options {
+                serve_without_sni,
+                ..Options::default()
+            },
+        );
+        store
+            .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+            .expect("certificate must be valid");
+
+        // Clients that send SNI are served as usual, in either mode...
+        handshake(rx.new_client().config(), FOO_NS1.name, rx.server().config())
+            .expect("handshake must succeed");
+        handshake(rx.new_client().config(), BAR_NS1.name, rx.server().config())
+            .expect_err("certificate is not valid for bar.ns1");
+
+        // ...while those that don't are only served when configured.
+        let mut no_sni = (*rx.new_client().config()).clone();
+        no_sni.enable_sni = false;
+        let res = handshake(Arc::new(no_sni), FOO_NS1.name, rx.server().config());
+        assert_eq!(res.is_ok(), serve_without_sni, "{:?}", res.err());
+        assert_eq!(
+            store.resolution_failures().no_sni,
+            if serve_without_sni { 0 } else { 1 }
+        );
+    }
+}
+
+#[test]
+fn invalid_sni_names_resolve_no_certificate() {
+    let mut store = load(&FOO_NS1);
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    assert!(store.resolves_sni(FOO_NS1.name));
+
+    for sni in [
+        "",
+        ".",
+        "foo..ns1.serviceaccount.identity.linkerd.cluster.local",
+        "foo ns1.serviceaccount.identity.linkerd.cluster.local",
+        "*.ns1.serviceaccount.identity.linkerd.cluster.local",
+        "f\u{f6}\u{f6}.ns1.serviceaccount.identity.linkerd.cluster.local",
+        "foo\0.ns1.serviceaccount.identity.linkerd.cluster.local",
+        "-foo.ns1.serviceaccount.identity.linkerd.cluster.local",
+    ] {
+        assert!(!store.resolves_sni(sni), "{:?} must not resolve", sni);
+    }
+    // Invalid names are not counted as mismatches with the certificate.
+    assert_eq!(store.sni_mismatches(), 0);
+}
+
+#[test]
+fn empty_certificates_are_rejected() {
+    let mut store = load(&FOO_NS1);
+
+    let err = store
+        .set_certificate(DerX509(Vec::new()), vec![], expiry())
+        .expect_err("empty leaves must be rejected");
+    assert!(err.is::<crate::creds::EmptyCertificate>(), "{}", err);
+    assert_eq!(err.to_string(), "certificate 0 in the chain is empty");
+
+    let err = store
+        .set_certificate(
+            DerX509(FOO_NS1.crt.to_vec()),
+            vec![DerX509(Vec::new())],
+            expiry(),
+        )
+        .expect_err("empty intermediates must be rejected");
+    assert!(err.is::<crate::creds::EmptyCertificate>(), "{}", err);
+    assert_eq!(err.to_string(), "certificate 1 in the chain is empty");
+
+    let installed = store.install_chain(vec![], expiry());
+    assert!(
+        matches!(installed, Install::ValidationFailed(ref e) if e.is::<crate::creds::EmptyChain>()),
+        "{:?}",
+        installed
+    );
+
+    // The store remains usable.
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+}
+
+#[test]
+fn peer_chains_are_verified_for_the_expected_name() {
+    let store = load(&FOO_NS1);
+    let bar = [rustls::Certificate(BAR_NS1.crt.to_vec())];
+    let bar_name = BAR_NS1.name.parse().unwrap();
+
+    store
+        .verify_peer(&bar, &bar_name)
+        .expect("bar's certificate must be valid for its name");
+    store
+        .verify_peer(&bar, &FOO_NS1.name.parse().unwrap())
+        .expect_err("bar's certificate must not be valid for our name");
+    store
+        .verify_peer(
+            &[rustls::Certificate(FOO_NS1_CA2.crt.to_vec())],
+            &FOO_NS1_CA2.name.parse().unwrap(),
+        )
+        .expect_err("certificates from other roots must be rejected");
+    let err = store
+        .verify_peer(&[], &bar_name)
+        .expect_err("empty chains must be rejected");
+    assert!(err.is::<crate::creds::EmptyChain>(), "{}", err);
+}
+
+#[test]
+fn verify_with_pool_discovers_intermediates() {
+    let store = load(&FOO_NS1);
+    let leaf = DerX509(FOO_NS1_INT_B.to_vec());
+    let int_a = DerX509(pem_to_der(INT_A));
+    let int_b = DerX509(pem_to_der(INT_B));
+    let unrelated = DerX509(pem_to_der(NS1_CA));
+
+    store
+        .verify_with_pool(
+            &leaf,
+            &[int_a.clone(), unrelated.clone(), int_b.clone()],
+            &[],
+        )
+        .expect("a path through int-b must be found");
+    store
+        .verify_with_pool(&leaf, &[int_a, unrelated.clone()], &[])
+        .expect_err("no path exists without int-b");
+
+    // An intermediate may itself be trusted, even when it does not chain to
+    // the configured roots.
+    let store = load(&FOO_NS1_CA2);
+    store
+        .verify_with_pool(&leaf, &[unrelated.clone()], &[])
+        .expect_err("int-b does not chain to ca2");
+    store
+        .verify_with_pool(&leaf, &[unrelated], &[int_b])
+        .expect("int-b must be accepted as a trust anchor");
+}
+
+#[test]
+fn path_len_constraints_are_enforced() {
+    let int = DerX509(pem_to_der(INT_PATHLEN0));
+    let sub = DerX509(pem_to_der(INT_PATHLEN0_SUB));
+
+    let mut store = load(&FOO_NS1);
+    store
+        .set_certificate(
+            DerX509(FOO_NS1_INT_PATHLEN0.to_vec()),
+            vec![int.clone()],
+            expiry(),
+        )
+        .expect("int-pathlen0 may issue leaves");
+    let err = store
+        .set_certificate(
+            DerX509(FOO_NS1_INT_PATHLEN0_SUB.to_vec()),
+            vec![sub.clone(), int.clone()],
+            expiry(),
+        )
+        .expect_err("int-pathlen0 may not issue CAs");
+    assert!(err.is::<crate::creds::PathLenExceeded>(), "{}", err);
+
+    // Peers presenting such a chain are rejected, too.
+    store
+        .verify_with_pool(&DerX509(FOO_NS1_INT_PATHLEN0.to_vec()), &[int.clone()], &[])
+        .expect("int-pathlen0 may issue leaves");
+    store
+        .verify_with_pool(
+            &DerX509(FOO_NS1_INT_PATHLEN0_SUB.to_vec()),
+            &[sub, int],
+            &[],
+        )
+        .expect_err("int-pathlen0 may not issue CAs");
+}
+
+#[test]
+fn certificates_outside_their_validity_period_are_rejected() {
+    let mut store = load(&FOO_NS1);
+
+    let err = store
+        .set_certificate(DerX509(FOO_NS1_EXPIRED.crt.to_vec()), vec![], expiry())
+        .expect_err("expired certificates must be rejected");
+    assert!(err.is::<crate::creds::Expired>(), "{}", err);
+    assert!(
+        err.to_string().contains("expired at 2021-01-01T00:00:00Z"),
+        "{}",
+        err
+    );
+
+    let err = store
+        .set_certificate(DerX509(FOO_NS1_NOT_YET_VALID.to_vec()), vec![], expiry())
+        .expect_err("certificates that aren't valid yet must be rejected");
+    assert!(err.is::<crate::creds::NotYetValid>(), "{}", err);
+    assert!(
+        err.to_string()
+            .contains("not valid until 2040-01-01T00:00:00Z"),
+        "{}",
+        err
+    );
+
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("valid certificates must be accepted");
+}
+
+#[test]
+fn certificates_are_validated_against_the_configured_clock() {
+    let at = |secs| {
+        let now = std::time::UNIX_EPOCH + Duration::from_secs(secs);
+        load_with_options(
+            &FOO_NS1,
+            Options {
+                clock: Some(Arc::new(move || now)),
+                ..Options::default()
+            },
+        )
+    };
+
+    // 2020-07-01T00:00:00Z, while the expired certificate was valid.
+    at(1_593_561_600)
+        .set_certificate(DerX509(FOO_NS1_EXPIRED.crt.to_vec()), vec![], expiry())
+        .expect("the certificate was valid");
+
+    // 2041-01-01T00:00:00Z, once the other certificate is valid.
+    let mut store = at(2_240_611_200);
+    store
+        .set_certificate(DerX509(FOO_NS1_NOT_YET_VALID.to_vec()), vec![], expiry())
+        .expect("the certificate is valid");
+    let err = store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect_err("the certificate has expired");
+    assert!(err.is::<crate::creds::Expired>(), "{}", err);
+    assert!(
+        err.to_string().contains("it is now 2041-01-01T00:00:00Z"),
+        "{}",
+        err
+    );
+    store
+        .verify_peer(
+            &[rustls::Certificate(FOO_NS1_NOT_YET_VALID.to_vec())],
+            &FOO_NS1.name.parse().unwrap(),
+        )
+        .expect("peers are verified at the same time");
+}
+
+#[tokio::test]
+async fn connections_carry_resolved_peer_identities() {
+    use linkerd_stack::{NewService, Service};
+
+    let (mut foo, foo_rx) = watch(&FOO_NS1, Options::default());
+    foo.set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    let (mut bar, bar_rx) = watch(&BAR_NS1, Options::default());
+    bar.set_certificate(DerX509(BAR_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+
+    let (client_io, server_io) = linkerd_io::duplex(64 * 1024);
+    let mut connect = foo_rx
+        .new_client()
+        .new_service(linkerd_tls::ClientTls::from(linkerd_tls::ServerId(
+            BAR_NS1.name.parse().unwrap(),
+        )));
+    let (client, (_, server)) =
+        tokio::try_join!(connect.call(client_io), bar_rx.server().call(server_io))
+            .expect("handshake must succeed");
+
+    assert_eq!(
+        client.server_identity().map(|id| id.name().as_str()),
+        Some(BAR_NS1.name)
+    );
+    assert_eq!(
+        server.client_identity().map(|id| id.name().as_str()),
+        Some(FOO_NS1.name)
+    );
+}
+
+#[test]
+fn client_identities_are_read_from_server_connections() {
+    let (mut foo, foo_rx) = watch(&FOO_NS1, Options::default());
+    foo.set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    let (mut bar, bar_rx) = watch(&BAR_NS1, Options::default());
+    bar.set_certificate(DerX509(BAR_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+
+    let client_identity = |client: Arc<rustls::ClientConfig>| {
+        let (_, server) = handshake(client, BAR_NS1.name, bar_rx.server().config())
+            .expect("handshake must succeed");
+        match server {
+            rustls::Connection::Server(ref conn) => crate::client_identity(conn),
+            rustls::Connection::Client(_) => unreachable!("server must accept"),
+        }
+    };
+    assert_eq!(
+        client_identity(foo_rx.new_client().config()),
+        Some(FOO_NS1.name.parse().unwrap())
+    );
+    assert_eq!(
+        client_identity(foo_rx.client_config_without_client_auth()),
+        None
+    );
+}
+
+#[cfg(feature = "test-util")]
+#[test]
+fn expired_test_credentials_present_an_expired_certificate() {
+    let (_store, rx) = crate::creds::for_test_expired();
+    assert_eq!(rx.readiness(), Readiness::Expired);
+    assert!(rx.cert_expiry().expect("a certificate is installed") < std::time::SystemTime::now());
+
+    let (_, client_rx) = watch(&BAR_NS1, Options::default());
+    handshake(
+        client_rx.new_client().config(),
+        FOO_NS1_EXPIRED.name,
+        rx.server().config(),
+    )
+    .expect_err("clients must reject the expired certificate");
+}
+
+#[cfg(feature = "test-util")]
+#[test]
+fn mutual_test_helpers_resolve_both_identities() {
+    let (foo, bar) = crate::creds::mutual_for_test(&FOO_NS1, &BAR_NS1);
+    let (server_id, client_id) =
+        crate::creds::handshake_for_test(&foo, &bar).expect("handshake must succeed");
+    assert_eq!(
+        server_id.map(|id| id.to_string()).as_deref(),
+        Some(BAR_NS1.name)
+    );
+    assert_eq!(
+        client_id.map(|id| id.to_string()).as_deref(),
+        Some(FOO_NS1.name)
+    );
+}
+
+#[cfg(feature = "test-util")]
+#[test]
+fn ed25519_identities_complete_handshakes() {
+    // The Ed25519 identity authenticates both as a server and as a client.
+    let (foo, bar) = crate::creds::mutual_for_test(&FOO_NS1_ED25519, &BAR_NS1);
+    let (server_id, client_id) =
+        crate::creds::handshake_for_test(&bar, &foo).expect("handshake must succeed");
+    assert_eq!(
+        server_id.map(|id| id.to_string()).as_deref(),
+        Some(FOO_NS1_ED25519.name)
+    );
+    assert_eq!(
+        client_id.map(|id| id.to_string()).as_deref(),
+        Some(BAR_NS1.name)
+    );
+    let (_, client_id) =
+        crate::creds::handshake_for_test(&foo, &bar).expect("handshake must succeed");
+    assert_eq!(
+        client_id.map(|id| id.to_string()).as_deref(),
+        Some(FOO_NS1_ED25519.name)
+    );
+}
+
+#[test]
+fn peers_may_be_pinned_by_serial() {
+    // The serial numbers of the FOO_NS1 and BAR_NS1 certificates.
+    let foo_serial = hex::decode("7B91E518DB98CFAC08363B77DFB183B902FB4AFF").unwrap();
+    let bar_serial = hex::decode("32FE2E3C204B8B20548A7D937522CD02C8E80F27").unwrap();
+
+    let (mut bar, bar_rx) = watch(&BAR_NS1, Options::default());
+    bar.set_certificate(DerX509(BAR_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+
+    for (allowed, ok) in [(bar_serial, true), (foo_serial, false)] {
+        let (mut foo, foo_rx) = watch(
+            &FOO_NS1,
+            Options {
+                allowed_serials: Some(vec![allowed]),
+                ..Options::default()
+            },
+        );
+        // Our own certificate need not be allowed.
+        foo.set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+            .expect("certificate must be valid");
+
+        let res = handshake(
+            foo_rx.new_client().config(),
+            BAR_NS1.name,
+            bar_rx.server().config(),
+        );
+        assert_eq!(res.is_ok(), ok, "{:?}", res.err());
+    }
+}
+
+#[test]
+fn server_names_may_be_constrained_to_permitted_domains() {
+    let (mut foo, foo_rx) = watch(&FOO_NS1, Options::default());
+    foo.set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    // Also names bar.ns1.
+    let (mut extra, extra_rx) = watch(&FOO_NS1, Options::default());
+    extra
+        .set_certificate(DerX509(FOO_NS1_EXTRA_SAN.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+
+    for (domain, foo_ok, extra_ok) in [
+        ("serviceaccount.identity.linkerd.cluster.local", true, true),
+        (
+            "*.ns1.serviceaccount.identity.linkerd.cluster.local",
+            true,
+            true,
+        ),
+        ("SERVICEACCOUNT.identity.linkerd.cluster.local.", true, true),
+        (FOO_NS1.name, true, false),
+        (
+            "*.foo.ns1.serviceaccount.identity.linkerd.cluster.local",
+            false,
+            false,
+        ),
+        ("account.identity.linkerd.cluster.local", false, false),
+        ("*.svc.cluster.local", false, false),
+    ] {
+        let options = Options {
+            permitted_dns_domains: Some(vec![domain.to_string()]),
+            ..Options::default()
+        };
+        let (mut client, client_rx) = watch(&BAR_NS1, options);
+        client
+            .set_certificate(DerX509(BAR_NS1.crt.to_vec()), vec![], expiry())
+            .expect("certificate must be valid");
+        for (server_rx, ok) in [(&foo_rx, foo_ok), (&extra_rx, extra_ok)] {
+            let res = handshake(
+                client_rx.new_client().config(),
+                FOO_NS1.name,
+                server_rx.server().config(),
+            );
+            assert_eq!(res.is_ok(), ok, "{}: {:?}", domain, res.err());
+        }
+    }
+
+    let err = crate::creds::watch_with_options(
+        BAR_NS1.name.parse().unwrap(),
+        std::str::from_utf8(BAR_NS1.trust_anchors).expect("valid PEM"),
+        BAR_NS1.key,
+        BAR_NS1.csr,
+        Options {
+            permitted_dns_domains: Some(vec![]),
+            ..Options::default()
+        },
+    )
+    .err()
+    .expect("an empty set of domains must be rejected");
+    assert!(err.is::<crate::creds::NoPermittedDnsDomains>(), "{}", err);
+}
+
+#[test]
+fn p384_identities_interoperate_with_p256_peers() {
+    let (mut foo, foo_rx) = watch_raw(
+        FOO_NS1.name,
+        FOO_NS1.trust_anchors,
+        FOO_NS1_P384_KEY,
+        Options::default(),
+    );
+    foo.set_certificate(DerX509(FOO_NS1_P384.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    let (mut bar, bar_rx) = watch(&BAR_NS1, Options::default());
+    bar.set_certificate(DerX509(BAR_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+
+    // Each side signs with the scheme that matches its own key.
+    let (client, server) = handshake(
+        bar_rx.new_client().config(),
+        FOO_NS1.name,
+        foo_rx.server().config(),
+    )
+    .expect("handshake must succeed");
+    assert_eq!(
+        client.peer_certificates().unwrap()[0].0,
+        FOO_NS1_P384.to_vec()
+    );
+    assert_eq!(
+        server.peer_certificates().unwrap()[0].0,
+        BAR_NS1.crt.to_vec()
+    );
+
+    let (client, server) = handshake(
+        foo_rx.new_client().config(),
+        BAR_NS1.name,
+        bar_rx.server().config(),
+    )
+    .expect("handshake must succeed");
+    assert_eq!(
+        client.peer_certificates().unwrap()[0].0,
+        BAR_NS1.crt.to_vec()
+    );
+    assert_eq!(
+        server.peer_certificates().unwrap()[0].0,
+        FOO_NS1_P384.to_vec()
+    );
+}
+
+#[test]
+fn supported_signature_schemes_are_those_keys_sign_with() {
+    use rustls::SignatureScheme::*;
+
+    for (key, expected) in [
+        (FOO_NS1.key, vec![ECDSA_NISTP256_SHA256]),
+        (FOO_NS1_P384_KEY, vec![ECDSA_NISTP384_SHA384]),
+        (FOO_NS1_ED25519.key, vec![ED25519]),
+        (
+            FOO_NS1_RSA_KEY,
+            vec![
+                RSA_PSS_SHA512,
+                RSA_PSS_SHA384,
+                RSA_PSS_SHA256,
+                RSA_PKCS1_SHA512,
+                RSA_PKCS1_SHA384,
+                RSA_PKCS1_SHA256,
+            ],
+        ),
+    ] {
+        let (store, _) = watch_raw(FOO_NS1.name, FOO_NS1.trust_anchors, key, Options::default());
+        assert_eq!(store.supported_signature_schemes(), expected);
+    }
+
+    // Keys sign with the first of these schemes that the peer offers.
+    let (mut store, rx) = watch(&FOO_NS1, Options::default());
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    let schemes = store.supported_signature_schemes();
+    let offered = [RSA_PSS_SHA256, ED25519, ECDSA_NISTP256_SHA256];
+    let key = rx
+        .client_config()
+        .client_auth_cert_resolver
+        .resolve(&[], &offered)
+        .expect("certificate must be resolved");
+    let signer = key
+        .key
+        .choose_scheme(&offered)
+        .expect("scheme must be supported");
+    assert_eq!(signer.scheme(), schemes[0]);
+}
+
+#[test]
+fn signatures_may_use_injected_randomness() {
+    const SCHEME: rustls::SignatureScheme = rustls::SignatureScheme::ECDSA_NISTP256_SHA256;
+
+    let sign = |byte: Option<u8>| {
+        let (mut store, rx) = watch(&FOO_NS1, Options::default());
+        if let Some(byte) = byte {
+            store.set_rng_for_test(Arc::new(ring::test::rand::FixedByteRandom { byte }));
+        }
+        store
+            .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+            .expect("certificate must be valid");
+        let key = rx
+            .client_config()
+            .client_auth_cert_resolver
+            .resolve(&[], &[SCHEME])
+            .expect("certificate must be resolved");
+        key.key
+            .choose_scheme(&[SCHEME])
+            .expect("scheme must be supported")
+            .sign(b"message")
+            .expect("message must be signed")
+    };
+
+    // Signatures are reproducible with a fixed source of randomness...
+    let fixed = sign(Some(7));
+    assert_eq!(sign(Some(7)), fixed);
+    assert_ne!(sign(Some(8)), fixed);
+    let key = ring::signature::EcdsaKeyPair::from_pkcs8(
+        &ring::signature::ECDSA_P256_SHA256_ASN1_SIGNING,
+        FOO_NS1.key,
+    )
+    .unwrap();
+    ring::signature::UnparsedPublicKey::new(
+        &ring::signature::ECDSA_P256_SHA256_ASN1,
+        ring::signature::KeyPair::public_key(&key),
+    )
+    .verify(b"message", &fixed)
+    .expect("signature must be valid");
+
+    // ...but not by default.
+    assert_ne!(sign(None), sign(None));
+}
+
+#[test]
+fn keys_sign_repeated_handshakes() {
+    for (key, crt) in [
+        (FOO_NS1.key, FOO_NS1.crt),
+        (FOO_NS1_P384_KEY, FOO_NS1_P384),
+        (FOO_NS1_RSA_KEY, FOO_NS1_RSA),
+    ] {
+        let (mut store, rx) =
+            watch_raw(FOO_NS1.name, FOO_NS1.trust_anchors, key, Options::default());
+        store
+            .set_certificate(DerX509(crt.to_vec()), vec![], expiry())
+            .expect("certificate must be valid");
+
+        // Each handshake signs with the key as both the server and the client.
+        for _ in 0..8 {
+            let (_, server) =
+                handshake(rx.new_client().config(), FOO_NS1.name, rx.server().config())
+                    .expect("handshake must succeed");
+            assert!(server.peer_certificates().is_some());
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rsa_signatures_may_be_offloaded() {
+    let options = Options {
+        offload_rsa_signatures: true,
+        ..Options::default()
+    };
+    let (mut store, rx) = watch_raw(
+        FOO_NS1.name,
+        FOO_NS1.trust_anchors,
+        FOO_NS1_RSA_KEY,
+        options,
+    );
+    store
+        .set_certificate(DerX509(FOO_NS1_RSA.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    let connect = |rx: Receiver| {
+        handshake(rx.new_client().config(), FOO_NS1.name, rx.server().config()).map(|_| ())
+    };
+
+    // Signatures are offloaded on the runtime's workers...
+    let rx0 = rx.clone();
+    tokio::spawn(async move { connect(rx0) })
+        .await
+        .expect("task must not panic")
+        .expect("handshake must succeed");
+
+    // ...and are computed inline on current-thread runtimes and outside of a
+    // runtime.
+    let rx0 = rx.clone();
+    std::thread::spawn(move || {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .expect("runtime must build")
+            .block_on(async move { connect(rx0) })
+    })
+    .join()
+    .expect("thread must not panic")
+    .expect("handshake must succeed");
+    std::thread::spawn(move || connect(rx))
+        .join()
+        .expect("thread must not panic")
+        .expect("handshake must succeed");
+}
+
+#[test]
+fn peer_lifetimes_may_be_limited() {
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    let (mut foo, foo_rx) = watch(&FOO_NS1, Options::default());
+    let (mut bar, bar_rx) = watch(&BAR_NS1, Options::default());
+    foo.set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    bar.set_certificate(DerX509(BAR_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+
+    // The test certificates are valid for 3648 days.
+    for (max, ok) in [(3648 * DAY, true), (3647 * DAY, false)] {
+        let options = Options {
+            max_peer_lifetime: Some(max),
+            ..Options::default()
+        };
+
+        // Clients verify servers...
+        let (mut limited, limited_rx) = watch(&FOO_NS1, options.clone());
+        limited
+            .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+            .expect("certificate must be valid");
+        let res = handshake(
+            limited_rx.new_client().config(),
+            BAR_NS1.name,
+            bar_rx.server().config(),
+        );
+        assert_eq!(res.is_ok(), ok, "{:?}", res.err());
+
+        // ...and servers verify clients.
+        let (mut limited, limited_rx) = watch(&BAR_NS1, options);
+        limited
+            .set_certificate(DerX509(BAR_NS1.crt.to_vec()), vec![], expiry())
+            .expect("certificate must be valid");
+        let res = handshake(
+            foo_rx.new_client().config(),
+            BAR_NS1.name,
+            limited_rx.server().config(),
+        );
+        assert_eq!(res.is_ok(), ok, "{:?}", res.err());
+    }
+}
+
+#[test]
+fn peer_intermediates_may_be_limited() {
+    // Foo presents a leaf issued by int-b, and int-b.
+    let chain = || {
+        (
+            DerX509(FOO_NS1_INT_B.to_vec()),
+            vec![DerX509(pem_to_der(INT_B))],
+        )
+    };
+    let (mut foo, foo_rx) = watch(&FOO_NS1, Options::default());
+    let (leaf, intermediates) = chain();
+    foo.set_certificate(leaf, intermediates, expiry())
+        .expect("certificate must be valid");
+
+    let too_many = |res: Result<_, rustls::Error>| match res {
+        Err(rustls::Error::InvalidCertificate(rustls::CertificateError::Other(e))) => {
+            e.is::<crate::creds::TooManyIntermediates>()
+        }
+        _ => false,
+    };
+
+    // Chains below and at the limit are accepted; those above it are not.
+    for (max, ok) in [(2, true), (1, true), (0, false)] {
+        let options = Options {
+            max_intermediates: Some(max),
+            ..Options::default()
+        };
+
+        // Clients verify servers...
+        let (mut bar, bar_rx) = watch(&BAR_NS1, options.clone());
+        bar.set_certificate(DerX509(BAR_NS1.crt.to_vec()), vec![], expiry())
+            .expect("certificate must be valid");
+        let res = handshake(
+            bar_rx.new_client().config(),
+            FOO_NS1.name,
+            foo_rx.server().config(),
+        );
+        assert_eq!(res.is_ok(), ok, "{:?}", res.as_ref().err());
+        assert_eq!(too_many(res), !ok);
+
+        // ...and servers verify clients.
+        let res = handshake(
+            foo_rx.new_client().config(),
+            BAR_NS1.name,
+            bar_rx.server().config(),
+        );
+        assert_eq!(res.is_ok(), ok, "{:?}", res.as_ref().err());
+        assert_eq!(too_many(res), !ok);
+    }
+
+    // The limit may also be set by the builder.
+    let (mut bar, bar_rx) = CredentialsBuilder::new()
+        .identity(BAR_NS1.name.parse().unwrap())
+        .trust_roots_pem(std::str::from_utf8(BAR_NS1.trust_anchors).unwrap())
+        .key_pkcs8(BAR_NS1.key)
+        .csr(BAR_NS1.csr.to_vec())
+        .max_intermediates(0)
+        .build()
+        .expect("credentials must be built");
+    bar.set_certificate(DerX509(BAR_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    let res = handshake(
+        bar_rx.new_client().config(),
+        FOO_NS1.name,
+        foo_rx.server().config(),
+    );
+    assert!(too_many(res));
+}
+
+#[test]
+fn intermediates_may_be_pinned() {
+    let int_a = pem_to_der(INT_A);
+    let int_b = pem_to_der(INT_B);
+    let pin_a = crate::creds::spki_sha256(&int_a).expect("certificate must be valid");
+    let pin_b = crate::creds::spki_sha256(&int_b).expect("certificate must be valid");
+
+    // One foo presents a leaf issued by int-b, and int-b; the other presents a
+    // leaf issued by ca1.
+    let (mut via_int_b, via_int_b_rx) = watch(&FOO_NS1, Options::default());
+    via_int_b
+        .set_certificate(
+            DerX509(FOO_NS1_INT_B.to_vec()),
+            vec![DerX509(int_b)],
+            expiry(),
+        )
+        .expect("certificate must be valid");
+    let (mut direct, direct_rx) = watch(&FOO_NS1, Options::default());
+    direct
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+
+    let not_pinned = |res: &Result<_, rustls::Error>| match res {
+        Err(rustls::Error::InvalidCertificate(rustls::CertificateError::Other(e))) => {
+            e.is::<crate::creds::IntermediateNotPinned>()
+        }
+        _ => false,
+    };
+
+    for (pins, via_int_b_ok, direct_ok) in [
+        (vec![], true, true),
+        (vec![pin_b], true, false),
+        (vec![pin_a], false, false),
+        (vec![pin_a, pin_b], true, false),
+    ] {
+        let (mut bar, bar_rx) = CredentialsBuilder::new()
+            .identity(BAR_NS1.name.parse().unwrap())
+            .trust_roots_pem(std::str::from_utf8(BAR_NS1.trust_anchors).unwrap())
+            .key_pkcs8(BAR_NS1.key)
+            .csr(BAR_NS1.csr.to_vec())
+            .pinned_intermediates(pins.clone())
+            .build()
+            .expect("credentials must be built");
+        bar.set_certificate(DerX509(BAR_NS1.crt.to_vec()), vec![], expiry())
+            .expect("certificate must be valid");
+
+        for (foo_rx, ok) in [(&via_int_b_rx, via_int_b_ok), (&direct_rx, direct_ok)] {
+            // Clients verify servers...
+            let res = handshake(
+                bar_rx.new_client().config(),
+                FOO_NS1.name,
+                foo_rx.server().config(),
+            );
+            assert_eq!(res.is_ok(), ok, "{:?}: {:?}", pins, res.as_ref().err());
+            assert_eq!(not_pinned(&res), !ok);
+
+            // ...and servers verify clients.
+            let res = handshake(
+                foo_rx.new_client().config(),
+                BAR_NS1.name,
+                bar_rx.server().config(),
+            );
+            assert_eq!(res.is_ok(), ok, "{:?}: {:?}", pins, res.as_ref().err());
+            assert_eq!(not_pinned(&res), !ok);
+        }
+    }
+}
+
+#[test]
+fn servers_may_advertise_curated_client_ca_hints() {
+    /// Records the issuers that servers advertise to the client.
+    struct Recording {
+        inner: Arc<dyn rustls::client::ResolvesClientCert>,
+        issuers: parking_lot::Mutex<Vec<Vec<u8>>>,
+    }
+
+    impl rustls::client::ResolvesClientCert for Recording {
+        fn resolve(
+            &self,
+            acceptable_issuers: &[&[u8]],
+            sigschemes: &[rustls::SignatureScheme],
+        ) -> Option<Arc<rustls::sign::CertifiedKey>> {
+            *self.issuers.lock() = acceptable_issuers.iter().map(|i| i.to_vec()).collect();
+            self.inner.resolve(acceptable_issuers, sigschemes)
+        }
+
+        fn has_certs(&self) -> bool {
+            self.inner.has_certs()
+        }
+    }
+
+    let names_early_root = |name: &[u8]| name.windows(10).any(|w| w == b"early-root");
+
+    let (mut foo, foo_rx) = watch(&FOO_NS1, Options::default());
+    foo.set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+
+    // Returns the issuers that bar's server advertises to foo's client, once
+    // foo has authenticated.
+    let advertised = |bar_rx: &Receiver| {
+        let recording = Arc::new(Recording {
+            inner: foo_rx.client_config().client_auth_cert_resolver.clone(),
+            issuers: parking_lot::Mutex::new(Vec::new()),
+        });
+        let mut config = (*foo_rx.client_config()).clone();
+        config.client_auth_cert_resolver = recording.clone();
+        let (_, server) = handshake(Arc::new(config), BAR_NS1.name, bar_rx.server_config())
+            .expect("handshake must succeed");
+        let issuers = recording.issuers.lock().clone();
+        assert!(server.peer_certificates().is_some());
+        issuers
+    };
+
+    // By default, the subject of each trust root is advertised.
+    let (mut bar, bar_rx) = watch(&BAR_NS1, Options::default());
+    bar.set_certificate(DerX509(BAR_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    let issuers = advertised(&bar_rx);
+    assert_eq!(issuers.len(), 1);
+    assert!(!names_early_root(&issuers[0]));
+
+    // Curated hints are advertised instead, but clients are still verified
+    // against the trust roots.
+    for (hints, expected) in [(vec![pem_to_der(EARLY_ROOT)], 1), (vec![], 0)] {
+        let (mut bar, bar_rx) = CredentialsBuilder::new()
+            .identity(BAR_NS1.name.parse().unwrap())
+            .trust_roots_pem(std::str::from_utf8(BAR_NS1.trust_anchors).unwrap())
+            .key_pkcs8(BAR_NS1.key)
+            .csr(BAR_NS1.csr.to_vec())
+            .client_ca_hints(hints)
+            .build()
+            .expect("credentials must be built");
+        bar.set_certificate(DerX509(BAR_NS1.crt.to_vec()), vec![], expiry())
+            .expect("certificate must be valid");
+        let issuers = advertised(&bar_rx);
+        assert_eq!(issuers.len(), expected);
+        assert!(issuers.iter().all(|i| names_early_root(i)));
+    }
+
+    // Hints must be certificates.
+    let err = CredentialsBuilder::new()
+        .identity(BAR_NS1.name.parse().unwrap())
+        .trust_roots_pem(std::str::from_utf8(BAR_NS1.trust_anchors).unwrap())
+        .key_pkcs8(BAR_NS1.key)
+        .csr(BAR_NS1.csr.to_vec())
+        .client_ca_hints(vec![b"not a certificate".to_vec()])
+        .build()
+        .err()
+        .expect("invalid hints are rejected");
+    assert!(err.is::<crate::creds::InvalidField>(), "{}", err);
+    assert!(err.to_string().contains("client CA hints"), "{}", err);
+}
+
+#[test]
+fn peer_signature_algorithms_may_be_restricted() {
+    // Foo presents a leaf signed with ecdsa-with-SHA384.
+    let (mut foo, foo_rx) = watch(&FOO_NS1, Options::default());
+    foo.set_certificate(DerX509(FOO_NS1_SHA384.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+
+    for (allowed, ok) in [
+        (
+            vec![
+                SignatureAlgorithm::EcdsaSha256,
+                SignatureAlgorithm::EcdsaSha384,
+            ],
+            true,
+        ),
+        (vec![SignatureAlgorithm::EcdsaSha256], false),
+    ] {
+        let (mut bar, bar_rx) = watch(
+            &BAR_NS1,
+            Options {
+                signature_algorithms: Some(allowed),
+                ..Options::default()
+            },
+        );
+        bar.set_certificate(DerX509(BAR_NS1.crt.to_vec()), vec![], expiry())
+            .expect("the store's own certificate is not restricted");
+
+        // Clients verify servers...
+        let res = handshake(
+            bar_rx.new_client().config(),
+            FOO_NS1.name,
+            foo_rx.server().config(),
+        );
+        assert_eq!(res.is_ok(), ok, "{:?}", res.err());
+
+        // ...and servers verify clients.
+        let res = handshake(
+            foo_rx.new_client().config(),
+            BAR_NS1.name,
+            bar_rx.server().config(),
+        );
+        assert_eq!(res.is_ok(), ok, "{:?}", res.err());
+    }
+
+    let err = crate::creds::watch_with_options(
+        BAR_NS1.name.parse().unwrap(),
+        std::str::from_utf8(BAR_NS1.trust_anchors).unwrap(),
+        BAR_NS1.key,
+        BAR_NS1.csr,
+        Options {
+            signature_algorithms: Some(vec![]),
+            ..Options::default()
+        },
+    )
+    .err()
+    .expect("an empty set of algorithms must be rejected");
+    assert!(err.is::<crate::creds::NoSignatureAlgorithms>(), "{}", err);
+}
+
+/// Returns the OCSP response that `rx`'s server staples, which is empty if
+/// none is stapled.
+fn stapled_ocsp(rx: &Receiver) -> Vec<u8> {
+    /// Accepts any server certificate, recording the stapled OCSP response.
+    #[derive(Default)]
+    struct CaptureOcsp(parking_lot::Mutex<Option<Vec<u8>>>);
+
+    impl rustls::client::ServerCertVerifier for CaptureOcsp {
+        fn verify_server_cert(
+            &self,
+            _: &rustls::Certificate,
+            _: &[rustls::Certificate],
+            _: &rustls::ServerName,
+            _: &mut dyn Iterator<Item = &[u8]>,
+            ocsp_response: &[u8],
+            _: std::time::SystemTime,
+        ) -> Result<rustls::client::ServerCertVerified, rustls::Error> {
+            *self.0.lock() = Some(ocsp_response.to_vec());
+            Ok(rustls::client::ServerCertVerified::assertion())
+        }
+    }
+
+    let capture = Arc::new(CaptureOcsp::default());
+    let client = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(capture.clone())
+        .with_no_client_auth();
+    handshake(Arc::new(client), FOO_NS1.name, rx.server().config())
+        .expect("handshake must succeed");
+    let ocsp = capture.0.lock().take();
+    ocsp.expect("server must be verified")
+}
+
+#[test]
+fn provided_ocsp_responses_are_stapled() {
+    let (mut store, rx) = watch(&FOO_NS1, Options::default());
+    let installed = store.install_certificate_with_ocsp(
+        DerX509(FOO_NS1.crt.to_vec()),
+        vec![],
+        Some(b"fake OCSP response".to_vec()),
+        expiry(),
+    );
+    assert!(matches!(installed, Install::Published), "{:?}", installed);
+    assert_eq!(stapled_ocsp(&rx), b"fake OCSP response");
+
+    // Each rotation replaces the response.
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    assert!(stapled_ocsp(&rx).is_empty());
+}
+
+#[test]
+fn servers_with_revoked_ocsp_staples_are_rejected() {
+    let (mut client, client_rx) = watch(&BAR_NS1, Options::default());
+    client
+        .set_certificate(DerX509(BAR_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+
+    for (ocsp, ok) in [
+        (None, true),
+        (Some(FOO_NS1_OCSP_GOOD), true),
+        (Some(&b"not an OCSP response"[..]), true),
+        (Some(FOO_NS1_OCSP_REVOKED), false),
+    ] {
+        let (mut server, server_rx) = watch(&FOO_NS1, Options::default());
+        let installed = server.install_certificate_with_ocsp(
+            DerX509(FOO_NS1.crt.to_vec()),
+            vec![],
+            ocsp.map(<[u8]>::to_vec),
+            expiry(),
+        );
+        assert!(matches!(installed, Install::Published), "{:?}", installed);
+
+        let res = handshake(
+            client_rx.new_client().config(),
+            FOO_NS1.name,
+            server_rx.server().config(),
+        );
+        match (res, ok) {
+            (Ok(_), true) => {}
+            (Err(rustls::Error::InvalidCertificate(rustls::CertificateError::Revoked)), false) => {}
+            (res, _) => panic!("unexpected result: {:?}", res.map(|_| ())),
+        }
+    }
+}
+
+/// Responds to fetches for `FOO_NS1_OCSP` with a fixed OCSP response that is
+/// fresh for an hour, or fails when none is set.
+struct MockOcspResponder {
+    response: Option<&'static [u8]>,
+    fetches: AtomicUsize,
+}
+
+impl MockOcspResponder {
+    fn new(response: Option<&'static [u8]>) -> Arc<Self> {
+        Arc::new(Self {
+            response,
+            fetches: AtomicUsize::new(0),
+        })
+    }
+
+    /// Waits for the store's background task to fetch (and staple) a
+    /// response.
+    async fn fetched(&self) {
+        while self.fetches.load(Ordering::SeqCst) == 0 {
+            tokio::task::yield_now().await;
+        }
+        tokio::task::yield_now().await;
+    }
+}
+
+impl crate::creds::OcspFetcher for MockOcspResponder {
+    fn fetch(
+        &self,
+        responder: &str,
+        chain: &[rustls::Certificate],
+    ) -> std::pin::Pin<
+        Box<
+            dyn std::future::Future<Output = linkerd_error::Result<crate::creds::OcspResponse>>
+                + Send
+                + 'static,
+        >,
+    > {
+        assert_eq!(responder, "http://ocsp.example.com");
+        assert_eq!(chain[0].as_ref(), FOO_NS1_OCSP);
+        self.fetches.fetch_add(1, Ordering::SeqCst);
+        let rsp: linkerd_error::Result<_> = self
+            .response
+            .map(|der| crate::creds::OcspResponse {
+                der: der.to_vec(),
+                next_update: std::time::SystemTime::now() + Duration::from_secs(3600),
+            })
+            .ok_or_else(|| "responder unavailable".into());
+        Box::pin(async move { rsp })
+    }
+}
+
+#[tokio::test]
+async fn ocsp_responses_are_stapled_once_fetched() {
+    async fn stapled(response: Option<&'static [u8]>) -> (Vec<u8>, usize) {
+        let fetcher = MockOcspResponder::new(response);
+        let (mut store, rx) = watch(
+            &FOO_NS1,
+            Options {
+                ocsp_fetcher: Some(fetcher.clone()),
+                ..Options::default()
+            },
+        );
+        store
+            .set_certificate(DerX509(FOO_NS1_OCSP.to_vec()), vec![], expiry())
+            .expect("certificate must be valid");
+        fetcher.fetched().await;
+
+        (stapled_ocsp(&rx), fetcher.fetches.load(Ordering::SeqCst))
+    }
+
+    let (ocsp, fetches) = stapled(Some(FOO_NS1_OCSP_LEAF_GOOD)).await;
+    assert_eq!(ocsp, FOO_NS1_OCSP_LEAF_GOOD);
+    assert_eq!(fetches, 1);
+
+    // Fetch failures fail open.
+    let (ocsp, _) = stapled(None).await;
+    assert!(ocsp.is_empty());
+
+    // Only responses that report the certificate as good are stapled.
+    for response in [
+        FOO_NS1_OCSP_LEAF_REVOKED,
+        FOO_NS1_OCSP_GOOD,
+        &b"not an OCSP response"[..],
+    ] {
+        let (ocsp, _) = stapled(Some(response)).await;
+        assert!(ocsp.is_empty());
+    }
+}
+
+#[tokio::test]
+async fn stapled_ocsp_responses_go_stale_by_the_configured_clock() {
+    let now = Arc::new(parking_lot::Mutex::new(std::time::SystemTime::now()));
+    let fetcher = MockOcspResponder::new(Some(FOO_NS1_OCSP_LEAF_GOOD));
+    let (mut store, rx) = watch(&FOO_NS1, {
+        let now = now.clone();
+        Options {
+            clock: Some(Arc::new(move || *now.lock())),
+            ocsp_fetcher: Some(fetcher.clone()),
+            ..Options::default()
+        }
+    });
+    store
+        .set_certificate(DerX509(FOO_NS1_OCSP.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    fetcher.fetched().await;
+    assert_eq!(stapled_ocsp(&rx), FOO_NS1_OCSP_LEAF_GOOD);
+
+    // Once the response's next update has passed, it is no longer stapled.
+    *now.lock() += Duration::from_secs(2 * 60 * 60);
+    assert!(stapled_ocsp(&rx).is_empty());
+}
+
+#[test]
+fn ordered_chains_are_presented_verbatim() {
+    let (mut store, rx) = watch(&FOO_NS1, Options::default());
+    let chain = vec![
+        FOO_NS1.crt.to_vec(),
+        pem_to_der(FOO_NS1_CA2.trust_anchors),
+        pem_to_der(FOO_NS1.trust_anchors),
+    ];
+    let installed = store.install_chain(chain.iter().cloned().map(DerX509).collect(), expiry());
+    assert!(matches!(installed, Install::Published), "{:?}", installed);
+
+    let (client, _) = handshake(rx.new_client().config(), FOO_NS1.name, rx.server().config())
+        .expect("handshake must succeed");
+    let presented = client
+        .peer_certificates()
+        .expect("server must present certificates")
+        .iter()
+        .map(|c| c.0.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(presented, chain);
+}
+
+#[test]
+fn installed_chains_must_be_ordered_paths_to_a_root() {
+    let (mut store, _rx) = watch(&FOO_NS1, Options::default());
+    let leaf = DerX509(FOO_NS1_INT_B.to_vec());
+    let int_a = DerX509(pem_to_der(INT_A));
+    let int_b = DerX509(pem_to_der(INT_B));
+
+    let err = store
+        .set_certificate(leaf.clone(), vec![int_a.clone(), int_b.clone()], expiry())
+        .expect_err("misordered chains must be rejected");
+    assert!(err.is::<crate::creds::MisorderedChain>(), "{}", err);
+    assert_eq!(
+        err.to_string(),
+        "certificate 0 in the chain is not issued by the certificate after it; its issuer is \"OU=int-b\""
+    );
+
+    let err = store
+        .set_certificate(leaf.clone(), vec![], expiry())
+        .expect_err("chains lacking an intermediate must be rejected");
+    assert!(err.is::<crate::creds::UntrustedChain>(), "{}", err);
+    assert_eq!(
+        err.to_string(),
+        "certificate 0 in the chain is not issued by a trust root; its issuer is \"OU=int-b\""
+    );
+
+    store
+        .set_certificate(leaf.clone(), vec![int_b.clone()], expiry())
+        .expect("ordered chains must be accepted");
+    let root = DerX509(pem_to_der(FOO_NS1.trust_anchors));
+    store
+        .set_certificate(leaf.clone(), vec![int_b.clone(), root], expiry())
+        .expect("chains may end with a trust root");
+
+    // Chains that are presented verbatim may be in any order.
+    let installed = store.install_chain(vec![leaf, int_a, int_b], expiry());
+    assert!(matches!(installed, Install::Published), "{:?}", installed);
+}
+
+#[test]
+fn resolver_decisions_are_traced_when_enabled() {
+    for trace_resolver in [false, true] {
+        let (mut store, rx) = watch(
+            &FOO_NS1,
+            Options {
+                trace_resolver,
+                ..Options::default()
+            },
+        );
+        store
+            .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+            .expect("certificate must be valid");
+
+        let messages = messages(tracing::Level::TRACE, || {
+            handshake(rx.new_client().config(), FOO_NS1.name, rx.server().config())
+                .expect("handshake must succeed");
+        });
+        let count = |message: &str| messages.iter().filter(|m| *m == message).count();
+
+        let expected = usize::from(trace_resolver);
+        assert_eq!(count("Resolved client certificate"), expected);
+        assert_eq!(count("Resolved server certificate"), expected);
+    }
+}
+
+#[test]
+fn sni_mismatches_are_counted() {
+    let metrics = Metrics::default();
+    let (mut store, rx) = watch(
+        &FOO_NS1,
+        Options {
+            metrics: metrics.clone(),
+            ..Options::default()
+        },
+    );
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+
+    handshake(rx.new_client().config(), FOO_NS1.name, rx.server().config())
+        .expect("handshake must succeed");
+    assert_eq!(store.sni_mismatches(), 0);
+
+    let messages = messages(tracing::Level::DEBUG, || {
+        handshake(rx.new_client().config(), BAR_NS1.name, rx.server().config())
+            .expect_err("server must not present a certificate for another name");
+    });
+    assert!(messages
+        .iter()
+        .any(|m| m == "Local certificate is not valid for SNI"));
+    assert_eq!(store.sni_mismatches(), 1);
+    let text = metrics.as_display().to_string();
+    assert!(
+        text.contains("identity_sni_mismatches_total 1\n"),
+        "missing mismatch count in:\n{}",
+        text
+    );
+}
+
+#[test]
+fn deprecated_trust_domains_are_accepted_while_migrating() {
+    let client = |until| {
+        let (_, rx) = watch(
+            &FOO_NS1,
+            Options {
+                trust_domain_migration: Some(TrustDomainMigration {
+                    trust_domain: "cluster.local".to_string(),
+                    deprecated: vec!["old.example".to_string()],
+                    until,
+                }),
+                ..Options::default()
+            },
+        );
+        rx.new_client().config()
+    };
+    // A server that presents `crt` regardless of SNI.
+    let server = |crt: &[u8]| {
+        let config = rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![rustls::Certificate(crt.to_vec())],
+                rustls::PrivateKey(FOO_NS1.key.to_vec()),
+            )
+            .expect("server config must be valid");
+        Arc::new(config)
+    };
+    let connect = |client, crt| {
+        let mut result = None;
+        let warnings = messages(tracing::Level::WARN, || {
+            result = Some(handshake(client, FOO_NS1.name, server(crt)).map(|_| ()));
+        });
+        let deprecated = warnings
+            .iter()
+            .any(|m| m.contains("deprecated trust domain"));
+        (result.unwrap(), deprecated)
+    };
+
+    let migrating = client(std::time::SystemTime::now() + Duration::from_secs(3600));
+    let (result, deprecated) = connect(migrating.clone(), FOO_NS1.crt);
+    assert!(result.is_ok() && !deprecated, "{:?}", result);
+    let (result, deprecated) = connect(migrating.clone(), FOO_NS1_OLD_DOMAIN);
+    assert!(result.is_ok() && deprecated, "{:?}", result);
+    let (result, _) = connect(migrating, FOO_NS1_UNKNOWN_DOMAIN);
+    assert!(result.is_err());
+
+    let migrated = client(std::time::SystemTime::now() - Duration::from_secs(1));
+    let (result, _) = connect(migrated.clone(), FOO_NS1.crt);
+    assert!(result.is_ok(), "{:?}", result);
+    let (result, _) = connect(migrated, FOO_NS1_OLD_DOMAIN);
+    assert!(result.is_err());
+}
+
+#[test]
+fn bootstrap_name_is_served_before_provisioning() {
+    /// Accepts any server certificate, so that the self-signed certificate
+    /// may be inspected.
+    struct AcceptAny;
+
+    impl rustls::client::ServerCertVerifier for AcceptAny {
+        fn verify_server_cert(
+            &self,
+            _: &rustls::Certificate,
+            _: &[rustls::Certificate],
+            _: &rustls::ServerName,
+            _: &mut dyn Iterator<Item = &[u8]>,
+            _: &[u8],
+            _: std::time::SystemTime,
+        ) -> Result<rustls::client::ServerCertVerified, rustls::Error> {
+            Ok(rustls::client::ServerCertVerified::assertion())
+        }
+    }
+
+    const BOOTSTRAP: &str = "bootstrap.linkerd.cluster.local";
+    let (mut store, rx) = watch(
+        &FOO_NS1,
+        Options {
+            bootstrap_server_name: Some(BOOTSTRAP.to_string()),
+            ..Options::default()
+        },
+    );
+    let client = Arc::new(
+        rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_custom_certificate_verifier(Arc::new(AcceptAny))
+            .with_no_client_auth(),
+    );
+
+    let (conn, _) = handshake(client.clone(), BOOTSTRAP, rx.server().config())
+        .expect("the bootstrap name must be served");
+    let presented = conn
+        .peer_certificates()
+        .expect("a certificate must be presented")[0]
+        .clone();
+    assert_ne!(presented.as_ref(), FOO_NS1.crt);
+    webpki::EndEntityCert::try_from(presented.as_ref())
+        .expect("certificate must be valid")
+        .verify_is_valid_for_subject_name(webpki::SubjectNameRef::DnsName(
+            webpki::DnsNameRef::try_from_ascii_str(BOOTSTRAP).unwrap(),
+        ))
+        .expect("certificate must name the bootstrap name");
+    assert!(handshake(client.clone(), FOO_NS1.name, rx.server().config()).is_err());
+
+    // Once provisioned, only the mesh identity is served.
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+    assert!(handshake(client.clone(), BOOTSTRAP, rx.server().config()).is_err());
+    let (conn, _) =
+        handshake(client, FOO_NS1.name, rx.server().config()).expect("the identity must be served");
+    assert_eq!(conn.peer_certificates().unwrap()[0].as_ref(), FOO_NS1.crt);
+}
+
+#[test]
+fn client_hellos_are_recorded_when_enabled() {
+    let hellos = Arc::new(parking_lot::Mutex::new(Vec::<ClientHelloRecord>::new()));
+    let (mut store, rx) = watch(
+        &FOO_NS1,
+        Options {
+            on_client_hello: Some({
+                let hellos = hellos.clone();
+                Arc::new(move |hello: &ClientHelloRecord| hellos.lock().push(hello.clone()))
+            }),
+            ..Options::default()
+        },
+    );
+    store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .expect("certificate must be valid");
+
+    let mut client = (*rx.new_client().config()).clone();
+    client.alpn_protocols = vec![b"h2".to_vec()];
+    handshake(Arc::new(client), FOO_NS1.name, rx.server().config())
+        .expect("handshake must succeed");
+
+    let hellos = hellos.lock();
+    assert_eq!(hellos.len(), 1);
+    let hello = &hellos[0];
+    assert_eq!(hello.server_name.as_deref(), Some(FOO_NS1.name));
+    assert_eq!(hello.alpn_protocols, Some(vec![b"h2".to_vec()]));
+    assert!(hello
+        .cipher_suites
+        .contains(&rustls::CipherSuite::TLS13_CHACHA20_POLY1305_SHA256));
+    assert!(hello
+        .signature_schemes
+        .contains(&rustls::SignatureScheme::ECDSA_NISTP256_SHA256));
+}
+
+#[test]
+fn trust_domain_mismatches_are_reported() {
+    for (trust_domain, mismatched) in [
+        (None, false),
+        (Some("cluster.local"), false),
+        (Some("linkerd.cluster.local."), false),
+        (Some("example.com"), true),
+        (Some("local.cluster.local"), true),
+    ] {
+        let mut store = load_with_options(
+            &FOO_NS1,
+            Options {
+                trust_domain: trust_domain.map(String::from),
+                ..Options::default()
+            },
+        );
+        let messages = messages(tracing::Level::WARN, || {
+            store
+                .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+                .expect("mismatched certificates must still be installed");
+        });
+        assert_eq!(
+            messages.iter().any(|m| m.contains("expected trust domain")),
+            mismatched,
+            "{:?}: {:?}",
+            trust_domain,
+            messages
+        );
+    }
+}
+
+#[test]
+fn name_constrained_ca_only_issues_permitted_names() {
+    let foo_ns2 = "foo.ns2.serviceaccount.identity.linkerd.cluster.local";
+    let constraint_violated = |error: &rustls::Error| match error {
+        rustls::Error::InvalidCertificate(rustls::CertificateError::Other(e)) => matches!(
+            e.downcast_ref::<webpki::Error>(),
+            Some(webpki::Error::NameConstraintViolation)
+        ),
+        _ => false,
+    };
+
+    let (mut store, rx) = watch_raw(FOO_NS1.name, NS1_CA, FOO_NS1.key, Options::default());
+    store
+        .set_certificate(DerX509(FOO_NS1_NS1_CA.to_vec()), vec![], expiry())
+        .expect("the name is permitted");
+
+    let (mut ns2_store, _) = watch_raw(foo_ns2, NS1_CA, FOO_NS1.key, Options::default());
+    let error = ns2_store
+        .set_certificate(DerX509(FOO_NS2_NS1_CA.to_vec()), vec![], expiry())
+        .expect_err("the name is not permitted");
+    assert!(
+        error
+            .downcast_ref::<rustls::Error>()
+            .map_or(false, constraint_violated),
+        "{}",
+        error
+    );
+
+    // Peers that present such a certificate are rejected, too.
+    let server = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![rustls::Certificate(FOO_NS2_NS1_CA.to_vec())],
+            rustls::PrivateKey(FOO_NS1.key.to_vec()),
+        )
+        .expect("server config must be valid");
+    let error = handshake(rx.new_client().config(), foo_ns2, Arc::new(server))
+        .expect_err("the server's name is not permitted");
+    assert!(constraint_violated(&error), "{}", error);
+}
+
+#[test]
+fn install_distinguishes_publish_outcomes() {
+    let (mut store, rx) = watch(&FOO_NS1, Options::default());
+    assert!(matches!(
+        store.install_certificate(DerX509(BAR_NS1.crt.to_vec()), vec![], expiry()),
+        Install::ValidationFailed(_)
+    ));
+    assert!(matches!(
+        store.install_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry()),
+        Install::Published
+    ));
+
+    drop(rx);
+    assert!(matches!(
+        store.install_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry()),
+        Install::NoReceivers
+    ));
+    // Credentials callers aren't affected by the lack of receivers.
+    assert!(store
+        .set_certificate(DerX509(FOO_NS1.crt.to_vec()), vec![], expiry())
+        .is_ok());
 }
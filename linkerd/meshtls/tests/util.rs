@@ -117,12 +117,7 @@ fn load(
 ) -> (meshtls::creds::Store, meshtls::NewClient, meshtls::Server) {
     let roots_pem = std::str::from_utf8(ent.trust_anchors).expect("valid PEM");
     let (mut store, rx) = mode
-        .watch(
-            ent.name.parse().unwrap(),
-            roots_pem,
-            ent.key,
-            b"fake CSR data",
-        )
+        .watch(ent.name.parse().unwrap(), roots_pem, ent.key, ent.csr)
         .expect("credentials must be readable");
 
     let expiry = std::time::SystemTime::now() + Duration::from_secs(600);
@@ -6,6 +6,8 @@ pub struct Entity {
     pub trust_anchors: &'static [u8],
     pub crt: &'static [u8],
     pub key: &'static [u8],
+    /// A PEM-encoded certificate signing request for `name` and `key`.
+    pub csr: &'static [u8],
 }
 
 pub static DEFAULT_DEFAULT: Entity = Entity {
@@ -13,6 +15,7 @@ pub static DEFAULT_DEFAULT: Entity = Entity {
     trust_anchors: include_bytes!("testdata/ca1.pem"),
     crt: include_bytes!("testdata/default-default-ca1/crt.der"),
     key: include_bytes!("testdata/default-default-ca1/key.p8"),
+    csr: include_bytes!("testdata/default-default-ca1/csr.pem"),
 };
 
 pub static FOO_NS1: Entity = Entity {
@@ -20,6 +23,7 @@ pub static FOO_NS1: Entity = Entity {
     trust_anchors: include_bytes!("testdata/ca1.pem"),
     crt: include_bytes!("testdata/foo-ns1-ca1/crt.der"),
     key: include_bytes!("testdata/foo-ns1-ca1/key.p8"),
+    csr: include_bytes!("testdata/foo-ns1-ca1/csr.pem"),
 };
 
 pub static FOO_NS1_CA2: Entity = Entity {
@@ -27,6 +31,7 @@ pub static FOO_NS1_CA2: Entity = Entity {
     trust_anchors: include_bytes!("testdata/ca2.pem"),
     crt: include_bytes!("testdata/foo-ns1-ca2/crt.der"),
     key: include_bytes!("testdata/foo-ns1-ca2/key.p8"),
+    csr: include_bytes!("testdata/foo-ns1-ca2/csr.pem"),
 };
 
 pub static BAR_NS1: Entity = Entity {
@@ -34,4 +39,24 @@ pub static BAR_NS1: Entity = Entity {
     trust_anchors: include_bytes!("testdata/ca1.pem"),
     crt: include_bytes!("testdata/bar-ns1-ca1/crt.der"),
     key: include_bytes!("testdata/bar-ns1-ca1/key.p8"),
+    csr: include_bytes!("testdata/bar-ns1-ca1/csr.pem"),
+};
+
+/// The same identity and key as `FOO_NS1`, with a certificate that expired at
+/// 2021-01-01T00:00:00Z.
+pub static FOO_NS1_EXPIRED: Entity = Entity {
+    name: "foo.ns1.serviceaccount.identity.linkerd.cluster.local",
+    trust_anchors: include_bytes!("testdata/ca1.pem"),
+    crt: include_bytes!("testdata/foo-ns1-expired-ca1/crt.der"),
+    key: include_bytes!("testdata/foo-ns1-ca1/key.p8"),
+    csr: include_bytes!("testdata/foo-ns1-ca1/csr.pem"),
+};
+
+/// The same identity as `FOO_NS1`, with an Ed25519 key.
+pub static FOO_NS1_ED25519: Entity = Entity {
+    name: "foo.ns1.serviceaccount.identity.linkerd.cluster.local",
+    trust_anchors: include_bytes!("testdata/ca1.pem"),
+    crt: include_bytes!("testdata/foo-ns1-ed25519-ca1/crt.der"),
+    key: include_bytes!("testdata/foo-ns1-ed25519-ca1/key.p8"),
+    csr: include_bytes!("testdata/foo-ns1-ed25519-ca1/csr.pem"),
 };
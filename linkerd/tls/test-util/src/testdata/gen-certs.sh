@@ -58,3 +58,39 @@ ee ca1 default default linkerd
 ee ca1 foo ns1 linkerd
 ee ca2 foo ns1 linkerd # Same, but different CA
 ee ca1 bar ns1 linkerd # Different service.
+
+# An Ed25519 identity issued by ca1. cfssl can't generate Ed25519 keys, so
+# OpenSSL (3.0+) issues it with the same extensions.
+ee_ed25519() {
+  ee=foo-ns1-ed25519-ca1
+  hostname=foo.ns1.serviceaccount.identity.linkerd.cluster.local
+  mkdir -p "${ee}"
+
+  openssl genpkey -algorithm ED25519 -out "${ee}-key.pem"
+  openssl pkcs8 -topk8 -nocrypt -inform pem -outform der \
+    -in "${ee}-key.pem" \
+    -out "${ee}/key.p8"
+
+  printf "[req]\ndistinguished_name=dn\n[dn]\n" > req.cnf
+  openssl req -new -key "${ee}-key.pem" -subj "/CN=${hostname}" -config req.cnf \
+    -out "${ee}/csr.pem"
+  printf "keyUsage=critical,digitalSignature,keyEncipherment\nextendedKeyUsage=serverAuth,clientAuth\nbasicConstraints=critical,CA:FALSE\nsubjectKeyIdentifier=hash\nauthorityKeyIdentifier=keyid\nsubjectAltName=critical,DNS:${hostname}\n" > ext.cnf
+  openssl x509 -req -in "${ee}/csr.pem" -CA ca1.pem -CAkey ca1-key.pem -days 3650 \
+    -extfile ext.cnf -outform der -out "${ee}/crt.der"
+  rm req.cnf ext.cnf "${ee}-key.pem"
+}
+ee_ed25519
+
+# The same identity as foo-ns1-ca1, with a certificate that expired in 2021.
+ee_expired() {
+  ee=foo-ns1-expired-ca1
+  hostname=foo.ns1.serviceaccount.identity.linkerd.cluster.local
+  mkdir -p "${ee}"
+
+  printf "keyUsage=critical,digitalSignature,keyEncipherment\nextendedKeyUsage=serverAuth,clientAuth\nbasicConstraints=critical,CA:FALSE\nsubjectKeyIdentifier=hash\nauthorityKeyIdentifier=keyid\nsubjectAltName=critical,DNS:${hostname}\n" > ext.cnf
+  openssl x509 -req -in foo-ns1-ca1/csr.pem -CA ca1.pem -CAkey ca1-key.pem \
+    -not_before 20200101000000Z -not_after 20210101000000Z \
+    -extfile ext.cnf -outform der -out "${ee}/crt.der"
+  rm ext.cnf
+}
+ee_expired